@@ -0,0 +1,392 @@
+//! The per-alert state machine: given whether an alert's condition is met,
+//! decides whether to fire, remind, hold back or clear. Kept free of I/O so
+//! every transition can be tested on its own.
+
+/// What an alert remembers between checks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlertState {
+    pub last_alerted: Option<u64>,
+    pub repeat_count: u32,
+    /// Since when the condition has been met without interruption.
+    pub met_since: Option<u64>,
+    pub last_reminded: Option<u64>,
+    /// Consecutive checks the condition has been met, counted up to
+    /// `confirm_cycles`.
+    pub met_cycles: u32,
+}
+
+impl AlertState {
+    /// Moves times later than `now`, left behind by a clock that jumped
+    /// backward, to `now`. A fired alert is then held back for a full
+    /// `withold_secs` from now instead of firing again, or of staying held
+    /// back until the clock catches up. Returns whether anything moved.
+    pub fn rewind(&mut self, now: u64) -> bool {
+        let mut moved = false;
+        for time in [
+            &mut self.last_alerted,
+            &mut self.met_since,
+            &mut self.last_reminded,
+        ] {
+            if let Some(time) = time.as_mut().filter(|time| **time > now) {
+                *time = now;
+                moved = true;
+            }
+        }
+        moved
+    }
+}
+
+/// The alert's settings that shape its transitions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlertPolicy {
+    /// Seconds after an alert before it may fire again while still met.
+    pub withold_secs: u64,
+    pub confirm_secs: Option<u64>,
+    /// Consecutive met checks needed before firing.
+    pub confirm_cycles: Option<u32>,
+    pub max_repeats: Option<u32>,
+    pub snoozed_until: Option<u64>,
+    pub reminder_interval_h: Option<u64>,
+    /// When the alert saw its first sample, if a condition met since then
+    /// should never fire (`suppress_initial_crossings`).
+    pub quiet_since_first_sample: Option<u64>,
+}
+
+/// Why a met condition does not fire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suppressed {
+    /// Held for `held` of the `confirm_secs` seconds so far.
+    Confirming { held: u64, confirm: u64 },
+    /// Met on `met` of the `confirm_cycles` consecutive checks so far.
+    ConfirmingCycles { met: u32, needed: u32 },
+    /// Met since the first sample, see `quiet_since_first_sample`.
+    InitialCrossing,
+    /// Acknowledged and snoozed until the given time.
+    Snoozed { until: u64 },
+    /// Already fired `max_repeats` times.
+    RepeatsExhausted { count: u32 },
+    /// Already fired within `withold_secs`.
+    Withheld,
+}
+
+impl Suppressed {
+    /// The `reason` label of the suppression counter.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Suppressed::Confirming { .. } | Suppressed::ConfirmingCycles { .. } => "confirming",
+            Suppressed::InitialCrossing => "initial_crossing",
+            Suppressed::Snoozed { .. } => "snoozed",
+            Suppressed::RepeatsExhausted { .. } => "repeats_exhausted",
+            Suppressed::Withheld => "withheld",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertDecision {
+    Fire,
+    /// Still met and held back, but a reminder is due.
+    Remind(Suppressed),
+    Suppress(Suppressed),
+    /// No longer met after having fired.
+    Clear,
+    /// Not met, and nothing to clear.
+    NoOp,
+}
+
+/// The decision for one check of an alert whose condition is `met` at `now`,
+/// and the state to keep for the next check.
+pub fn evaluate(
+    mut state: AlertState,
+    policy: &AlertPolicy,
+    met: bool,
+    now: u64,
+) -> (AlertDecision, AlertState) {
+    if !met {
+        state.met_since = None;
+        state.met_cycles = 0;
+        if state.last_alerted.is_none() {
+            return (AlertDecision::NoOp, state);
+        }
+        state.last_alerted = None;
+        state.last_reminded = None;
+        state.repeat_count = 0;
+        return (AlertDecision::Clear, state);
+    }
+
+    let since = *state.met_since.get_or_insert(now);
+    if let Some(confirm) = policy.confirm_secs {
+        let held = now.saturating_sub(since);
+        if held < confirm {
+            let reason = Suppressed::Confirming { held, confirm };
+            return (AlertDecision::Suppress(reason), state);
+        }
+    }
+    if let Some(needed) = policy.confirm_cycles {
+        if state.met_cycles < needed {
+            state.met_cycles += 1;
+        }
+        if state.met_cycles < needed {
+            let reason = Suppressed::ConfirmingCycles {
+                met: state.met_cycles,
+                needed,
+            };
+            return (AlertDecision::Suppress(reason), state);
+        }
+    }
+    if policy
+        .quiet_since_first_sample
+        .is_some_and(|first| since <= first)
+    {
+        return (AlertDecision::Suppress(Suppressed::InitialCrossing), state);
+    }
+
+    let withheld = state
+        .last_alerted
+        .is_some_and(|last| now.saturating_sub(last) <= policy.withold_secs);
+    let reason = match policy.snoozed_until {
+        Some(until) if !withheld && now < until => {
+            return (
+                AlertDecision::Suppress(Suppressed::Snoozed { until }),
+                state,
+            )
+        }
+        _ if withheld => Suppressed::Withheld,
+        _ => match policy.max_repeats {
+            Some(max) if state.repeat_count >= max => Suppressed::RepeatsExhausted {
+                count: state.repeat_count,
+            },
+            _ => {
+                state.last_alerted = Some(now);
+                state.last_reminded = None;
+                state.repeat_count += 1;
+                return (AlertDecision::Fire, state);
+            }
+        },
+    };
+
+    let reminder_due = policy.reminder_interval_h.is_some_and(|hours| {
+        state
+            .last_reminded
+            .or(state.last_alerted)
+            .is_some_and(|since| now.saturating_sub(since) >= hours * 3600)
+    });
+    if reminder_due {
+        state.last_reminded = Some(now);
+        return (AlertDecision::Remind(reason), state);
+    }
+    (AlertDecision::Suppress(reason), state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WITHOLD: u64 = 3_600;
+
+    fn policy() -> AlertPolicy {
+        AlertPolicy {
+            withold_secs: WITHOLD,
+            ..Default::default()
+        }
+    }
+
+    fn fired_at(at: u64) -> AlertState {
+        AlertState {
+            last_alerted: Some(at),
+            repeat_count: 1,
+            met_since: Some(at),
+            last_reminded: None,
+            met_cycles: 0,
+        }
+    }
+
+    #[test]
+    fn unmet_without_history_is_a_no_op() {
+        let (decision, state) = evaluate(AlertState::default(), &policy(), false, 1_000);
+        assert_eq!(decision, AlertDecision::NoOp);
+        assert_eq!(state, AlertState::default());
+    }
+
+    #[test]
+    fn first_met_check_fires() {
+        let (decision, state) = evaluate(AlertState::default(), &policy(), true, 1_000);
+        assert_eq!(decision, AlertDecision::Fire);
+        assert_eq!(state, fired_at(1_000));
+    }
+
+    #[test]
+    fn withheld_until_the_window_has_passed() {
+        let (decision, state) = evaluate(fired_at(1_000), &policy(), true, 1_000 + WITHOLD);
+        assert_eq!(decision, AlertDecision::Suppress(Suppressed::Withheld));
+        assert_eq!(state, fired_at(1_000));
+
+        let (decision, state) = evaluate(state, &policy(), true, 1_001 + WITHOLD);
+        assert_eq!(decision, AlertDecision::Fire);
+        assert_eq!(state.last_alerted, Some(1_001 + WITHOLD));
+        assert_eq!(state.repeat_count, 2);
+        assert_eq!(state.met_since, Some(1_000));
+    }
+
+    #[test]
+    fn clearing_resets_the_state() {
+        let state = AlertState {
+            last_reminded: Some(2_000),
+            repeat_count: 3,
+            ..fired_at(1_000)
+        };
+        let (decision, state) = evaluate(state, &policy(), false, 2_500);
+        assert_eq!(decision, AlertDecision::Clear);
+        assert_eq!(state, AlertState::default());
+
+        let (decision, _) = evaluate(state, &policy(), true, 2_560);
+        assert_eq!(decision, AlertDecision::Fire);
+    }
+
+    #[test]
+    fn confirmation_holds_back_until_met_long_enough() {
+        let policy = AlertPolicy {
+            confirm_secs: Some(120),
+            ..policy()
+        };
+        let (decision, state) = evaluate(AlertState::default(), &policy, true, 1_000);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::Confirming {
+                held: 0,
+                confirm: 120
+            })
+        );
+        assert_eq!(state.met_since, Some(1_000));
+
+        let (decision, _) = evaluate(state, &policy, true, 1_119);
+        assert!(matches!(
+            decision,
+            AlertDecision::Suppress(Suppressed::Confirming { held: 119, .. })
+        ));
+        let (decision, _) = evaluate(state, &policy, true, 1_120);
+        assert_eq!(decision, AlertDecision::Fire);
+
+        // An interruption restarts the confirmation
+        let (_, state) = evaluate(state, &policy, false, 1_060);
+        assert_eq!(state.met_since, None);
+    }
+
+    #[test]
+    fn snooze_only_holds_back_alerts_that_would_fire() {
+        let policy = AlertPolicy {
+            snoozed_until: Some(5_000),
+            ..policy()
+        };
+        let (decision, state) = evaluate(AlertState::default(), &policy, true, 1_000);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::Snoozed { until: 5_000 })
+        );
+        assert_eq!(state.last_alerted, None);
+
+        let (decision, _) = evaluate(fired_at(1_000), &policy, true, 1_060);
+        assert_eq!(decision, AlertDecision::Suppress(Suppressed::Withheld));
+
+        let (decision, _) = evaluate(state, &policy, true, 5_000);
+        assert_eq!(decision, AlertDecision::Fire);
+    }
+
+    #[test]
+    fn repeats_stop_at_the_maximum() {
+        let policy = AlertPolicy {
+            max_repeats: Some(2),
+            withold_secs: 0,
+            ..policy()
+        };
+        let (_, state) = evaluate(fired_at(1_000), &policy, true, 1_060);
+        assert_eq!(state.repeat_count, 2);
+        let (decision, next) = evaluate(state, &policy, true, 1_120);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::RepeatsExhausted { count: 2 })
+        );
+        assert_eq!(next, state);
+    }
+
+    #[test]
+    fn reminders_are_sent_while_held_back() {
+        let policy = AlertPolicy {
+            reminder_interval_h: Some(1),
+            withold_secs: 86_400,
+            ..policy()
+        };
+        let (decision, state) = evaluate(fired_at(1_000), &policy, true, 4_599);
+        assert_eq!(decision, AlertDecision::Suppress(Suppressed::Withheld));
+
+        let (decision, state) = evaluate(state, &policy, true, 4_600);
+        assert_eq!(decision, AlertDecision::Remind(Suppressed::Withheld));
+        assert_eq!(state.last_reminded, Some(4_600));
+
+        let (decision, _) = evaluate(state, &policy, true, 8_199);
+        assert_eq!(decision, AlertDecision::Suppress(Suppressed::Withheld));
+        let (decision, _) = evaluate(state, &policy, true, 8_200);
+        assert_eq!(decision, AlertDecision::Remind(Suppressed::Withheld));
+    }
+
+    #[test]
+    fn met_since_the_first_sample_never_fires() {
+        let policy = AlertPolicy {
+            quiet_since_first_sample: Some(1_000),
+            withold_secs: 0,
+            ..policy()
+        };
+        let (decision, state) = evaluate(AlertState::default(), &policy, true, 1_000);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::InitialCrossing)
+        );
+        let (decision, state) = evaluate(state, &policy, true, 90_000);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::InitialCrossing)
+        );
+        assert_eq!(state.last_alerted, None);
+
+        let (decision, state) = evaluate(state, &policy, false, 90_060);
+        assert_eq!(decision, AlertDecision::NoOp);
+        let (decision, _) = evaluate(state, &policy, true, 90_120);
+        assert_eq!(decision, AlertDecision::Fire);
+    }
+
+    #[test]
+    fn confirm_cycles_count_consecutive_met_checks() {
+        let policy = AlertPolicy {
+            confirm_cycles: Some(3),
+            ..policy()
+        };
+        let (decision, state) = evaluate(AlertState::default(), &policy, true, 1_000);
+        assert_eq!(
+            decision,
+            AlertDecision::Suppress(Suppressed::ConfirmingCycles { met: 1, needed: 3 })
+        );
+        let (_, state) = evaluate(state, &policy, true, 1_060);
+        // Any unmet check starts the count over
+        let (_, state) = evaluate(state, &policy, false, 1_120);
+        assert_eq!(state.met_cycles, 0);
+
+        let (_, state) = evaluate(state, &policy, true, 1_180);
+        let (_, state) = evaluate(state, &policy, true, 1_240);
+        let (decision, state) = evaluate(state, &policy, true, 1_300);
+        assert_eq!(decision, AlertDecision::Fire);
+        assert_eq!(state.met_cycles, 3);
+    }
+
+    #[test]
+    fn state_from_the_future_is_held_back_from_now() {
+        let mut state = fired_at(10_000);
+        assert!(state.rewind(4_000));
+        assert_eq!(state, fired_at(4_000));
+        assert!(!state.rewind(4_000));
+
+        let (decision, state) = evaluate(state, &policy(), true, 4_000 + WITHOLD);
+        assert_eq!(decision, AlertDecision::Suppress(Suppressed::Withheld));
+        let (decision, _) = evaluate(state, &policy(), true, 4_001 + WITHOLD);
+        assert_eq!(decision, AlertDecision::Fire);
+    }
+}
@@ -0,0 +1,245 @@
+//! Exchange announcements, polled on their own interval and notified once
+//! each when the title contains one of the configured keywords. Reads
+//! Binance's announcement API, a JSON Feed or an RSS feed.
+
+use crate::notify::{self, Notification};
+use crate::{AnnouncementsConfig, Config};
+use serde_json::Value;
+
+/// Binance's "New Cryptocurrency Listing" announcements, newest first.
+pub const BINANCE_LISTINGS_URL: &str = "https://www.binance.com/bapi/composite/v1/public/cms/article/list/query?type=1&catalogId=48&pageNo=1&pageSize=20";
+
+/// Announcement ids remembered in `Config::announcements_seen`, well above
+/// what a feed returns at once.
+const SEEN_LIMIT: usize = 500;
+
+#[derive(Debug, PartialEq)]
+pub struct Announcement {
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+}
+
+/// Fetches the feed, remembers every announcement in it and notifies the
+/// matching ones not seen before. The first poll only remembers, so the
+/// feed's backlog is not sent at startup.
+pub fn poll(config: &mut Config) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(feed) = &config.announcements else {
+        return Ok(());
+    };
+    let client = config
+        .http_client()
+        .user_agent(concat!("bye-watch/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .build()?;
+    let response = client.get(&feed.url).send()?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()).into());
+    }
+    let announcements = parse(&response.text()?)?;
+    let channel = feed.channel;
+    for announcement in new_matches(config, announcements) {
+        let notification = compose(&announcement);
+        let result = match channel {
+            Some(channel) => notify::notify_via(config, channel, &notification),
+            None => notify::notify(config, &notification),
+        };
+        match result {
+            Ok(()) => mark_seen(config, &announcement.id),
+            Err(e) => eprintln!(
+                "Failed to send announcement \"{}\", retrying on the next poll: {}",
+                announcement.title, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Those of `announcements` not seen before whose title matches a keyword,
+/// recording the others as seen. The matches are left to `mark_seen` once
+/// they were sent.
+pub fn new_matches(config: &mut Config, announcements: Vec<Announcement>) -> Vec<Announcement> {
+    let Some(feed) = &config.announcements else {
+        return Vec::new();
+    };
+    let first_poll = config.announcements_seen.is_empty();
+    let (mut matches, mut seen) = (Vec::new(), Vec::new());
+    // Oldest first, so they are notified in the order they were published
+    for announcement in announcements.into_iter().rev() {
+        if config.announcements_seen.contains(&announcement.id) {
+            continue;
+        }
+        if !first_poll && is_match(feed, &announcement.title) {
+            matches.push(announcement);
+        } else {
+            seen.push(announcement.id);
+        }
+    }
+    for id in seen {
+        mark_seen(config, &id);
+    }
+    matches
+}
+
+/// Records announcement `id` as seen, forgetting the oldest past `SEEN_LIMIT`.
+pub fn mark_seen(config: &mut Config, id: &str) {
+    config.announcements_seen.push_back(id.to_string());
+    while config.announcements_seen.len() > SEEN_LIMIT {
+        config.announcements_seen.pop_front();
+    }
+}
+
+/// Whether `title` contains one of the keywords, ignoring case. Without
+/// keywords every announcement matches.
+fn is_match(feed: &AnnouncementsConfig, title: &str) -> bool {
+    let title = title.to_lowercase();
+    feed.keywords.is_empty()
+        || feed
+            .keywords
+            .iter()
+            .any(|keyword| title.contains(&keyword.to_lowercase()))
+}
+
+fn compose(announcement: &Announcement) -> Notification {
+    let mut message = announcement.title.clone();
+    if let Some(link) = &announcement.link {
+        message.push_str("\n\n");
+        message.push_str(link);
+    }
+    Notification::text(format!("[bye-watch] {}", announcement.title), message)
+}
+
+/// Announcements of an RSS feed, a JSON Feed or a response of Binance's
+/// announcement API, told apart by their content.
+pub fn parse(body: &str) -> Result<Vec<Announcement>, Box<dyn std::error::Error>> {
+    if body.trim_start().starts_with('<') {
+        return Ok(parse_rss(body));
+    }
+    let value: Value = serde_json::from_str(body)?;
+    if let Some(items) = value["items"].as_array() {
+        // JSON Feed, https://jsonfeed.org
+        return Ok(items
+            .iter()
+            .filter_map(|item| {
+                let title = item["title"].as_str()?;
+                let link = item["url"].as_str().map(str::to_string);
+                let id = item["id"].as_str().or(link.as_deref()).unwrap_or(title);
+                Some(Announcement {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    link,
+                })
+            })
+            .collect());
+    }
+    let catalogs = value["data"]["catalogs"]
+        .as_array()
+        .ok_or("neither an RSS feed, a JSON Feed nor a Binance announcement list")?;
+    Ok(catalogs
+        .iter()
+        .flat_map(|catalog| catalog["articles"].as_array().into_iter().flatten())
+        .filter_map(|article| {
+            let code = article["code"].as_str()?;
+            Some(Announcement {
+                id: code.to_string(),
+                title: article["title"].as_str()?.to_string(),
+                link: Some(format!(
+                    "https://www.binance.com/en/support/announcement/{}",
+                    code
+                )),
+            })
+        })
+        .collect())
+}
+
+/// The `<item>`s of an RSS feed, identified by their guid, else their link.
+fn parse_rss(body: &str) -> Vec<Announcement> {
+    let mut announcements = Vec::new();
+    let mut rest = body;
+    while let Some((item, after)) = element(rest, "item") {
+        rest = after;
+        let Some(title) = element(item, "title").map(|(title, _)| text(title)) else {
+            continue;
+        };
+        let link = element(item, "link").map(|(link, _)| text(link));
+        let id = element(item, "guid")
+            .map(|(guid, _)| text(guid))
+            .or_else(|| link.clone())
+            .unwrap_or_else(|| title.clone());
+        announcements.push(Announcement { id, title, link });
+    }
+    announcements
+}
+
+/// Contents of the first `<name>` element in `xml` and what follows it.
+fn element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut at = 0;
+    // Skip longer names sharing the prefix, e.g. <itemCount>
+    let start = loop {
+        let found = at + xml[at..].find(&open)?;
+        at = found + open.len();
+        if xml[at..].starts_with(['>', ' ', '\t', '\r', '\n']) {
+            break at + xml[at..].find('>')? + 1;
+        }
+    };
+    let end = start + xml[start..].find(&close)?;
+    Some((&xml[start..end], &xml[end + close.len()..]))
+}
+
+/// Character data of an element, unwrapping CDATA and the predefined
+/// entities.
+fn text(raw: &str) -> String {
+    let raw = raw.trim();
+    if let Some(data) = raw
+        .strip_prefix("<![CDATA[")
+        .and_then(|raw| raw.strip_suffix("]]>"))
+    {
+        return data.trim().to_string();
+    }
+    raw.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feeds_of_every_format_are_read() {
+        let binance = r#"{"code":"000000","data":{"catalogs":[{"catalogId":48,"articles":[
+            {"id":1,"code":"abc123","title":"Binance Will List Foo (FOO)","releaseDate":1}
+        ]}]}}"#;
+        assert_eq!(
+            parse(binance).unwrap(),
+            vec![Announcement {
+                id: "abc123".to_string(),
+                title: "Binance Will List Foo (FOO)".to_string(),
+                link: Some("https://www.binance.com/en/support/announcement/abc123".to_string()),
+            }]
+        );
+
+        let json_feed = r#"{"version":"https://jsonfeed.org/version/1.1","items":[
+            {"id":"7","title":"Bar listed","url":"https://example.com/7"}
+        ]}"#;
+        assert_eq!(parse(json_feed).unwrap()[0].id, "7");
+
+        let rss = r#"<?xml version="1.0"?><rss><channel><title>News</title>
+            <item><title><![CDATA[Listing: BAZ & QUX]]></title><link>https://example.com/a</link></item>
+            <item><title>Maintenance &amp; upgrades</title><guid isPermaLink="false">b</guid></item>
+        </channel></rss>"#;
+        let items = parse(rss).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Listing: BAZ & QUX");
+        assert_eq!(items[0].id, "https://example.com/a");
+        assert_eq!(items[1].title, "Maintenance & upgrades");
+        assert_eq!(items[1].id, "b");
+
+        assert!(parse(r#"{"data":{}}"#).is_err());
+    }
+}
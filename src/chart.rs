@@ -0,0 +1,50 @@
+use plotters::prelude::*;
+
+const WIDTH: u32 = 240;
+const HEIGHT: u32 = 60;
+
+/// Minimum number of samples worth drawing a line for.
+pub const MIN_SAMPLES: usize = 3;
+
+/// Renders `prices` as a small axis-less line chart, PNG encoded.
+pub fn sparkline_png(prices: &[f64]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if prices.len() < MIN_SAMPLES {
+        return Err(format!("need at least {} samples for a chart", MIN_SAMPLES).into());
+    }
+
+    let (min, max) = prices
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &p| {
+            (min.min(p), max.max(p))
+        });
+    // Pad flat series so the line ends up in the middle instead of the edge.
+    let padding = ((max - min) * 0.1).max(max.abs() * 1e-6).max(f64::EPSILON);
+
+    let mut pixels = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(4)
+            .build_cartesian_2d(0..prices.len() - 1, (min - padding)..(max + padding))?;
+        let color = if prices[prices.len() - 1] >= prices[0] {
+            RGBColor(22, 163, 74)
+        } else {
+            RGBColor(220, 38, 38)
+        };
+        chart.draw_series(LineSeries::new(
+            prices.iter().copied().enumerate(),
+            color.stroke_width(2),
+        ))?;
+        root.present()?;
+    }
+
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, WIDTH, HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+    }
+    Ok(png)
+}
@@ -0,0 +1,103 @@
+use crate::Config;
+use chrono::Local;
+use std::io::Write;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+/// Redraws the whole terminal with one row per alert, highlighting the rows
+/// whose condition was met during the last check.
+pub fn render(config: &Config) -> std::io::Result<()> {
+    let mut out = std::io::stdout().lock();
+    draw(&mut out, config)?;
+    out.flush()
+}
+
+/// The screen `render` draws, including the profiles' alerts.
+fn draw(out: &mut impl Write, config: &Config) -> std::io::Result<()> {
+    write!(out, "{}", CLEAR_SCREEN)?;
+    writeln!(
+        out,
+        "Bye-Watch - {} (refreshing every {} seconds)\n",
+        Local::now().format(config.timestamp_format()),
+        config.check_interval
+    )?;
+    writeln!(
+        out,
+        "{:<14} {:>16}  {:<32} STATUS",
+        "SYMBOL", "PRICE", "RULE"
+    )?;
+
+    for currency in config.all_currencies() {
+        let price = match currency.last_price {
+            Some(price) => config.number_format.apply(&format!(
+                "{:.*}",
+                currency.tick.map_or(2, |tick| tick.decimals),
+                price
+            )),
+            None => "-".to_string(),
+        };
+        let status = match (currency.last_price, currency.condition_met) {
+            (None, _) => "no data",
+            (Some(_), true) if currency.last_alerted.is_some() => "MET (alerted)",
+            (Some(_), true) => "MET",
+            (Some(_), false) => "not met",
+        };
+        let rule = currency.rule();
+        let rule = rule
+            .strip_prefix(currency.name())
+            .unwrap_or(&rule)
+            .trim_start();
+        let row = format!(
+            "{:<14} {:>16}  {:<32} {}",
+            currency.name(),
+            price,
+            rule,
+            status
+        );
+        if currency.condition_met {
+            writeln!(out, "{}{}{}", HIGHLIGHT, row, RESET)?;
+        } else {
+            writeln!(out, "{}", row)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_show_price_and_status_of_every_alert() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "currencies": [
+                { "symbol": "BTCEUR", "threshold": 100.0, "alert_condition": "Above" },
+                { "symbol": "ETHEUR", "threshold": 100.0, "alert_condition": "Above" },
+            ],
+            "profiles": [{
+                "name": "night",
+                "currencies": [
+                    { "symbol": "XRPEUR", "threshold": 1.0, "alert_condition": "Below" },
+                ],
+            }],
+        }))
+        .unwrap();
+        config.currencies[0].last_price = Some(150.0);
+        config.currencies[0].condition_met = true;
+        config.currencies[1].last_price = Some(50.0);
+
+        let mut out = Vec::new();
+        draw(&mut out, &config).unwrap();
+        let screen = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = screen.lines().skip(3).collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with(HIGHLIGHT) && rows[0].ends_with(RESET));
+        assert!(rows[0].contains("150.00") && rows[0].contains("MET"));
+        assert!(rows[1].starts_with("ETHEUR") && rows[1].ends_with("not met"));
+        assert!(rows[2].starts_with("XRPEUR") && rows[2].ends_with("no data"));
+    }
+}
@@ -0,0 +1,102 @@
+//! Every fired alert POSTed on its own to `event_webhook` as soon as it is
+//! evaluated, numbered so receivers can spot gaps. Events that cannot be
+//! delivered stay queued in the config and are resent in order.
+
+use crate::notify::{self, FiredAlert};
+use crate::Config;
+use serde_json::{json, Value};
+
+/// Events kept for redelivery before the oldest are dropped.
+const MAX_PENDING_EVENTS: usize = 1000;
+
+/// Queues an event for each of `alerts` and sends whatever is pending.
+pub fn publish<'a>(config: &mut Config, alerts: impl IntoIterator<Item = &'a FiredAlert>) {
+    if config.event_webhook.is_none() {
+        return;
+    }
+    for alert in alerts {
+        config.event_sequence += 1;
+        config.pending_events.push_back(json!({
+            "sequence": config.event_sequence,
+            "event": alert.event.as_str(),
+            "symbol": alert.symbol,
+            "rule": alert.rule,
+            "condition": alert.condition,
+            "threshold": alert.threshold,
+            "price": alert.price,
+            "timestamp": alert.timestamp,
+            "details": alert.details.trim_end(),
+        }));
+    }
+    while config.pending_events.len() > MAX_PENDING_EVENTS {
+        if let Some(dropped) = config.pending_events.pop_front() {
+            eprintln!(
+                "Event queue is full, dropping event {}",
+                dropped["sequence"]
+            );
+        }
+    }
+    flush(config);
+}
+
+/// Sends the pending events oldest first, stopping at the first failure so
+/// they arrive in sequence.
+fn flush(config: &mut Config) {
+    let Some(webhook) = &config.event_webhook else {
+        return;
+    };
+    let retry = webhook.retry.or(config.retry);
+    while let Some(event) = config.pending_events.front() {
+        match send(config, webhook, retry, event) {
+            Ok(()) => {
+                config.pending_events.pop_front();
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to send event {}, {} events pending until the next check: {}",
+                    event["sequence"],
+                    config.pending_events.len(),
+                    e
+                );
+                return;
+            }
+        }
+    }
+}
+
+fn send(
+    config: &Config,
+    webhook: &crate::EventWebhookConfig,
+    retry: crate::RetryPolicy,
+    event: &Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let body = serde_json::to_vec(event)?;
+    let signature = webhook
+        .hmac_secret
+        .as_ref()
+        .map(|secret| notify::sign(secret.as_bytes(), &body));
+    let client = config
+        .http_client()
+        .timeout(notify::timeout(retry))
+        .build()?;
+    notify::with_retries(
+        "event",
+        retry,
+        config.warmup_until,
+        |_| true,
+        |_| {
+            let mut request = client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(notify::DEFAULT_SIGNATURE_HEADER, signature);
+            }
+            let response = request.send()?;
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", response.status()).into());
+            }
+            Ok(())
+        },
+    )
+}
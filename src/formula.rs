@@ -0,0 +1,235 @@
+//! `Formula` conditions: arithmetic over symbol prices, compared against a
+//! number or another expression, e.g. `(BTCUSDT + ETHUSDT) / 2 > 40000`.
+
+/// A parsed formula, `left` compared to `right` with `comparison`.
+#[derive(Debug, PartialEq)]
+pub struct Formula {
+    left: Expr,
+    comparison: Comparison,
+    right: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Symbol(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    Symbol(&'a str),
+    Op(char),
+    Comparison(Comparison),
+    Open,
+    Close,
+}
+
+/// The symbols `text` references, in order of appearance and without
+/// repeats, for fetching their prices. Unparsable formulas reference none.
+pub fn symbols(text: &str) -> Vec<&str> {
+    let mut symbols = Vec::new();
+    for token in tokenize(text).unwrap_or_default() {
+        if let Token::Symbol(symbol) = token {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+    }
+    symbols
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphabetic() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Symbol(&rest[..len]));
+            len
+        } else if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| format!("invalid number \"{}\"", &rest[..len]))?;
+            tokens.push(Token::Number(number));
+            len
+        } else if let Some(comparison) = [
+            (">=", Comparison::AtLeast),
+            ("<=", Comparison::AtMost),
+            (">", Comparison::Above),
+            ("<", Comparison::Below),
+        ]
+        .into_iter()
+        .find(|(op, _)| rest.starts_with(op))
+        {
+            tokens.push(Token::Comparison(comparison.1));
+            comparison.0.len()
+        } else {
+            tokens.push(match c {
+                '+' | '-' | '*' | '/' => Token::Op(c),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => return Err(format!("unexpected \"{}\"", c)),
+            });
+            1
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Parses `left <comparison> right`, where both sides are `+ - * /` over
+/// numbers, symbols and parentheses, and the comparison is one of
+/// `> >= < <=`.
+pub fn parse(text: &str) -> Result<Formula, String> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, at: 0 };
+    let left = parser.sum()?;
+    let comparison = match parser.next() {
+        Some(Token::Comparison(comparison)) => comparison,
+        _ => return Err("expected one of > >= < <= between two expressions".into()),
+    };
+    let right = parser.sum()?;
+    if parser.at < parser.tokens.len() {
+        return Err("unexpected input after the comparison".into());
+    }
+    Ok(Formula {
+        left,
+        comparison,
+        right,
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.at).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek()?;
+        self.at += 1;
+        Some(token)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            self.at += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek() {
+            self.at += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op('-')) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Symbol(symbol)) => Ok(Expr::Symbol(symbol.to_string())),
+            Some(Token::Open) => {
+                let expr = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing \")\"".into()),
+                }
+            }
+            _ => Err("expected a number, symbol or \"(\"".into()),
+        }
+    }
+}
+
+impl Formula {
+    /// Whether the comparison holds, with the value of the left side. Fails
+    /// on a symbol `price` has no price for and on division by zero.
+    pub fn evaluate(&self, price: impl Fn(&str) -> Option<f64>) -> Result<(bool, f64), String> {
+        let left = self.left.value(&price)?;
+        let right = self.right.value(&price)?;
+        let holds = match self.comparison {
+            Comparison::Above => left > right,
+            Comparison::AtLeast => left >= right,
+            Comparison::Below => left < right,
+            Comparison::AtMost => left <= right,
+        };
+        Ok((holds, left))
+    }
+}
+
+impl Expr {
+    fn value(&self, price: &impl Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        match self {
+            Expr::Number(number) => Ok(*number),
+            Expr::Symbol(symbol) => price(symbol).ok_or_else(|| format!("no price for {}", symbol)),
+            Expr::Neg(expr) => Ok(-expr.value(price)?),
+            Expr::Binary(left, op, right) => {
+                let (left, right) = (left.value(price)?, right.value(price)?);
+                match op {
+                    '+' => Ok(left + right),
+                    '-' => Ok(left - right),
+                    '*' => Ok(left * right),
+                    _ if right == 0.0 => Err("division by zero".into()),
+                    _ => Ok(left / right),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_follow_precedence_and_report_missing_legs() {
+        let prices = |symbol: &str| match symbol {
+            "BTCUSDT" => Some(42_000.0),
+            "ETHUSDT" => Some(2_000.0),
+            "BTCEUR" => Some(40_000.0),
+            _ => None,
+        };
+        let formula = parse("(BTCUSDT + ETHUSDT) / 2 > 20000").unwrap();
+        assert_eq!(formula.evaluate(prices), Ok((true, 22_000.0)));
+        let formula = parse("BTCUSDT / BTCEUR - 1 * 0.05 <= -1").unwrap();
+        assert_eq!(formula.evaluate(prices), Ok((false, 1.0)));
+        assert_eq!(
+            parse("SOLUSDT * 2 > ETHUSDT").unwrap().evaluate(prices),
+            Err("no price for SOLUSDT".to_string())
+        );
+
+        assert_eq!(
+            symbols("(BTCUSDT + ETHUSDT) / BTCUSDT > 1"),
+            vec!["BTCUSDT", "ETHUSDT"]
+        );
+        assert!(parse("BTCUSDT + 1").is_err());
+        assert!(parse("(BTCUSDT > 1").is_err());
+        assert!(parse("BTCUSDT > 1 2").is_err());
+        assert!(parse("BTCUSDT % 2 > 1").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! USDⓈ-M futures market data, kept apart from the spot endpoints.
+
+use serde::Deserialize;
+
+const FUTURES_BASE_URL: &str = "https://fapi.binance.com";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PremiumIndex {
+    pub symbol: String,
+    pub mark_price: String,
+    /// Average of the symbol's spot price over the exchanges in the index.
+    pub index_price: String,
+    pub last_funding_rate: String,
+    /// Milliseconds since the epoch.
+    pub next_funding_time: i64,
+}
+
+/// Fetches mark price and funding data for the given perpetual symbols.
+pub fn fetch_premium_index(
+    client: reqwest::blocking::ClientBuilder,
+    symbols: &[&str],
+) -> Result<Vec<PremiumIndex>, Box<dyn std::error::Error>> {
+    if symbols.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let client = client.build()?;
+    let url = format!("{}/fapi/v1/premiumIndex", FUTURES_BASE_URL);
+    let response = client.get(url).send()?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch premium index: HTTP {}", response.status()).into());
+    }
+
+    let index: Vec<PremiumIndex> = response.json()?;
+    Ok(index
+        .into_iter()
+        .filter(|entry| symbols.contains(&entry.symbol.as_str()))
+        .collect())
+}
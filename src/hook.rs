@@ -0,0 +1,59 @@
+//! `on_alert_command`, run once for every triggered alert after it was
+//! notified.
+
+use crate::notify::FiredAlert;
+use std::process::{Command, Stdio};
+
+/// The command for `alert`, described to it through `BYE_WATCH_*`
+/// environment variables. Nothing is interpolated into the arguments or run
+/// through a shell.
+pub fn command(argv: &[String], alert: &FiredAlert) -> Option<Command> {
+    let (program, args) = argv.split_first()?;
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .env("BYE_WATCH_SYMBOL", &alert.symbol)
+        .env("BYE_WATCH_RULE", &alert.rule)
+        .env("BYE_WATCH_CONDITION", &alert.condition)
+        .env("BYE_WATCH_PRICE", alert.price.to_string())
+        .env(
+            "BYE_WATCH_THRESHOLD",
+            alert.threshold.map(|t| t.to_string()).unwrap_or_default(),
+        )
+        .env("BYE_WATCH_TIMESTAMP", alert.timestamp.to_string())
+        .stdin(Stdio::null());
+    Some(command)
+}
+
+/// Runs the command in the background and logs its exit status and output,
+/// so a slow script does not hold up the checks.
+pub fn run(argv: &[String], alert: &FiredAlert) {
+    let Some(mut command) = command(argv, alert) else {
+        return;
+    };
+    let rule = alert.rule.clone();
+    std::thread::spawn(move || match command.output() {
+        Ok(output) => {
+            let log = |stream: &str, bytes: &[u8]| {
+                let text = String::from_utf8_lossy(bytes);
+                if !text.trim().is_empty() {
+                    println!("on_alert_command {}: {}", stream, text.trim_end());
+                }
+            };
+            log("stdout", &output.stdout);
+            log("stderr", &output.stderr);
+            if output.status.success() {
+                println!(
+                    "on_alert_command for {} exited with {}",
+                    rule, output.status
+                );
+            } else {
+                eprintln!(
+                    "on_alert_command for {} exited with {}",
+                    rule, output.status
+                );
+            }
+        }
+        Err(e) => eprintln!("Failed to run on_alert_command for {}: {}", rule, e),
+    });
+}
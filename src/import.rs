@@ -0,0 +1,118 @@
+use crate::{AlertCondition, Config, CurrencyAlert, Threshold};
+
+/// Merges `symbol,condition,threshold` rows (comma or tab separated) into
+/// the top-level alert list. Rows matching an existing symbol and condition
+/// update its threshold and keep its state. Nothing is merged if any row is
+/// invalid; all errors are reported with their line numbers.
+pub fn import_alerts(config: &mut Config, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)?;
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (line_number, fields) in csv_rows(&content) {
+        match parse_row(&fields) {
+            Ok(row) => rows.push(row),
+            Err(e) => errors.push(format!("line {}: {}", line_number, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!("{}: {}", path, error);
+        }
+        return Err(format!(
+            "{} invalid rows in {}, nothing imported",
+            errors.len(),
+            path
+        )
+        .into());
+    }
+
+    let (mut added, mut updated) = (0, 0);
+    for (symbol, condition, threshold) in rows {
+        let existing = config.currencies.iter_mut().find(|c| {
+            c.symbol == symbol
+                && std::mem::discriminant(&c.alert_condition) == std::mem::discriminant(&condition)
+        });
+        match existing {
+            Some(currency) => {
+                currency.alert_condition = condition;
+                currency.threshold = Some(Threshold::Value(threshold));
+                updated += 1;
+            }
+            None => {
+                let currency: CurrencyAlert = serde_json::from_value(serde_json::json!({
+                    "symbol": symbol,
+                    "threshold": threshold,
+                    "alert_condition": condition,
+                    "last_alerted": null,
+                }))?;
+                config.currencies.push(currency);
+                added += 1;
+            }
+        }
+    }
+    println!(
+        "Imported {} from {}: {} added, {} updated",
+        added + updated,
+        path,
+        added,
+        updated
+    );
+    Ok(())
+}
+
+/// The fields of each row of comma or tab separated `content`, with its line
+/// number. Blank lines, `#` comments and a `symbol,...` header on the first
+/// line are skipped.
+pub fn csv_rows(content: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    content.lines().enumerate().filter_map(|(index, line)| {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let delimiter = if line.contains('\t') { '\t' } else { ',' };
+        let fields: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+        let header = index == 0 && fields[0].eq_ignore_ascii_case("symbol");
+        (!header).then_some((index + 1, fields))
+    })
+}
+
+fn parse_row(fields: &[&str]) -> Result<(String, AlertCondition, f64), String> {
+    let [symbol, condition, value] = fields else {
+        return Err(format!(
+            "expected 3 fields (symbol, condition, threshold), found {}",
+            fields.len()
+        ));
+    };
+    if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid symbol \"{}\"", symbol));
+    }
+    let value: f64 = value
+        .parse()
+        .ok()
+        .filter(|v: &f64| v.is_finite())
+        .ok_or_else(|| format!("invalid threshold \"{}\"", value))?;
+
+    // Conditions other than above/below take the value as their parameter.
+    let (condition, threshold) = match condition.to_ascii_lowercase().as_str() {
+        "above" => (AlertCondition::Above, value),
+        "below" => (AlertCondition::Below, value),
+        "daily_change_above" => (AlertCondition::DailyChangeAbove { pct: value }, 0.0),
+        "daily_change_below" => (AlertCondition::DailyChangeBelow { pct: value }, 0.0),
+        "velocity" => (AlertCondition::Velocity { pct_per_min: value }, 0.0),
+        "funding_above" => (AlertCondition::FundingAbove { pct: value }, 0.0),
+        "funding_below" => (AlertCondition::FundingBelow { pct: value }, 0.0),
+        "near_high" => (AlertCondition::NearHigh { pct: value }, 0.0),
+        "near_low" => (AlertCondition::NearLow { pct: value }, 0.0),
+        "trade_count_above" => {
+            if value < 0.0 || value.fract() != 0.0 {
+                return Err(format!("trade count \"{}\" is not a whole number", value));
+            }
+            let threshold = value as u64;
+            (AlertCondition::TradeCountAbove { threshold }, 0.0)
+        }
+        other => return Err(format!("unknown condition \"{}\"", other)),
+    };
+    Ok((symbol.to_uppercase(), condition, threshold))
+}
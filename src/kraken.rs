@@ -0,0 +1,44 @@
+//! Kraken spot prices, a fallback for symbols whose `price_sources` list it.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const KRAKEN_BASE_URL: &str = "https://api.kraken.com";
+
+#[derive(Debug, Deserialize)]
+struct TickerResponse {
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, Ticker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    /// Last trade as `[price, lot volume]`.
+    c: (String, String),
+}
+
+/// Last trade price of `symbol`, e.g. "BTCEUR". Kraken resolves the common
+/// names to its own pair names ("XXBTZEUR") itself.
+pub fn fetch_price(
+    client: reqwest::blocking::ClientBuilder,
+    symbol: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let client = client.build()?;
+    let url = format!("{}/0/public/Ticker", KRAKEN_BASE_URL);
+    let response = client.get(url).query(&[("pair", symbol)]).send()?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch Kraken ticker: HTTP {}", response.status()).into());
+    }
+
+    let ticker: TickerResponse = response.json()?;
+    if !ticker.error.is_empty() {
+        return Err(ticker.error.join(", ").into());
+    }
+    ticker
+        .result
+        .into_values()
+        .next()
+        .map(|ticker| ticker.c.0)
+        .ok_or_else(|| format!("Kraken has no ticker for {}", symbol).into())
+}
@@ -1,27 +1,1114 @@
-use chrono::Local;
-use lettre::{transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
-use reqwest;
+mod alert;
+mod announcements;
+mod chart;
+mod dashboard;
+mod events;
+mod formula;
+mod futures;
+mod hook;
+mod import;
+mod kraken;
+mod metrics;
+mod migrate;
+mod mqtt;
+mod notify;
+mod secrets;
+mod server;
+mod summary;
+mod threshold_file;
+mod update;
+mod variables;
+mod weight;
+
+use alert::{AlertDecision, Suppressed};
+use chrono::{Local, TimeZone, Timelike};
+use notify::{notify, AlertEvent, FiredAlert, Notification, TokenBucket};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Deserialize, Serialize)]
 struct EmailConfig {
     username: String,
     password: String,
+    /// Display name the alerts are sent from, e.g. "Bye-Watch Alerts".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_name: Option<String>,
+    /// Per-alert template, see `notify::render_alert` for the placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<String>,
+    /// Embed a sparkline of recent prices for every alerted symbol.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    include_chart: bool,
+    /// Maximum number of pooled SMTP connections (default 2).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool_size: Option<u32>,
+    /// Seconds an idle pooled connection is kept open (default 60).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool_idle_timeout_s: Option<u64>,
+    /// Longest body in bytes this channel is sent; alerts that do not fit
+    /// are replaced with a "more alerts truncated" line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_body_len: Option<usize>,
+    #[serde(flatten)]
+    retry: RetryPolicy,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// How often and how patiently a channel retries a failed send. Set at the
+/// top level of the config and overridable in every channel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+struct RetryPolicy {
+    /// Attempts after the first failed one (default 1).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retries: Option<u32>,
+    /// Wait before the first retry, doubled for every further one (default 500).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    retry_backoff_ms: Option<u64>,
+    /// Timeout of a single attempt (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// This policy, with the settings it leaves out taken from `global`.
+    fn or(self, global: RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            retries: self.retries.or(global.retries),
+            retry_backoff_ms: self.retry_backoff_ms.or(global.retry_backoff_ms),
+            timeout_secs: self.timeout_secs.or(global.timeout_secs),
+        }
+    }
 }
 #[derive(Debug, Deserialize, Serialize)]
 struct Config {
+    /// Schema version, see `migrate.rs`.
+    #[serde(default)]
+    config_version: u64,
     email: EmailConfig,
+    /// Channel defaults, see `RetryPolicy`.
+    #[serde(flatten)]
+    retry: RetryPolicy,
     check_interval: u64,
     withold_notification_h: Option<u64>,
+    /// Scales the withold to the symbol's recent volatility.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    adaptive_cooldown: Option<AdaptiveCooldown>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_notifications_per_hour: Option<u32>,
+    #[serde(default)]
+    missing_symbol_policy: MissingSymbolPolicy,
+    #[serde(default)]
+    number_format: NumberFormat,
+    #[serde(default)]
+    body_format: BodyFormat,
+    /// Write prices of a thousand and more as "65.43K" or "1.23M" in
+    /// notifications. Templates can still show the exact value through
+    /// `{price_full}`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    compact_numbers: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    http: Option<HttpConfig>,
+    /// Base assets (e.g. "BTC") whose alerts are skipped entirely.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    muted_base_assets: Vec<String>,
+    /// Quote assets (e.g. "EUR") whose alerts are skipped entirely.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    muted_quote_assets: Vec<String>,
+    /// Symbols muted via `POST /mute/<symbol>`, until when.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    muted_symbols: BTreeMap<String, u64>,
+    /// Lower bound for every check interval, protecting against bans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_check_interval: Option<u64>,
+    /// Also notify when a previously alerted condition clears.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    notify_on_clear: bool,
+    /// Record the first sample of a newly added alert without notifying, so
+    /// a condition that is already met only alerts once it crosses again.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    suppress_initial_crossings: bool,
+    /// Only warn about keys in config.json that match no field, instead of
+    /// refusing to start.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    lenient_config: bool,
+    /// For this many seconds after startup, failed sends keep being retried
+    /// with backoff past `retries`, for mail servers and the like that are
+    /// restarted together with bye-watch and still coming up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel_warmup_retry_secs: Option<u64>,
+    /// End of `channel_warmup_retry_secs`, set at startup.
+    #[serde(skip)]
+    warmup_until: Option<SystemTime>,
+    /// At startup, send a notice listing the alerts that were added since
+    /// the last run, confirming they are watched.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    notify_on_arm: bool,
+    /// Channel the `notify_on_arm` notice goes through, default all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arm_channel: Option<Channel>,
+    /// Abort at startup if the exchange is unreachable or a symbol is unknown.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    strict_startup: bool,
+    #[serde(default)]
+    currencies: Vec<CurrencyAlert>,
+    /// Independent watch lists with their own interval and channels.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    profiles: Vec<Profile>,
+    #[serde(skip)]
+    notification_budget: TokenBucket,
+    #[serde(skip)]
+    channel_queues: HashMap<Channel, notify::ChannelQueue>,
+    /// JSON map of credentials such as `email_password`, kept out of this
+    /// file, see `secrets.rs`. It should be readable by its owner only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secrets_file: Option<String>,
+    /// Where the secrets from `secrets_file` went, so they are not saved.
+    #[serde(skip)]
+    secret_paths: Vec<&'static str>,
+    /// Named levels alert thresholds refer to as `"${name}"`, see
+    /// `variables.rs`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, f64>,
+    /// Thresholds resolved from `variables`, saved as their reference.
+    #[serde(skip)]
+    variable_paths: variables::Resolved,
+    /// Notifications other than operational notices, such as failing checks
+    /// or a crash, are dropped until this time, set by `--snooze`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snooze_until: Option<u64>,
+    #[serde(skip)]
+    warned_missing_symbols: HashSet<String>,
+    /// First symbol without a price in the last check, with the `Error`
+    /// missing symbol policy.
+    #[serde(skip)]
+    missing_symbol: Option<String>,
+    /// Consecutive failed fetches before a recovery notice is sent (default 3).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recovery_notice_after: Option<u32>,
+    #[serde(skip)]
+    outage: Option<Outage>,
+    /// Last good contents of every `threshold_file`, by path.
+    #[serde(skip)]
+    threshold_files: HashMap<String, HashMap<String, f64>>,
+    /// SMTP transports by account, built on first use.
+    #[serde(skip)]
+    mailers: HashMap<String, lettre::SmtpTransport>,
+    /// Spot prices by symbol from `--price-override`, used instead of the
+    /// exchange's ticker.
+    #[serde(skip)]
+    price_override: Option<HashMap<String, String>>,
+    /// Dead band, in quote currency, around Above/Below thresholds: a price
+    /// within this distance of the threshold keeps the previous state instead
+    /// of flipping it, so tiny fluctuations don't alternate trigger and clear.
+    /// The distance is measured after rounding to the tick size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comparison_epsilon: Option<f64>,
+    /// At most one notification per symbol within this many seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbol_throttle_secs: Option<u64>,
+    /// When each symbol was last notified about, for `symbol_throttle_secs`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    symbol_last_notified: HashMap<String, u64>,
+    /// Of several Above (or Below) rules on a symbol triggering in the same
+    /// check and group, only notify the one with the most extreme threshold.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dedup_overlapping: bool,
+    /// An identical alert (same symbol, condition, threshold and roughly the
+    /// same price) is not sent again within this many seconds, even after a
+    /// restart.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dedup_window_secs: Option<u64>,
+    /// When each alert fingerprint was last sent, see `alert_fingerprint`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    sent_fingerprints: HashMap<String, u64>,
+    /// Consecutive failed checks after which an error notification is sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_notify_after: Option<u32>,
+    /// Consecutive failed checks after which bye-watch notifies and exits
+    /// with code 5, leaving the restart to its supervisor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_consecutive_failures: Option<u32>,
+    /// Order book levels per side summed for depth conditions (default 20).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    depth_limit: Option<u32>,
+    /// Friendlier names for symbols in output, e.g. "1000SHIBUSDT" -> "SHIB (x1000)".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    display_names: BTreeMap<String, String>,
+    /// Ordered sources to try for a symbol's price, the next one only if the
+    /// previous fails. Symbols not listed use Binance alone.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    price_sources: BTreeMap<String, Vec<PriceSource>>,
+    /// Program and arguments run for every triggered alert once it was
+    /// notified, e.g. `["/usr/local/bin/plug", "on"]`. The alert is passed in
+    /// `BYE_WATCH_*` environment variables, see `hook::command`. The command
+    /// runs with the daemon's privileges, and anyone who can edit this config
+    /// can run anything through it, so keep the file writable by its owner
+    /// only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    on_alert_command: Vec<String>,
+    /// Local IP all outbound HTTP requests are sent from, for exchange API
+    /// whitelists on multi-homed hosts. Email is not affected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    local_address: Option<String>,
+    /// The first check waits a random delay of up to this many seconds, so
+    /// instances deployed together don't all hit the exchange at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    startup_delay_secs: Option<u64>,
+    /// How many notification channels are sent to at once, default all of
+    /// them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_concurrency: Option<usize>,
+    #[serde(default)]
+    channel_mode: ChannelMode,
+    /// Channels `FirstSuccess` tries, in order. Defaults to every configured
+    /// channel, email first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    channel_order: Vec<Channel>,
+    /// Append a "sent by" footer naming this instance to every notification.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    footer: bool,
+    /// Name used in the footer, defaults to the hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instance_name: Option<String>,
+    /// strftime pattern for every printed or sent timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp_format: Option<String>,
+    /// Spot API base URL, e.g. a regional mirror or a local mock server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    binance_base_url: Option<String>,
+    /// Spot API base URLs such as "https://api1.binance.com", used in place
+    /// of `binance_base_url`. Each request starts at the next one in turn and
+    /// fails over to the others when a host is unreachable or erroring.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    binance_endpoints: Vec<String>,
+    #[serde(skip)]
+    next_endpoint: std::sync::atomic::AtomicUsize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webhook: Option<WebhookConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    event_webhook: Option<EventWebhookConfig>,
+    /// Sequence number of the last event queued for `event_webhook`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    event_sequence: u64,
+    /// Events `event_webhook` has not accepted yet, oldest first.
+    #[serde(default, skip_serializing_if = "VecDeque::is_empty")]
+    pending_events: VecDeque<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ntfy: Option<NtfyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    telegram: Option<TelegramConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pushgateway: Option<PushgatewayConfig>,
+    /// Publishes prices and alert states to Home Assistant, see `mqtt.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mqtt: Option<MqttConfig>,
+    #[serde(skip)]
+    stats: metrics::Stats,
+    /// Spot request weight allowed per minute (default Binance's limit).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight_budget_per_min: Option<u64>,
+    /// Futures request weight allowed per minute (default Binance's limit).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    futures_weight_budget_per_min: Option<u64>,
+    #[serde(skip)]
+    spot_weight: weight::WeightTracker,
+    #[serde(skip)]
+    futures_weight: weight::WeightTracker,
+    /// Look for a newer GitHub release at startup.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    check_updates: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<SummaryConfig>,
+    /// New listing announcements, see `announcements.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    announcements: Option<AnnouncementsConfig>,
+    /// Ids of the announcements already seen, oldest first.
+    #[serde(default, skip_serializing_if = "VecDeque::is_empty")]
+    announcements_seen: VecDeque<String>,
+    /// When the last price summary was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary_last_sent: Option<u64>,
+    /// Drop the state of symbols no alert watches any more at startup, as
+    /// `--prune-state` does.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    prune_state_on_load: bool,
+    /// How long to stop saving state after writing config.json failed, for
+    /// example on a full disk, before trying again (default 300).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_retry_secs: Option<u64>,
+    #[serde(skip)]
+    state_writes: StateWrites,
+    /// Record a price in the history only once it moved this many percent
+    /// from the last recorded one, or `history_max_gap_secs` passed. Keeps
+    /// the history of flat markets short, at the cost of sample counted
+    /// indicators such as Bollinger bands spanning more time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    history_min_change_pct: Option<f64>,
+    /// Longest time between recorded prices with `history_min_change_pct`
+    /// (default 600).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    history_max_gap_secs: Option<u64>,
+    /// Price history older than this many seconds at startup is dropped
+    /// rather than resumed, so indicators don't bridge a long downtime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warm_restart_max_age_secs: Option<u64>,
+    /// Recent prices per symbol, persisted so indicator windows survive restarts.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    price_history: HashMap<String, VecDeque<PriceSample>>,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// A separately scheduled watch list. Anything not set falls back to the
+/// top-level configuration.
+#[derive(Debug, Deserialize, Serialize)]
+struct Profile {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    check_interval: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    email: Option<EmailConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    webhook: Option<WebhookConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ntfy: Option<NtfyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    telegram: Option<TelegramConfig>,
     currencies: Vec<CurrencyAlert>,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// A daily digest of every alert's price, see `summary.rs`.
+#[derive(Debug, Deserialize, Serialize)]
+struct SummaryConfig {
+    /// Local time of day it is sent, "HH:MM".
+    at: String,
+    #[serde(default)]
+    channel: Channel,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// A feed polled for exchange announcements.
+#[derive(Debug, Deserialize, Serialize)]
+struct AnnouncementsConfig {
+    /// Binance's announcement API, a JSON Feed or an RSS feed, default
+    /// Binance's new listings.
+    #[serde(default = "default_announcements_url")]
+    url: String,
+    /// Only announcements whose title contains one of these, ignoring case,
+    /// are notified. Empty notifies all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keywords: Vec<String>,
+    #[serde(default = "default_announcements_interval")]
+    interval_secs: u64,
+    /// Sent through this channel alone instead of all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel: Option<Channel>,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_announcements_url() -> String {
+    announcements::BINANCE_LISTINGS_URL.to_string()
+}
+
+fn default_announcements_interval() -> u64 {
+    300
+}
+
+/// A single notification channel, for messages not sent through all of them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+enum Channel {
+    #[default]
+    Email,
+    Webhook,
+    Ntfy,
+    Telegram,
+}
+
+/// Shortens the withold when a symbol is volatile and lengthens it when calm:
+/// at `reference_pct` volatility (the standard deviation of the last
+/// `periods` prices, in percent of their mean) the configured withold
+/// applies, at twice that half of it, clamped to `min_secs`..=`max_secs`.
+#[derive(Debug, Deserialize, Serialize)]
+struct AdaptiveCooldown {
+    reference_pct: f64,
+    min_secs: u64,
+    max_secs: u64,
+    #[serde(default = "default_cooldown_periods")]
+    periods: usize,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_cooldown_periods() -> usize {
+    20
+}
+
+impl AdaptiveCooldown {
+    /// The withold for `base` seconds, `base` itself until `history` holds
+    /// `periods` prices.
+    fn withold_secs(&self, base: u64, history: Option<&VecDeque<PriceSample>>) -> u64 {
+        let Some((mean, stddev)) = last_prices(history, self.periods).and_then(mean_stddev) else {
+            return base;
+        };
+        let volatility = stddev / mean * 100.0;
+        let scaled = if volatility > 0.0 {
+            base as f64 * self.reference_pct / volatility
+        } else {
+            f64::INFINITY
+        };
+        (scaled.min(self.max_secs as f64) as u64).max(self.min_secs)
+    }
 }
 
+/// Whether a notification goes out through every channel or only the first
+/// one that takes it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+enum ChannelMode {
+    #[default]
+    AllChannels,
+    /// Try `channel_order` one by one, stopping at the first delivery.
+    FirstSuccess,
+}
+
+/// Optional status server, see `server.rs`.
+#[derive(Debug, Deserialize, Serialize)]
+struct HttpConfig {
+    /// Address to listen on, e.g. "0.0.0.0:8080".
+    bind: String,
+    /// Base URL used in notification links, defaults to `http://<bind>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_url: Option<String>,
+    /// How long `GET /ack/<token>` snoozes the acknowledged alert.
+    #[serde(default = "default_ack_extension_h")]
+    ack_extension_h: u64,
+    /// Required as `Authorization: Bearer <token>` by `/evaluate` and the
+    /// mute endpoints, which are disabled without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_ack_extension_h() -> u64 {
+    24
+}
+
+/// Generic HTTP webhook channel, POSTed a JSON body for every notification.
+#[derive(Debug, Deserialize, Serialize)]
+struct WebhookConfig {
+    url: String,
+    #[serde(default)]
+    payload: WebhookPayload,
+    /// Body size limit, see `EmailConfig::max_body_len`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_body_len: Option<usize>,
+    /// Signs every request: `signature_header` carries "sha256=" followed by
+    /// the lowercase hex HMAC-SHA256, keyed with this secret's UTF-8 bytes,
+    /// of the raw request body exactly as sent. Receivers should recompute it
+    /// over the bytes they received, before parsing them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hmac_secret: Option<String>,
+    /// Header the signature is sent in (default "X-Signature").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature_header: Option<String>,
+    /// Most messages sent per minute (default 30, Discord's limit). Further
+    /// ones wait in a queue and go out over the following checks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rate_limit_per_min: Option<u32>,
+    #[serde(flatten)]
+    retry: RetryPolicy,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// Receives every fired, reminded and cleared alert on its own the moment it
+/// is evaluated, as `{"sequence": n, "event": ..., "symbol": ..., ...}`,
+/// see `events.rs`. Unlike `webhook` it is neither batched nor rate limited,
+/// and failed events are resent on later checks.
+#[derive(Debug, Deserialize, Serialize)]
+struct EventWebhookConfig {
+    url: String,
+    /// Signs each event like `WebhookConfig::hmac_secret`, in "X-Signature".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hmac_secret: Option<String>,
+    #[serde(flatten)]
+    retry: RetryPolicy,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum WebhookPayload {
+    /// `{"text": "<subject>\n<body>"}`, understood by most chat webhooks.
+    #[default]
+    Text,
+    /// Versioned structured payload, see `notify::WebhookBody`.
+    Json,
+}
+
+/// Messages from a Telegram bot to one chat.
+#[derive(Debug, Deserialize, Serialize)]
+struct TelegramConfig {
+    bot_token: String,
+    chat_id: String,
+    #[serde(default)]
+    parse_mode: ParseMode,
+    /// Per-alert template with the placeholders of `notify::render_alert`,
+    /// written in `parse_mode` markup. Placeholder values are escaped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<String>,
+    /// Body size limit (default 4096, Telegram's message limit).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_body_len: Option<usize>,
+    /// Most messages sent per minute (default 20, Telegram's limit for
+    /// groups), see `WebhookConfig::rate_limit_per_min`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rate_limit_per_min: Option<u32>,
+    #[serde(flatten)]
+    retry: RetryPolicy,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+/// Markup a message is written in, see Telegram's `parse_mode`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum ParseMode {
+    #[default]
+    Plain,
+    MarkdownV2,
+    Html,
+}
+
+/// Push notifications through ntfy.sh or a self-hosted ntfy server.
+#[derive(Debug, Deserialize, Serialize)]
+struct NtfyConfig {
+    #[serde(default = "default_ntfy_url")]
+    url: String,
+    topic: String,
+    /// Access token for protected topics.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    /// Body size limit (default 4096, ntfy's message limit).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_body_len: Option<usize>,
+    /// Most messages sent per minute (default 12, ntfy.sh's sustained rate),
+    /// see `WebhookConfig::rate_limit_per_min`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rate_limit_per_min: Option<u32>,
+    #[serde(flatten)]
+    retry: RetryPolicy,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_ntfy_url() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+/// Prometheus Pushgateway the metrics are pushed to after every cycle.
 #[derive(Debug, Deserialize, Serialize)]
+struct PushgatewayConfig {
+    /// e.g. "http://localhost:9091"
+    url: String,
+    #[serde(default = "default_push_job")]
+    job: String,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_push_job() -> String {
+    "bye-watch".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MqttConfig {
+    /// "host:port" of the broker, e.g. "homeassistant.local:1883".
+    broker: String,
+    /// Topics are "<topic_prefix>/<SYMBOL>/price" and
+    /// "<topic_prefix>/alert/<rule>/state".
+    #[serde(default = "default_topic_prefix")]
+    topic_prefix: String,
+    /// Where Home Assistant listens for discovery messages.
+    #[serde(default = "default_discovery_prefix")]
+    discovery_prefix: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
+}
+
+fn default_topic_prefix() -> String {
+    "bye-watch".to_string()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+/// Minimum number of samples kept per symbol in `Config::price_history`,
+/// indicator conditions may need more.
+const HISTORY_SAMPLES: usize = 60;
+
+/// A single observed price, kept between checks for rate-of-change conditions,
+/// indicators and charts.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+struct PriceSample {
+    price: f64,
+    timestamp: u64,
+}
+
+impl PriceSample {
+    /// Percent change per minute from `self` to `later`, `None` if no time passed.
+    fn velocity_to(&self, later: &PriceSample) -> Option<f64> {
+        let elapsed_min = later.timestamp.checked_sub(self.timestamp)? as f64 / 60.0;
+        if elapsed_min <= 0.0 || self.price == 0.0 {
+            return None;
+        }
+        Some((later.price - self.price) / self.price * 100.0 / elapsed_min)
+    }
+}
+
+/// TWAP by symbol and minutes.
+type Twaps = HashMap<(String, u64), String>;
+
+/// The TWAP of every `Twap` price basis, from the price history and the
+/// latest spot price.
+fn twaps(config: &Config, market: &MarketData, current_time: u64) -> Twaps {
+    let mut twaps = Twaps::new();
+    for currency in &config.currencies {
+        let PriceBasis::Twap { minutes } = currency.price_basis else {
+            continue;
+        };
+        let latest = market
+            .prices
+            .iter()
+            .find(|p| p.symbol == currency.symbol)
+            .and_then(|p| parse_price(&p.price))
+            .map(|price| PriceSample {
+                price,
+                timestamp: current_time,
+            });
+        let history = config.price_history.get(&currency.symbol);
+        if let Some(value) = latest
+            .zip(history)
+            .and_then(|(latest, history)| twap(history, latest, minutes * 60))
+        {
+            twaps.insert((currency.symbol.clone(), minutes), format!("{:.8}", value));
+        }
+    }
+    twaps
+}
+
+/// The raw price `currency` is judged by: the mark price of futures
+/// symbols, otherwise the one of its price basis.
+fn basis_price<'a>(
+    currency: &CurrencyAlert,
+    market: &'a MarketData,
+    twaps: &'a Twaps,
+) -> Option<&'a String> {
+    if currency.is_futures() {
+        market
+            .premium_index
+            .iter()
+            .find(|p| p.symbol == currency.symbol)
+            .map(|p| &p.mark_price)
+    } else if let PriceBasis::CandleClose { interval } = &currency.price_basis {
+        market
+            .candle_closes
+            .get(&(currency.symbol.clone(), interval.clone()))
+    } else if let PriceBasis::Vwap { interval, periods } = &currency.price_basis {
+        market
+            .vwaps
+            .get(&(currency.symbol.clone(), interval.clone(), *periods))
+    } else if let PriceBasis::Twap { minutes } = currency.price_basis {
+        twaps.get(&(currency.symbol.clone(), minutes))
+    } else {
+        market
+            .prices
+            .iter()
+            .find(|p| p.symbol == currency.symbol)
+            .map(|p| &p.price)
+    }
+}
+
+/// Time weighted average over the `window_secs` up to `latest`, each sample
+/// counting for as long as it stood until the next one, so unevenly spaced
+/// checks are weighted correctly. `None` until `history` covers the window.
+fn twap(history: &VecDeque<PriceSample>, latest: PriceSample, window_secs: u64) -> Option<f64> {
+    let start = latest.timestamp.checked_sub(window_secs)?;
+    let mut samples = history
+        .iter()
+        .filter(|sample| sample.timestamp < latest.timestamp)
+        .chain(std::iter::once(&latest))
+        .peekable();
+    if samples.peek()?.timestamp > start {
+        return None;
+    }
+    let (mut weighted, mut total) = (0.0, 0.0);
+    while let Some(sample) = samples.next() {
+        let Some(next) = samples.peek() else {
+            break;
+        };
+        if next.timestamp > start {
+            let held = (next.timestamp - sample.timestamp.max(start)) as f64;
+            weighted += sample.price * held;
+            total += held;
+        }
+    }
+    Some(if total > 0.0 {
+        weighted / total
+    } else {
+        latest.price
+    })
+}
+
+/// What to do when Binance returns no price for a configured symbol.
+#[derive(Debug, Default, Deserialize, Serialize)]
+enum MissingSymbolPolicy {
+    /// Log once until the symbol shows up again.
+    WarnOnce,
+    /// Log on every check.
+    #[default]
+    WarnEveryCycle,
+    /// Stop the daemon.
+    Error,
+}
+
+/// How the alerts of a notification body are laid out.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+enum BodyFormat {
+    /// Each alert through the channel's template.
+    #[default]
+    Template,
+    /// One row per alert in an aligned monospace table, without the
+    /// condition details and snooze links.
+    Table,
+}
+
+/// How prices are written in notifications and console output.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+enum NumberFormat {
+    /// `1234.56`
+    #[default]
+    Plain,
+    /// `1,234.56`
+    English,
+    /// `1.234,56`
+    European,
+}
+
+impl NumberFormat {
+    /// Re-formats a plain number such as "1234.56" using this format.
+    fn apply(self, plain: &str) -> String {
+        let (thousands, decimal) = match self {
+            NumberFormat::Plain => return plain.to_string(),
+            NumberFormat::English => (',', '.'),
+            NumberFormat::European => ('.', ','),
+        };
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain),
+        };
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+        if !integer.bytes().all(|b| b.is_ascii_digit()) {
+            return plain.to_string();
+        }
+
+        let mut out = String::from(sign);
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                out.push(thousands);
+            }
+            out.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            out.push(decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// `value` shortened to two decimals with a K, M or B suffix, e.g.
+    /// 1234567.891 -> "1.23M". `None` below a thousand.
+    fn compact(self, value: f64) -> Option<String> {
+        let (scale, suffix) = [(1e9, "B"), (1e6, "M"), (1e3, "K")]
+            .into_iter()
+            .find(|(scale, _)| value.abs() >= *scale)?;
+        Some(format!(
+            "{}{}",
+            self.apply(&format!("{:.2}", value / scale)),
+            suffix
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct MissingSymbolError(String);
+
+impl std::fmt::Display for MissingSymbolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No price data found for {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingSymbolError {}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 enum AlertCondition {
     Above,
     Below,
+    /// Binance's own 24h `priceChangePercent` is above `pct`.
+    DailyChangeAbove {
+        pct: f64,
+    },
+    /// Binance's own 24h `priceChangePercent` is below `pct`.
+    DailyChangeBelow {
+        pct: f64,
+    },
+    /// Price moved faster than `pct_per_min` (either direction) between the
+    /// last two checks.
+    Velocity {
+        pct_per_min: f64,
+    },
+    /// Perpetual futures funding rate, in percent, is above `pct`.
+    FundingAbove {
+        pct: f64,
+    },
+    /// Perpetual futures funding rate, in percent, is below `pct`.
+    FundingBelow {
+        pct: f64,
+    },
+    /// Price is more than `k` standard deviations away from the mean of the
+    /// previous `periods` checks.
+    BollingerBreak {
+        periods: usize,
+        k: f64,
+    },
+    /// Price breaches the `percentile` (0-100) of the previous `periods`
+    /// checks: above it for percentiles of 50 and up, below it otherwise.
+    PercentileBreak {
+        periods: usize,
+        percentile: f64,
+    },
+    /// The mean of the previous `short` checks is more than `pct` percent
+    /// above or below the mean of the previous `long` ones.
+    SmaSpread {
+        short: usize,
+        long: usize,
+        pct: f64,
+    },
+    /// Summed quantity of the top `depth_limit` levels on `side` is more than
+    /// `ratio` times that of the other side.
+    DepthImbalance {
+        ratio: f64,
+        side: DepthSide,
+    },
+    /// Price deviates from `peg` by more than `tolerance` in either direction.
+    Depeg {
+        peg: f64,
+        tolerance: f64,
+    },
+    /// Price is above `factor` times the current price of `reference`.
+    RelativeAbove {
+        reference: String,
+        factor: f64,
+    },
+    /// Price is below `factor` times the current price of `reference`.
+    RelativeBelow {
+        reference: String,
+        factor: f64,
+    },
+    /// The symbol's last trade is more than `max_age_secs` old, as happens
+    /// when a market is halted or illiquid. A symbol without any trade on
+    /// record counts as stale.
+    DataStale {
+        max_age_secs: u64,
+    },
+    /// Price is within `pct` percent below the 24h high.
+    NearHigh {
+        pct: f64,
+    },
+    /// Price is within `pct` percent above the 24h low.
+    NearLow {
+        pct: f64,
+    },
+    /// More than `threshold` trades in the last 24h, per Binance's ticker.
+    TradeCountAbove {
+        threshold: u64,
+    },
+    /// Price is beyond `price` on `side` while the 24h quote volume is above
+    /// `min_volume`, so thin-market moves are ignored.
+    PriceBreakWithVolume {
+        side: BreakSide,
+        price: f64,
+        min_volume: f64,
+    },
+    /// The symbol's price on `exchange_a` and `exchange_b` differs by more
+    /// than `pct` percent of the lower one.
+    ArbSpread {
+        exchange_a: PriceSource,
+        exchange_b: PriceSource,
+        pct: f64,
+    },
+    /// The spot price deviates from the futures index price by more than
+    /// `pct` percent of the index.
+    IndexDeviation {
+        pct: f64,
+    },
+    /// A comparison over the prices of any symbols, see `formula.rs`.
+    Formula {
+        formula: String,
+    },
+}
+
+/// Where a spot price can be fetched from, see `price_sources`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+enum PriceSource {
+    Binance,
+    Kraken,
+}
+
+impl std::fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceSource::Binance => write!(f, "Binance"),
+            PriceSource::Kraken => write!(f, "Kraken"),
+        }
+    }
+}
+
+/// The price an alert is evaluated against.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+enum PriceBasis {
+    /// The latest trade price.
+    #[default]
+    Spot,
+    /// Close of the most recent closed candle of `interval`, e.g. "5m",
+    /// ignoring the noise within the candle that is still open.
+    CandleClose { interval: String },
+    /// Volume weighted average of the typical price (high + low + close) / 3
+    /// over the last `periods` candles of `interval`, including the open one.
+    Vwap {
+        interval: String,
+        #[serde(default = "default_vwap_periods")]
+        periods: usize,
+    },
+    /// Time weighted average of the checked prices over the last `minutes`,
+    /// from the price history.
+    Twap { minutes: u64 },
+    /// Mark price of the perpetual futures symbol, from `/fapi/v1/premiumIndex`.
+    Mark,
+}
+
+fn default_vwap_periods() -> usize {
+    24
+}
+
+impl PriceBasis {
+    fn is_spot(&self) -> bool {
+        *self == PriceBasis::Spot
+    }
+}
+
+/// Intervals accepted by `/api/v3/klines`.
+const KLINE_INTERVALS: [&str; 16] = [
+    "1s", "1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w",
+    "1M",
+];
+
+/// Which side of the order book must dominate for a `DepthImbalance` alert.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum DepthSide {
+    Bid,
+    Ask,
+    Either,
+}
+
+/// Which way the price must break for a `PriceBreakWithVolume` alert.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum BreakSide {
+    Above,
+    Below,
+}
+
+impl AlertCondition {
+    fn needs_daily_ticker(&self) -> bool {
+        matches!(
+            self,
+            AlertCondition::DailyChangeAbove { .. }
+                | AlertCondition::DailyChangeBelow { .. }
+                | AlertCondition::NearHigh { .. }
+                | AlertCondition::NearLow { .. }
+                | AlertCondition::TradeCountAbove { .. }
+                | AlertCondition::PriceBreakWithVolume { .. }
+        )
+    }
+
+    /// Number of past samples the condition needs, 0 for stateless conditions.
+    fn history_len(&self) -> usize {
+        match self {
+            AlertCondition::BollingerBreak { periods, .. }
+            | AlertCondition::PercentileBreak { periods, .. } => *periods,
+            AlertCondition::SmaSpread { long, .. } => *long,
+            _ => 0,
+        }
+    }
+
+    /// The other symbols whose prices the condition is computed from.
+    fn reference_symbols(&self) -> Vec<&str> {
+        match self {
+            AlertCondition::RelativeAbove { reference, .. }
+            | AlertCondition::RelativeBelow { reference, .. } => vec![reference],
+            AlertCondition::Formula { formula } => formula::symbols(formula),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The exchanges quoted besides the regular price.
+    fn exchanges(&self) -> Vec<PriceSource> {
+        match self {
+            AlertCondition::ArbSpread {
+                exchange_a,
+                exchange_b,
+                ..
+            } => vec![*exchange_a, *exchange_b],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Conditions that compare the spot price to the futures index price.
+    fn needs_index_price(&self) -> bool {
+        matches!(self, AlertCondition::IndexDeviation { .. })
+    }
+
+    fn needs_depth(&self) -> bool {
+        matches!(self, AlertCondition::DepthImbalance { .. })
+    }
+
+    fn needs_last_trade(&self) -> bool {
+        matches!(self, AlertCondition::DataStale { .. })
+    }
+
+    /// Futures conditions are evaluated against the futures market only.
+    fn needs_premium_index(&self) -> bool {
+        matches!(
+            self,
+            AlertCondition::FundingAbove { .. } | AlertCondition::FundingBelow { .. }
+        )
+    }
 }
 
 impl std::fmt::Display for AlertCondition {
@@ -29,6 +1116,75 @@ impl std::fmt::Display for AlertCondition {
         match self {
             AlertCondition::Above => write!(f, "Above"),
             AlertCondition::Below => write!(f, "Below"),
+            AlertCondition::DailyChangeAbove { pct } => write!(f, "24h change above {}%", pct),
+            AlertCondition::DailyChangeBelow { pct } => write!(f, "24h change below {}%", pct),
+            AlertCondition::Velocity { pct_per_min } => {
+                write!(f, "moving faster than {}%/min", pct_per_min)
+            }
+            AlertCondition::FundingAbove { pct } => write!(f, "funding rate above {}%", pct),
+            AlertCondition::FundingBelow { pct } => write!(f, "funding rate below {}%", pct),
+            AlertCondition::BollingerBreak { periods, k } => {
+                write!(f, "outside {}-period Bollinger bands (k={})", periods, k)
+            }
+            AlertCondition::PercentileBreak {
+                periods,
+                percentile,
+            } => {
+                let side = if *percentile >= 50.0 {
+                    "above"
+                } else {
+                    "below"
+                };
+                write!(f, "{} its {}-period p{}", side, periods, percentile)
+            }
+            AlertCondition::SmaSpread { short, long, pct } => {
+                write!(f, "{}/{}-period SMA gap above {}%", short, long, pct)
+            }
+            AlertCondition::DepthImbalance { ratio, side } => {
+                let side = match side {
+                    DepthSide::Bid => "bid",
+                    DepthSide::Ask => "ask",
+                    DepthSide::Either => "bid/ask",
+                };
+                write!(f, "{} depth imbalance above {}x", side, ratio)
+            }
+            AlertCondition::Depeg { peg, tolerance } => {
+                write!(f, "more than {} away from its {} peg", tolerance, peg)
+            }
+            AlertCondition::RelativeAbove { reference, factor } => {
+                write!(f, "above {} x {}", factor, reference)
+            }
+            AlertCondition::RelativeBelow { reference, factor } => {
+                write!(f, "below {} x {}", factor, reference)
+            }
+            AlertCondition::DataStale { max_age_secs } => {
+                write!(f, "exchange data older than {}s", max_age_secs)
+            }
+            AlertCondition::NearHigh { pct } => write!(f, "within {}% of its 24h high", pct),
+            AlertCondition::NearLow { pct } => write!(f, "within {}% of its 24h low", pct),
+            AlertCondition::TradeCountAbove { threshold } => {
+                write!(f, "more than {} trades in 24h", threshold)
+            }
+            AlertCondition::PriceBreakWithVolume {
+                side,
+                price,
+                min_volume,
+            } => {
+                let side = match side {
+                    BreakSide::Above => "above",
+                    BreakSide::Below => "below",
+                };
+                write!(f, "{} {} on 24h volume above {}", side, price, min_volume)
+            }
+            AlertCondition::ArbSpread {
+                exchange_a,
+                exchange_b,
+                pct,
+            } => write!(f, "{}/{} spread above {}%", exchange_a, exchange_b, pct),
+            AlertCondition::IndexDeviation { pct } => {
+                write!(f, "more than {}% away from its index price", pct)
+            }
+            AlertCondition::Formula { formula } => write!(f, "formula {}", formula),
         }
     }
 }
@@ -36,169 +1192,6299 @@ impl std::fmt::Display for AlertCondition {
 #[derive(Debug, Deserialize, Serialize)]
 struct CurrencyAlert {
     symbol: String,
-    threshold: f64,
+    /// Required by `Above`/`Below` alerts, unless they have tiers or a
+    /// `threshold_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<Threshold>,
     alert_condition: AlertCondition,
     last_alerted: Option<u64>,
+    /// Concrete value an expression threshold was resolved to against the
+    /// price seen at startup. Cleared by `--re-anchor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    anchored_threshold: Option<f64>,
+    /// Alerts sharing a group are sent together in their own email.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    /// Stop re-alerting after this many notifications until the condition clears.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_repeats: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    repeat_count: u32,
+    /// Token of the last notification, redeemable via `GET /ack/<token>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ack_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snoozed_until: Option<u64>,
+    /// Overrides the global `notify_on_clear` for this alert.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify_on_clear: Option<bool>,
+    /// While the condition stays met after alerting, send a lower severity
+    /// reminder every this many hours.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reminder_interval_h: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_reminded: Option<u64>,
+    /// When the alert saw its first sample.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initialized: Option<u64>,
+    /// Seconds the condition must hold continuously before it fires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confirm_secs: Option<u64>,
+    /// When the condition most recently became true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    met_since: Option<u64>,
+    /// Consecutive checks the condition must be met before it fires, a
+    /// debounce counted in checks rather than seconds like `confirm_secs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confirm_cycles: Option<u32>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    met_cycles: u32,
+    /// Only fire Above/Below once the price crosses the threshold, not when it
+    /// is already past it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    require_crossing: bool,
+    /// Whether a price exactly at the threshold meets Above/Below (`>=` and
+    /// `<=`). Off by default: the price must be strictly past the threshold.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    inclusive: bool,
+    /// File mapping symbols to thresholds, overriding `threshold` and re-read
+    /// every check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold_file: Option<String>,
+    /// Local time window such as "15:30-22:00" outside of which the alert is
+    /// not evaluated at all. May wrap past midnight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_hours: Option<String>,
+    /// Local time such as "2026-10-16 22:00" (or a UTC offset, RFC 3339)
+    /// after which the alert is skipped. A date alone lasts through that day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<String>,
+    /// Set once the alert was announced through `notify_on_arm`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    armed: bool,
+    /// Set once `expires_at` passed and saved with the state, so the expiry
+    /// is logged only once, even across restarts.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    expired: bool,
+    /// Which price the condition is evaluated against.
+    #[serde(default, skip_serializing_if = "PriceBasis::is_spot")]
+    price_basis: PriceBasis,
+    /// Layered Above/Below targets used instead of `threshold`. Each fires
+    /// when crossed, subject to this alert's confirmation, withold, snooze
+    /// and repeat settings, and re-arms when the price is back. The tiers
+    /// crossed in the same check are sent as one alert.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tiers: Vec<Tier>,
+    /// Alias from `display_names`, shown in place of the symbol.
+    #[serde(skip)]
+    display_name: Option<String>,
+    /// Value for this symbol from `threshold_file`, as of the last check.
+    #[serde(skip)]
+    file_threshold: Option<f64>,
+    /// Price tick from exchangeInfo, used for display and comparisons.
+    #[serde(skip)]
+    tick: Option<PriceTick>,
+    #[serde(skip)]
+    base_asset: Option<String>,
+    #[serde(skip)]
+    quote_asset: Option<String>,
+    #[serde(skip)]
+    last_price: Option<f64>,
+    #[serde(skip)]
+    condition_met: bool,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
 }
 
-#[derive(Debug, Deserialize)]
-struct BinancePrice {
-    symbol: String,
-    price: String,
-}
+impl CurrencyAlert {
+    /// The numeric threshold in effect, `None` for an expression that has not
+    /// been anchored to a price yet.
+    fn threshold(&self) -> Option<f64> {
+        if self.threshold_file.is_some() {
+            return self.file_threshold;
+        }
+        self.threshold
+            .as_ref()
+            .and_then(Threshold::fixed)
+            .or(self.anchored_threshold)
+    }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config_path = "config.json";
-    let config_content = fs::read_to_string(config_path)?;
-    let mut config: Config = serde_json::from_str(&config_content)?;
+    /// The display name if one is configured, otherwise the raw symbol.
+    fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.symbol)
+    }
 
-    println!("Bye-Watch Started");
-    println!(
-        "Checking {} alerts every {} seconds",
-        config.currencies.len(),
-        config.check_interval
-    );
+    /// A futures symbol, priced from the premium index instead of spot.
+    fn is_futures(&self) -> bool {
+        self.alert_condition.needs_premium_index() || self.price_basis == PriceBasis::Mark
+    }
 
-    loop {
-        match check_currencies(&mut config) {
-            Ok(_) => println!(
-                "Check completed at {}",
-                Local::now().format("%d-%m-%Y %H:%M:%S")
-            ),
-            Err(e) => eprintln!("Error during check: {}", e),
+    fn fired(&self, event: AlertEvent, price: f64, details: String, timestamp: u64) -> FiredAlert {
+        FiredAlert {
+            symbol: self.symbol.clone(),
+            name: self.name().to_string(),
+            rule: self.rule(),
+            condition: self.alert_condition.to_string(),
+            threshold: self.threshold(),
+            price,
+            details,
+            timestamp,
+            ack_url: None,
+            decimals: self.tick.map(|tick| tick.decimals),
+            event,
+            index: None,
         }
-
-        let updated_config = serde_json::to_string_pretty(&config)?;
-        fs::write(config_path, updated_config)?;
-        std::thread::sleep(Duration::from_secs(config.check_interval));
     }
-}
 
-fn fetch_prices(config: &Config) -> Result<Vec<BinancePrice>, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::new();
-    let url = "https://api.binance.com/api/v3/ticker/price";
-    let response = client.get(url).send()?;
-    if !response.status().is_success() {
-        return Err(format!("Failed to fetch prices: HTTP {}", response.status()).into());
+    /// What firing changes, to roll back when the alert is not delivered.
+    fn notified(&self) -> Notified {
+        Notified {
+            last_alerted: self.last_alerted,
+            repeat_count: self.repeat_count,
+            last_reminded: self.last_reminded,
+            tiers: self
+                .tiers
+                .iter()
+                .map(|tier| (tier.hit_at, tier.repeat_count, tier.last_reminded))
+                .collect(),
+        }
     }
 
-    let prices: Vec<BinancePrice> = response.json()?;
-
-    let currency_symbols: Vec<String> =
-        config.currencies.iter().map(|c| c.symbol.clone()).collect();
+    /// Rolls back to `notified`, so the alert fires again on the next check.
+    /// How long the condition has been met is kept.
+    fn restore(&mut self, notified: &Notified) {
+        self.last_alerted = notified.last_alerted;
+        self.repeat_count = notified.repeat_count;
+        self.last_reminded = notified.last_reminded;
+        for (tier, &(hit_at, repeat_count, last_reminded)) in
+            self.tiers.iter_mut().zip(&notified.tiers)
+        {
+            tier.hit_at = hit_at;
+            tier.repeat_count = repeat_count;
+            tier.last_reminded = last_reminded;
+        }
+    }
 
-    let filtered_prices: Vec<BinancePrice> = prices
-        .into_iter()
-        .filter(|price_data| currency_symbols.contains(&price_data.symbol))
-        .collect();
+    /// Runs every tier through the alert state machine against `price`,
+    /// returning each tier's decision in the order the price crosses them.
+    fn evaluate_tiers(
+        &mut self,
+        price: f64,
+        policy: &alert::AlertPolicy,
+        now: u64,
+    ) -> Vec<(usize, AlertDecision)> {
+        let on_tick = |value: f64| self.tick.map_or(value, |tick| tick.round(value));
+        let above = matches!(self.alert_condition, AlertCondition::Above);
+        let inclusive = self.inclusive;
+        let mut decisions = Vec::new();
+        for (index, tier) in self.tiers.iter_mut().enumerate() {
+            let (price, threshold) = (on_tick(price), on_tick(tier.threshold));
+            let met = match (above, inclusive) {
+                (true, true) => price >= threshold,
+                (true, false) => price > threshold,
+                (false, true) => price <= threshold,
+                (false, false) => price < threshold,
+            };
+            let mut state = tier.state();
+            state.rewind(now);
+            let (decision, state) = alert::evaluate(state, policy, met, now);
+            tier.set_state(state);
+            decisions.push((index, decision));
+        }
+        let tiers = &self.tiers;
+        decisions.sort_by(|&(a, _), &(b, _)| {
+            let order = tiers[a].threshold.total_cmp(&tiers[b].threshold);
+            if above {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+        decisions
+    }
 
-    Ok(filtered_prices)
-}
+    /// The state machine settings of this alert.
+    fn policy(
+        &self,
+        withold_secs: u64,
+        quiet_since_first_sample: Option<u64>,
+    ) -> alert::AlertPolicy {
+        alert::AlertPolicy {
+            withold_secs,
+            confirm_secs: self.confirm_secs,
+            confirm_cycles: self.confirm_cycles,
+            max_repeats: self.max_repeats,
+            snoozed_until: self.snoozed_until,
+            reminder_interval_h: self.reminder_interval_h,
+            quiet_since_first_sample,
+        }
+    }
 
-fn check_currencies(config: &mut Config) -> Result<(), Box<dyn std::error::Error>> {
-    let prices = fetch_prices(config)?;
+    /// A link acknowledging the alert from the notification, when the HTTP
+    /// endpoint is enabled. Replaces any earlier token.
+    fn new_ack_url(&mut self, http: Option<&HttpConfig>) -> Option<String> {
+        let http = http?;
+        let token = server::new_token();
+        let base = http
+            .public_url
+            .clone()
+            .unwrap_or_else(|| format!("http://{}", http.bind));
+        let url = format!("{}/ack/{}", base.trim_end_matches('/'), token);
+        self.ack_token = Some(token);
+        Some(url)
+    }
 
-    let mut body = String::new();
-    let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-    for currency in &mut config.currencies {
-        if let Some(current_price) = prices.iter().find(|p| p.symbol == currency.symbol) {
-            let alert_triggered = match currency.alert_condition {
-                AlertCondition::Above => {
-                    current_price.price.parse::<f64>().unwrap() > currency.threshold
-                }
-                AlertCondition::Below => {
-                    current_price.price.parse::<f64>().unwrap() < currency.threshold
-                }
-            };
+    /// Human readable form of the rule, e.g. "BTCEUR Above 50000".
+    fn rule(&self) -> String {
+        match &self.price_basis {
+            PriceBasis::Spot => self.condition_rule(),
+            PriceBasis::CandleClose { interval } => {
+                format!("{} on {} close", self.condition_rule(), interval)
+            }
+            PriceBasis::Vwap { interval, periods } => {
+                format!(
+                    "{} on {} x {} VWAP",
+                    self.condition_rule(),
+                    periods,
+                    interval
+                )
+            }
+            PriceBasis::Twap { minutes } => {
+                format!("{} on {}m TWAP", self.condition_rule(), minutes)
+            }
+            PriceBasis::Mark => format!("{} on mark price", self.condition_rule()),
+        }
+    }
 
-            let withold_time_secs = config.withold_notification_h.unwrap_or(
-                24 * 60 * 60, // Default to 24 hours if not specified
+    fn condition_rule(&self) -> String {
+        if !self.tiers.is_empty() {
+            let thresholds: Vec<String> =
+                self.tiers.iter().map(|t| t.threshold.to_string()).collect();
+            return format!(
+                "{} {} tiers {}",
+                self.name(),
+                self.alert_condition,
+                thresholds.join("/")
             );
-            if alert_triggered {
-                let should_alert = match currency.last_alerted {
-                    Some(timestamp) => current_time - timestamp > withold_time_secs,
-                    None => true,
-                };
-                if should_alert {
-                    println!(
-                        "Alert triggered for {} {} {}. Current price {} (now: {}, last alerted: {}, withold_time_secs: {})",
-                        currency.symbol,
-                        currency.alert_condition,
-                        currency.threshold,
-                        current_price.price,
-                        current_time,
-                        currency.last_alerted.unwrap_or(0),
-                        withold_time_secs,
-                    );
-                    let price_text = format!(
-                        "\n{} {} threshold {}\nCurrent price: {:.2}\nTime: {}\n",
-                        currency.symbol,
-                        currency.alert_condition,
-                        currency.threshold,
-                        current_price.price.parse::<f64>().unwrap_or(0.0),
-                        Local::now().format("%d-%m-%Y %H:%M:%S")
-                    );
-                    body.push_str(&price_text);
-                    currency.last_alerted = Some(current_time);
-                } else {
-                    println!(
-                        "Alert condition met for {} {} {}, but already alerted within {:.2} hours",
-                        currency.symbol,
-                        currency.alert_condition,
-                        currency.threshold,
-                        withold_time_secs as f64 / 3600.0,
-                    );
-                }
-            } else {
-                if currency.last_alerted.is_some() {
-                    println!(
-                        "Condition no longer met for {} {} {}, resetting alert status",
-                        currency.symbol, currency.alert_condition, currency.threshold
-                    );
-                    currency.last_alerted = None;
-                } else {
-                    println!(
-                        "Alert condition NOT met for {} {} {}, current price: {}",
-                        currency.symbol,
-                        currency.alert_condition,
-                        currency.threshold,
-                        current_price.price
-                    );
+        }
+        if let (AlertCondition::Above | AlertCondition::Below, Some(path)) =
+            (&self.alert_condition, &self.threshold_file)
+        {
+            let value = self
+                .file_threshold
+                .map_or_else(|| "?".to_string(), |v| v.to_string());
+            return format!(
+                "{} {} {} ({})",
+                self.name(),
+                self.alert_condition,
+                value,
+                path
+            );
+        }
+        match (&self.alert_condition, &self.threshold) {
+            (AlertCondition::Above | AlertCondition::Below, Some(Threshold::Value(value))) => {
+                format!("{} {} {}", self.name(), self.alert_condition, value)
+            }
+            (AlertCondition::Above | AlertCondition::Below, Some(Threshold::Expression(expr))) => {
+                match self.anchored_threshold {
+                    Some(value) => format!(
+                        "{} {} {} ({})",
+                        self.name(),
+                        self.alert_condition,
+                        value,
+                        expr
+                    ),
+                    None => format!("{} {} {}", self.name(), self.alert_condition, expr),
                 }
             }
-        } else {
-            eprintln!("No price data found for {}", currency.symbol);
+            _ => format!("{} {}", self.name(), self.alert_condition),
         }
     }
+}
 
-    if !body.is_empty() {
-        let body = format!("Found the following crypto alerts\n\n {}", body);
-        send_email(config, "[bye-watch] Price Alert", &body)?;
-        println!("{}", body);
-    }
+/// An alert's notification state before a check, see `CurrencyAlert::restore`.
+#[derive(Debug)]
+struct Notified {
+    last_alerted: Option<u64>,
+    repeat_count: u32,
+    last_reminded: Option<u64>,
+    /// `hit_at`, `repeat_count` and `last_reminded` of every tier.
+    tiers: Vec<(Option<u64>, u32, Option<u64>)>,
+}
 
-    Ok(())
+/// One target of an alert's `tiers`. Each tier goes through the alert state
+/// machine on its own, with the settings of its alert.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Tier {
+    threshold: f64,
+    label: String,
+    /// Free-form, e.g. "high", shown next to the label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    severity: Option<String>,
+    /// When the tier last fired, until the price is back on the other side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hit_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    repeat_count: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    met_since: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_reminded: Option<u64>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    met_cycles: u32,
+    #[serde(flatten, default, skip_serializing_if = "BTreeMap::is_empty")]
+    unknown_fields: UnknownFields,
 }
 
-fn send_email(
-    config: &Config,
-    subject: &str,
-    body: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let email = Message::builder()
-        .from(config.email.username.parse().unwrap())
-        .to(config.email.username.parse().unwrap())
-        .subject(subject)
-        .body(body.to_string())
-        .unwrap();
+impl Tier {
+    fn state(&self) -> alert::AlertState {
+        alert::AlertState {
+            last_alerted: self.hit_at,
+            repeat_count: self.repeat_count,
+            met_since: self.met_since,
+            last_reminded: self.last_reminded,
+            met_cycles: self.met_cycles,
+        }
+    }
 
-    let creds = Credentials::new(config.email.username.clone(), config.email.password.clone());
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(creds)
-        .build();
+    fn set_state(&mut self, state: alert::AlertState) {
+        self.hit_at = state.last_alerted;
+        self.repeat_count = state.repeat_count;
+        self.met_since = state.met_since;
+        self.last_reminded = state.last_reminded;
+        self.met_cycles = state.met_cycles;
+    }
+
+    fn describe(&self) -> String {
+        match &self.severity {
+            Some(severity) => format!("{} at {} [{}]", self.label, self.threshold, severity),
+            None => format!("{} at {}", self.label, self.threshold),
+        }
+    }
+}
+
+/// A symbol's `PRICE_FILTER` tick size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PriceTick {
+    size: f64,
+    /// Decimal places of `size`, e.g. 2 for "0.01000000".
+    decimals: usize,
+}
+
+impl PriceTick {
+    fn parse(tick_size: &str) -> Option<Self> {
+        let size = parse_price(tick_size).filter(|size| *size > 0.0)?;
+        let decimals = tick_size
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.trim_end_matches('0').len());
+        Some(PriceTick { size, decimals })
+    }
+
+    /// `value` rounded to the nearest tick.
+    fn round(&self, value: f64) -> f64 {
+        let rounded = (value / self.size).round() * self.size;
+        // strip the float noise the multiplication reintroduces
+        format!("{:.*}", self.decimals, rounded)
+            .parse()
+            .unwrap_or(rounded)
+    }
+}
+
+/// Quote assets recognised when splitting a symbol, longest first so that
+/// e.g. "FDUSD" wins over "USD".
+const QUOTE_ASSETS: &[&str] = &[
+    "FDUSD", "USDT", "USDC", "TUSD", "BUSD", "BIDR", "IDRT", "BTC", "ETH", "BNB", "EUR", "GBP",
+    "TRY", "BRL", "JPY", "AUD", "RUB", "UAH", "ZAR", "PLN", "RON", "ARS", "MXN", "COP", "DAI",
+    "USD",
+];
+
+/// Splits a Binance symbol such as "BTCEUR" into ("BTC", "EUR").
+fn split_symbol(symbol: &str) -> Option<(String, String)> {
+    QUOTE_ASSETS.iter().find_map(|quote| {
+        symbol
+            .strip_suffix(quote)
+            .filter(|base| !base.is_empty())
+            .map(|base| (base.to_string(), quote.to_string()))
+    })
+}
+
+impl Config {
+    /// Alerts of the top-level list and of every profile.
+    fn all_currencies(&self) -> impl Iterator<Item = &CurrencyAlert> {
+        self.currencies
+            .iter()
+            .chain(self.profiles.iter().flat_map(|p| p.currencies.iter()))
+    }
+
+    fn all_currencies_mut(&mut self) -> impl Iterator<Item = &mut CurrencyAlert> {
+        self.currencies.iter_mut().chain(
+            self.profiles
+                .iter_mut()
+                .flat_map(|p| p.currencies.iter_mut()),
+        )
+    }
+
+    /// Runs `f` with the currencies and channels of profile `index` swapped
+    /// into the top-level configuration.
+    fn with_profile<T>(&mut self, index: usize, f: impl FnOnce(&mut Config) -> T) -> T {
+        // Decided once, swapping back may leave an override unset
+        let profile = &self.profiles[index];
+        let overrides = [
+            profile.webhook.is_some(),
+            profile.ntfy.is_some(),
+            profile.telegram.is_some(),
+        ];
+        self.swap_profile(index, overrides);
+        let result = f(self);
+        self.swap_profile(index, overrides);
+        result
+    }
+
+    /// Swaps the currencies and the email of profile `index` with the
+    /// top-level ones, and its webhook, ntfy and telegram channels where
+    /// `overrides` says so.
+    fn swap_profile(&mut self, index: usize, [webhook, ntfy, telegram]: [bool; 3]) {
+        let profile = &mut self.profiles[index];
+        std::mem::swap(&mut self.currencies, &mut profile.currencies);
+        if let Some(email) = profile.email.as_mut() {
+            std::mem::swap(&mut self.email, email);
+        }
+        if webhook {
+            std::mem::swap(&mut self.webhook, &mut profile.webhook);
+        }
+        if ntfy {
+            std::mem::swap(&mut self.ntfy, &mut profile.ntfy);
+        }
+        if telegram {
+            std::mem::swap(&mut self.telegram, &mut profile.telegram);
+        }
+    }
+
+    /// Waits until a spot request of `weight` fits the budget, then books it.
+    fn spend_spot_weight(&self, label: &str, weight: u64) {
+        let budget = self
+            .weight_budget_per_min
+            .unwrap_or(SPOT_WEIGHT_LIMIT_PER_MIN);
+        self.spot_weight.spend(label, weight, budget);
+    }
+
+    fn spend_futures_weight(&self, label: &str, weight: u64) {
+        let budget = self
+            .futures_weight_budget_per_min
+            .unwrap_or(FUTURES_WEIGHT_LIMIT_PER_MIN);
+        self.futures_weight.spend(label, weight, budget);
+    }
+
+    fn timestamp_format(&self) -> &str {
+        self.timestamp_format
+            .as_deref()
+            .unwrap_or(DEFAULT_TIMESTAMP_FORMAT)
+    }
+
+    /// Builder for every outbound HTTP client, bound to `local_address`.
+    fn http_client(&self) -> reqwest::blocking::ClientBuilder {
+        let local_address: Option<std::net::IpAddr> = self
+            .local_address
+            .as_deref()
+            .and_then(|address| address.parse().ok());
+        reqwest::blocking::Client::builder().local_address(local_address)
+    }
+
+    /// Full URL of a spot API endpoint such as "/api/v3/ticker/price".
+    fn binance_url(&self, path: &str) -> String {
+        let base = self
+            .binance_base_url
+            .as_deref()
+            .unwrap_or(DEFAULT_BINANCE_BASE_URL);
+        format!("{}{}", base.trim_end_matches('/'), path)
+    }
+
+    /// GETs a spot API endpoint, rotating through `binance_endpoints` if
+    /// there are any. Client errors such as a rate limit are returned as they
+    /// are, another host would answer the same.
+    fn binance_get(
+        &self,
+        client: &reqwest::blocking::Client,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<reqwest::blocking::Response, Box<dyn std::error::Error>> {
+        let endpoints = &self.binance_endpoints;
+        if endpoints.is_empty() {
+            return Ok(client.get(self.binance_url(path)).query(query).send()?);
+        }
+        let start = self
+            .next_endpoint
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let mut failures = Vec::new();
+        for i in 0..endpoints.len() {
+            let base = endpoints[(start + i) % endpoints.len()].trim_end_matches('/');
+            match client.get(format!("{}{}", base, path)).query(query).send() {
+                Ok(response) if !response.status().is_server_error() => {
+                    println!("{} served by {}", path, base);
+                    return Ok(response);
+                }
+                Ok(response) => failures.push(format!("{}: HTTP {}", base, response.status())),
+                Err(e) => failures.push(format!("{}: {}", base, e)),
+            }
+        }
+        Err(format!(
+            "every Binance endpoint failed for {} ({})",
+            path,
+            failures.join("; ")
+        )
+        .into())
+    }
+
+    fn is_muted(&self, currency: &CurrencyAlert) -> bool {
+        let muted = |asset: &Option<String>, list: &[String]| {
+            asset
+                .as_ref()
+                .is_some_and(|asset| list.iter().any(|m| m.eq_ignore_ascii_case(asset)))
+        };
+        muted(&currency.base_asset, &self.muted_base_assets)
+            || muted(&currency.quote_asset, &self.muted_quote_assets)
+    }
+}
+
+/// Parses "HH:MM-HH:MM" into start and end minutes since midnight.
+fn parse_active_hours(window: &str) -> Result<(u32, u32), String> {
+    window
+        .split_once('-')
+        .and_then(|(start, end)| Some((parse_time_of_day(start)?, parse_time_of_day(end)?)))
+        .ok_or_else(|| format!("invalid active_hours \"{}\", expected HH:MM-HH:MM", window))
+}
+
+/// Parses an alert's `expires_at` into Unix seconds, in local time unless it
+/// states an offset.
+fn parse_expiry(at: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid expires_at \"{}\", expected YYYY-MM-DD, YYYY-MM-DD HH:MM or RFC 3339",
+            at
+        )
+    };
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(at) {
+        return Ok(time.timestamp().max(0) as u64);
+    }
+    let local = chrono::NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(at, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .or_else(|| {
+            let day = chrono::NaiveDate::parse_from_str(at, "%Y-%m-%d").ok()?;
+            day.succ_opt()?.and_hms_opt(0, 0, 0)
+        })
+        .ok_or_else(invalid)?;
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.timestamp().max(0) as u64)
+        .ok_or_else(invalid)
+}
+
+/// Parses "HH:MM" into minutes since midnight.
+fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u32>().ok()?, minutes.parse::<u32>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Whether `minute` (since midnight) falls inside `[start, end)`, wrapping
+/// past midnight when `end` is before `start`.
+fn in_active_hours((start, end): (u32, u32), minute: u32) -> bool {
+    if start <= end {
+        (start..end).contains(&minute)
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// A threshold is either a plain number or an expression relative to the
+/// price at startup: `"-5%"`, `"+2.5%"`, `"price*0.95"`, `"price-100"`.
+/// A string holding just a number, such as `"0.00012345"`, is a fixed
+/// threshold written as the exchange formats its prices. Like every other
+/// threshold it is stored and compared as an `f64`, not as a decimal.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+enum Threshold {
+    Value(f64),
+    Expression(String),
+}
+
+impl Threshold {
+    /// The fixed value of a threshold that is not relative to the price.
+    fn fixed(&self) -> Option<f64> {
+        match self {
+            Threshold::Value(value) => Some(*value),
+            Threshold::Expression(expr) => parse_decimal(expr),
+        }
+    }
+}
+
+/// Parses a plain decimal such as `"0.00012345"` into the nearest `f64`.
+/// Exponents and the special values `f64` accepts are rejected. Prices go
+/// through the same correctly rounded conversion in `parse_price`, so a
+/// threshold and a price with the same digits become the same `f64` and
+/// compare as equal. Rounding to nearest never reorders two decimals, and
+/// only ones differing past about the 15th significant digit, far below any
+/// exchange's tick, can meet in one `f64`. That is exact enough for
+/// `Above`/`Below`, so no decimal type is pulled in.
+fn parse_decimal(text: &str) -> Option<f64> {
+    let digits = text.trim().trim_start_matches(['+', '-']);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let plain = !(whole.is_empty() && fraction.is_empty())
+        && whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit());
+    plain.then(|| text.trim().parse().ok()).flatten()
+}
+
+/// Resolves a threshold expression against `price`.
+fn resolve_threshold_expression(expr: &str, price: f64) -> Result<f64, String> {
+    let compact: String = expr.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || format!("Invalid threshold expression \"{}\"", expr);
+
+    if let Some(pct) = compact.strip_suffix('%') {
+        let pct: f64 = pct.parse().map_err(|_| invalid())?;
+        return Ok(price * (1.0 + pct / 100.0));
+    }
+
+    let rest = compact.strip_prefix("price").ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Ok(price);
+    }
+    let mut chars = rest.chars();
+    let op = chars.next().ok_or_else(invalid)?;
+    let operand: f64 = chars.as_str().parse().map_err(|_| invalid())?;
+    match op {
+        '*' => Ok(price * operand),
+        '/' if operand != 0.0 => Ok(price / operand),
+        '+' => Ok(price + operand),
+        '-' => Ok(price - operand),
+        _ => Err(invalid()),
+    }
+}
+
+const DEFAULT_MIN_CHECK_INTERVAL: u64 = 5;
+
+/// Binance's documented request weight limits per minute and IP.
+const SPOT_WEIGHT_LIMIT_PER_MIN: u64 = 6000;
+const FUTURES_WEIGHT_LIMIT_PER_MIN: u64 = 2400;
+
+/// Clamps check intervals to the configured floor and warns when the
+/// resulting request rate would exceed the exchange's weight limits.
+fn enforce_request_rate(config: &mut Config) {
+    let floor = config
+        .min_check_interval
+        .unwrap_or(DEFAULT_MIN_CHECK_INTERVAL)
+        .max(1);
+    if config.check_interval < floor {
+        eprintln!(
+            "check_interval of {} seconds is below the minimum of {}, using {}",
+            config.check_interval, floor, floor
+        );
+        config.check_interval = floor;
+    }
+    for profile in &mut config.profiles {
+        if let Some(interval) = profile.check_interval.filter(|i| *i < floor) {
+            eprintln!(
+                "Profile {}: check_interval of {} seconds is below the minimum of {}, using {}",
+                profile.name, interval, floor, floor
+            );
+            profile.check_interval = Some(floor);
+        }
+    }
+
+    let mut spot_per_min = 0;
+    let mut futures_per_min = 0;
+    let depth_limit = config.depth_limit.unwrap_or(DEFAULT_DEPTH_LIMIT);
+    let mut add_job = |currencies: &[CurrencyAlert], interval: u64| {
+        if currencies.is_empty() {
+            return;
+        }
+        let (spot, futures) = request_weight(currencies, depth_limit);
+        spot_per_min += spot * 60 / interval;
+        futures_per_min += futures * 60 / interval;
+    };
+    add_job(&config.currencies, config.check_interval);
+    for profile in &config.profiles {
+        add_job(
+            &profile.currencies,
+            profile.check_interval.unwrap_or(config.check_interval),
+        );
+    }
+
+    if spot_per_min > SPOT_WEIGHT_LIMIT_PER_MIN {
+        eprintln!(
+            "Estimated spot request weight of {}/min exceeds Binance's limit of {}/min, increase check_interval",
+            spot_per_min, SPOT_WEIGHT_LIMIT_PER_MIN
+        );
+    }
+    if futures_per_min > FUTURES_WEIGHT_LIMIT_PER_MIN {
+        eprintln!(
+            "Estimated futures request weight of {}/min exceeds Binance's limit of {}/min, increase check_interval",
+            futures_per_min, FUTURES_WEIGHT_LIMIT_PER_MIN
+        );
+    }
+}
+
+/// Spot and futures request weight of a single check of `currencies`.
+fn request_weight(currencies: &[CurrencyAlert], depth_limit: u32) -> (u64, u64) {
+    let plan = FetchPlan::new(currencies);
+
+    let mut spot = PRICE_TICKER_WEIGHT;
+    spot += daily_ticker_weight(plan.daily_tickers.len());
+    spot += depth_weight(depth_limit) * plan.depth.len() as u64;
+    spot += TRADES_WEIGHT * plan.last_trades.len() as u64;
+    spot += KLINES_WEIGHT * (plan.klines.len() + plan.vwap.len()) as u64;
+    let futures = match plan.premium_index.len() {
+        0 => 0,
+        _ => PREMIUM_INDEX_WEIGHT,
+    };
+    (spot, futures)
+}
+
+/// `/api/v3/ticker/price` for all symbols.
+const PRICE_TICKER_WEIGHT: u64 = 4;
+const EXCHANGE_INFO_WEIGHT: u64 = 20;
+/// `/fapi/v1/premiumIndex` for all symbols.
+const PREMIUM_INDEX_WEIGHT: u64 = 10;
+/// `/api/v3/klines` for one symbol and interval.
+const KLINES_WEIGHT: u64 = 2;
+/// `/api/v3/trades` for one symbol.
+const TRADES_WEIGHT: u64 = 25;
+/// Most candles `/api/v3/klines` returns at once.
+const KLINES_LIMIT: usize = 1000;
+
+/// `/api/v3/ticker/24hr` for `symbols` symbols.
+fn daily_ticker_weight(symbols: usize) -> u64 {
+    match symbols {
+        0 => 0,
+        1..=20 => 2,
+        21..=100 => 40,
+        _ => 80,
+    }
+}
+
+/// `/api/v3/depth` for one symbol, scaling with the requested limit.
+fn depth_weight(limit: u32) -> u64 {
+    match limit {
+        0..=100 => 5,
+        101..=500 => 25,
+        501..=1000 => 50,
+        _ => 250,
+    }
+}
+
+/// Warns about alerts repeating an earlier one's symbol, condition and
+/// threshold within the same list. They work, but share nothing and fire twice.
+fn warn_duplicate_alerts(label: &str, currencies: &[CurrencyAlert]) {
+    for (index, currency) in currencies.iter().enumerate() {
+        let duplicate = currencies[..index].iter().any(|earlier| {
+            earlier.symbol == currency.symbol
+                && earlier.alert_condition == currency.alert_condition
+                && earlier.threshold == currency.threshold
+                && earlier.price_basis == currency.price_basis
+        });
+        if duplicate {
+            eprintln!(
+                "Duplicate alert {}{}, it will notify alongside the first one",
+                currency.rule(),
+                label
+            );
+        }
+    }
+}
 
-    println!("Sending email");
-    mailer.send(&email)?;
+fn validate_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    warn_duplicate_alerts("", &config.currencies);
+    for profile in &config.profiles {
+        warn_duplicate_alerts(
+            &format!(" in profile {}", profile.name),
+            &profile.currencies,
+        );
+    }
+    let emails = std::iter::once(&config.email)
+        .chain(config.profiles.iter().filter_map(|p| p.email.as_ref()));
+    for email in emails {
+        notify::from_mailbox(email).map_err(|e| format!("email {}: {}", email.username, e))?;
+    }
+    for currency in config.all_currencies() {
+        match &currency.threshold {
+            None if matches!(
+                currency.alert_condition,
+                AlertCondition::Above | AlertCondition::Below
+            ) && currency.tiers.is_empty()
+                && currency.threshold_file.is_none() =>
+            {
+                return Err(format!(
+                    "{}: {} alerts need a threshold",
+                    currency.symbol, currency.alert_condition
+                )
+                .into());
+            }
+            None => {}
+            Some(Threshold::Value(value)) if !value.is_finite() => {
+                return Err(
+                    format!("{}: threshold {} is not finite", currency.symbol, value).into(),
+                );
+            }
+            Some(Threshold::Value(_)) => {}
+            Some(Threshold::Expression(expr)) if parse_decimal(expr).is_some() => {}
+            Some(Threshold::Expression(expr)) => {
+                resolve_threshold_expression(expr, 1.0)
+                    .map_err(|e| format!("{}: {}", currency.symbol, e))?;
+            }
+        }
+        if !currency.tiers.is_empty() {
+            if !matches!(
+                currency.alert_condition,
+                AlertCondition::Above | AlertCondition::Below
+            ) || currency.threshold_file.is_some()
+            {
+                return Err(format!(
+                    "{}: tiers only apply to Above/Below alerts without a threshold_file",
+                    currency.rule()
+                )
+                .into());
+            }
+            if let Some(tier) = currency
+                .tiers
+                .iter()
+                .find(|t| !t.threshold.is_finite() || t.label.trim().is_empty())
+            {
+                return Err(format!(
+                    "{}: tier \"{}\" needs a label and a finite threshold",
+                    currency.rule(),
+                    tier.label
+                )
+                .into());
+            }
+        }
+        if currency.threshold_file.is_some()
+            && !matches!(
+                currency.alert_condition,
+                AlertCondition::Above | AlertCondition::Below
+            )
+        {
+            return Err(format!(
+                "{}: threshold_file only applies to Above/Below alerts",
+                currency.rule()
+            )
+            .into());
+        }
+        if let Some(window) = &currency.active_hours {
+            parse_active_hours(window).map_err(|e| format!("{}: {}", currency.symbol, e))?;
+        }
+        if let Some(at) = &currency.expires_at {
+            parse_expiry(at).map_err(|e| format!("{}: {}", currency.symbol, e))?;
+        }
+        if currency.price_basis == (PriceBasis::Twap { minutes: 0 }) {
+            return Err(format!("{}: TWAP minutes must be positive", currency.symbol).into());
+        }
+        if let PriceBasis::CandleClose { interval } | PriceBasis::Vwap { interval, .. } =
+            &currency.price_basis
+        {
+            if let PriceBasis::Vwap { periods, .. } = currency.price_basis {
+                if !(1..=KLINES_LIMIT).contains(&periods) {
+                    return Err(format!(
+                        "{}: VWAP periods must be between 1 and {}",
+                        currency.symbol, KLINES_LIMIT
+                    )
+                    .into());
+                }
+            }
+            if !KLINE_INTERVALS.contains(&interval.as_str()) {
+                return Err(format!(
+                    "{}: candle interval \"{}\" is not one of {:?}",
+                    currency.symbol, interval, KLINE_INTERVALS
+                )
+                .into());
+            }
+            if currency.alert_condition.needs_premium_index() {
+                return Err(format!(
+                    "{}: futures conditions are always evaluated on the mark price",
+                    currency.rule()
+                )
+                .into());
+            }
+        }
+        if let Some(value) = currency.anchored_threshold.filter(|v| !v.is_finite()) {
+            return Err(format!(
+                "{}: anchored threshold {} is not finite",
+                currency.symbol, value
+            )
+            .into());
+        }
+        let condition_value = match currency.alert_condition {
+            AlertCondition::DailyChangeAbove { pct } | AlertCondition::DailyChangeBelow { pct } => {
+                Some(pct)
+            }
+            AlertCondition::Velocity { pct_per_min } => Some(pct_per_min),
+            AlertCondition::FundingAbove { pct } | AlertCondition::FundingBelow { pct } => {
+                Some(pct)
+            }
+            AlertCondition::BollingerBreak { periods, k } => {
+                if periods < 2 || k <= 0.0 {
+                    return Err(format!(
+                        "{}: needs at least 2 periods and a positive k",
+                        currency.rule()
+                    )
+                    .into());
+                }
+                Some(k)
+            }
+            AlertCondition::PercentileBreak {
+                periods,
+                percentile,
+            } => {
+                if periods < 2 || !(0.0..=100.0).contains(&percentile) {
+                    return Err(format!(
+                        "{}: needs at least 2 periods and a percentile between 0 and 100",
+                        currency.rule()
+                    )
+                    .into());
+                }
+                Some(percentile)
+            }
+            AlertCondition::SmaSpread { short, long, pct } => {
+                if short == 0 || long <= short || pct <= 0.0 {
+                    return Err(format!(
+                        "{}: needs a short period below the long one and a positive pct",
+                        currency.rule()
+                    )
+                    .into());
+                }
+                Some(pct)
+            }
+            AlertCondition::DepthImbalance { ratio, .. } => {
+                if ratio <= 1.0 {
+                    return Err(format!("{}: ratio must be greater than 1", currency.rule()).into());
+                }
+                Some(ratio)
+            }
+            AlertCondition::Depeg { peg, tolerance } => {
+                if !(peg > 0.0 && tolerance > 0.0) {
+                    return Err(
+                        format!("{}: peg and tolerance must be positive", currency.rule()).into(),
+                    );
+                }
+                Some(peg + tolerance)
+            }
+            AlertCondition::RelativeAbove { factor, .. }
+            | AlertCondition::RelativeBelow { factor, .. } => {
+                if factor <= 0.0 {
+                    return Err(format!("{}: factor must be positive", currency.rule()).into());
+                }
+                Some(factor)
+            }
+            AlertCondition::DataStale { max_age_secs } => {
+                if max_age_secs == 0 {
+                    return Err(
+                        format!("{}: max_age_secs must be positive", currency.rule()).into(),
+                    );
+                }
+                None
+            }
+            AlertCondition::NearHigh { pct } | AlertCondition::NearLow { pct } => {
+                if pct <= 0.0 {
+                    return Err(format!("{}: pct must be positive", currency.rule()).into());
+                }
+                Some(pct)
+            }
+            AlertCondition::PriceBreakWithVolume {
+                price, min_volume, ..
+            } => {
+                if price <= 0.0 || min_volume < 0.0 {
+                    return Err(format!(
+                        "{}: needs a positive price and a non-negative min_volume",
+                        currency.rule()
+                    )
+                    .into());
+                }
+                Some(price + min_volume)
+            }
+            AlertCondition::ArbSpread {
+                exchange_a,
+                exchange_b,
+                pct,
+            } => {
+                if exchange_a == exchange_b || pct <= 0.0 {
+                    return Err(format!(
+                        "{}: needs two different exchanges and a positive pct",
+                        currency.rule()
+                    )
+                    .into());
+                }
+                Some(pct)
+            }
+            AlertCondition::IndexDeviation { pct } => {
+                if pct <= 0.0 {
+                    return Err(format!("{}: pct must be positive", currency.rule()).into());
+                }
+                Some(pct)
+            }
+            AlertCondition::Formula { ref formula } => {
+                formula::parse(formula)
+                    .map_err(|e| format!("{}: invalid formula: {}", currency.rule(), e))?;
+                None
+            }
+            AlertCondition::TradeCountAbove { .. }
+            | AlertCondition::Above
+            | AlertCondition::Below => None,
+        };
+        if let Some(value) = condition_value.filter(|v| !v.is_finite()) {
+            return Err(format!("{}: {} is not finite", currency.rule(), value).into());
+        }
+    }
+    if let Some(epsilon) = config
+        .comparison_epsilon
+        .filter(|e| !e.is_finite() || *e < 0.0)
+    {
+        return Err(format!(
+            "comparison_epsilon {} must be a non-negative number",
+            epsilon
+        )
+        .into());
+    }
+    if let Some(format) = &config.timestamp_format {
+        let invalid = chrono::format::StrftimeItems::new(format)
+            .any(|item| matches!(item, chrono::format::Item::Error));
+        let mut sample = String::new();
+        if invalid
+            || std::fmt::Write::write_fmt(
+                &mut sample,
+                format_args!("{}", Local::now().format(format)),
+            )
+            .is_err()
+        {
+            return Err(format!(
+                "timestamp_format \"{}\" is not a valid strftime pattern",
+                format
+            )
+            .into());
+        }
+    }
+    if let Some(address) = &config.local_address {
+        address
+            .parse::<std::net::IpAddr>()
+            .map_err(|_| format!("local_address \"{}\" is not an IP address", address))?;
+    }
+    let configured = |channel: Channel| notify::is_configured(config, channel);
+    if let Some(summary) = &config.summary {
+        if parse_time_of_day(&summary.at).is_none() {
+            return Err(format!("summary at \"{}\" is not a HH:MM time", summary.at).into());
+        }
+        if !configured(summary.channel) {
+            return Err(format!("summary channel {:?} is not configured", summary.channel).into());
+        }
+    }
+    if let Some(channel) = config.arm_channel.filter(|channel| !configured(*channel)) {
+        return Err(format!("arm_channel {:?} is not configured", channel).into());
+    }
+    if let Some(mqtt) = &config.mqtt {
+        if mqtt.password.is_some() && mqtt.username.is_none() {
+            return Err("mqtt password needs a username".into());
+        }
+    }
+    if let Some(feed) = &config.announcements {
+        if feed.interval_secs == 0 {
+            return Err("announcements interval_secs must be at least 1".into());
+        }
+        if let Some(channel) = feed.channel.filter(|channel| !configured(*channel)) {
+            return Err(format!("announcements channel {:?} is not configured", channel).into());
+        }
+    }
+    let rate_limits = [
+        config.webhook.as_ref().and_then(|w| w.rate_limit_per_min),
+        config.ntfy.as_ref().and_then(|n| n.rate_limit_per_min),
+        config.telegram.as_ref().and_then(|t| t.rate_limit_per_min),
+    ];
+    let profile_rate_limits = config.profiles.iter().flat_map(|p| {
+        [
+            p.webhook.as_ref().and_then(|w| w.rate_limit_per_min),
+            p.ntfy.as_ref().and_then(|n| n.rate_limit_per_min),
+            p.telegram.as_ref().and_then(|t| t.rate_limit_per_min),
+        ]
+    });
+    if rate_limits
+        .into_iter()
+        .chain(profile_rate_limits)
+        .any(|limit| limit == Some(0))
+    {
+        return Err("rate_limit_per_min must be at least 1".into());
+    }
+    if let Some(profile) = config.profiles.iter().find(|p| p.check_interval == Some(0)) {
+        return Err(format!(
+            "profile {}: check_interval must be at least 1",
+            profile.name
+        )
+        .into());
+    }
+    if let Some(channel) = config
+        .channel_order
+        .iter()
+        .find(|&&channel| !notify::is_configured(config, channel))
+    {
+        return Err(format!("channel_order lists {:?}, which is not configured", channel).into());
+    }
+    if config
+        .history_min_change_pct
+        .is_some_and(|pct| pct.is_nan() || pct < 0.0)
+    {
+        return Err("history_min_change_pct must not be negative".into());
+    }
+    if let Some(cooldown) = &config.adaptive_cooldown {
+        if cooldown.reference_pct <= 0.0
+            || !cooldown.reference_pct.is_finite()
+            || cooldown.min_secs > cooldown.max_secs
+            || cooldown.periods < 2
+        {
+            return Err(
+                "adaptive_cooldown needs a positive reference_pct, min_secs \
+                        no greater than max_secs and at least 2 periods"
+                    .into(),
+            );
+        }
+    }
+    if config.notify_concurrency == Some(0) {
+        return Err("notify_concurrency must be at least 1".into());
+    }
+    if config.max_consecutive_failures == Some(0) {
+        return Err("max_consecutive_failures must be at least 1".into());
+    }
+    if let Some((symbol, _)) = config.price_sources.iter().find(|(_, s)| s.is_empty()) {
+        return Err(format!("price_sources for {} lists no source", symbol).into());
+    }
+    if let Some(limit) = config.depth_limit.filter(|l| !DEPTH_LIMITS.contains(l)) {
+        return Err(format!("depth_limit {} is not one of {:?}", limit, DEPTH_LIMITS).into());
+    }
     Ok(())
 }
+
+#[derive(Debug, Deserialize)]
+struct BinancePrice {
+    symbol: String,
+    price: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceTicker24h {
+    symbol: String,
+    price_change_percent: String,
+    high_price: String,
+    low_price: String,
+    /// Number of trades in the 24h window.
+    count: u64,
+    /// 24h volume in the quote asset.
+    quote_volume: String,
+}
+
+/// A trade from `/api/v3/trades`.
+#[derive(Debug, Deserialize)]
+struct BinanceTrade {
+    /// Milliseconds since the epoch.
+    time: i64,
+}
+
+/// Order book snapshot from `/api/v3/depth`, levels as `[price, quantity]`.
+#[derive(Debug, Deserialize)]
+struct BinanceDepth {
+    bids: Vec<(String, String)>,
+    asks: Vec<(String, String)>,
+}
+
+impl BinanceDepth {
+    /// Summed bid and ask quantity, `None` if a side is empty or unparsable.
+    fn volumes(&self) -> Option<(f64, f64)> {
+        let sum = |levels: &[(String, String)]| {
+            levels
+                .iter()
+                .map(|(_, qty)| parse_price(qty))
+                .sum::<Option<f64>>()
+                .filter(|total| *total > 0.0)
+        };
+        Some((sum(&self.bids)?, sum(&self.asks)?))
+    }
+}
+
+/// Keys of a config object that match none of its fields, such as a
+/// misspelled `witdhold_notification_h`, collected by a flattened
+/// `unknown_fields` on every config struct. They are rejected at load unless
+/// `lenient_config` is set, and kept so that saving the config does not drop
+/// them.
+type UnknownFields = BTreeMap<String, serde_json::Value>;
+
+/// Everything fetched from the exchange for one check.
+#[derive(Debug, Default)]
+struct MarketData {
+    prices: Vec<BinancePrice>,
+    daily_tickers: Vec<BinanceTicker24h>,
+    premium_index: Vec<futures::PremiumIndex>,
+    depth: HashMap<String, BinanceDepth>,
+    /// Seconds since the epoch of the latest trade, by symbol, `None` for a
+    /// symbol without any trade on record.
+    last_trades: HashMap<String, Option<u64>>,
+    /// Close of the latest closed candle, by symbol and interval.
+    candle_closes: HashMap<(String, String), String>,
+    vwaps: Vwaps,
+    fallback_sources: FallbackSources,
+    exchange_prices: ExchangePrices,
+}
+
+/// Prices from exchanges other than Binance, by symbol and exchange.
+type ExchangePrices = HashMap<(String, PriceSource), String>;
+
+/// VWAP by symbol, interval and periods.
+type Vwaps = HashMap<(String, String, usize), String>;
+
+/// Symbols whose price came from a fallback in `price_sources`.
+type FallbackSources = HashMap<String, PriceSource>;
+
+/// Why bye-watch gave up, telling supervisors apart failures that need a
+/// human from ones worth a restart. Other errors exit with 1.
+#[derive(Debug)]
+enum Fatal {
+    /// config.json is missing, unparsable or invalid, or names a symbol the
+    /// exchange doesn't list. Exit code 2.
+    Config(Box<dyn std::error::Error>),
+    /// The startup check could not reach the exchange. Only raised with
+    /// `strict_startup`, otherwise checks keep being retried and a lasting
+    /// outage ends in `Failing`. Exit code 3.
+    Network(Box<dyn std::error::Error>),
+    /// A notification that had to go out, such as the one of
+    /// `--simulate-alert`, failed: its email was not sent, or with
+    /// `FirstSuccess` no channel took it. Exit code 4.
+    Notification(Box<dyn std::error::Error>),
+    /// `max_consecutive_failures` checks in a row failed. Exit code 5.
+    Failing(Box<dyn std::error::Error>),
+}
+
+impl Fatal {
+    fn config(e: impl Into<Box<dyn std::error::Error>>) -> Self {
+        Fatal::Config(e.into())
+    }
+
+    fn exit_code(&self) -> u8 {
+        match self {
+            Fatal::Config(_) => 2,
+            Fatal::Network(_) => 3,
+            Fatal::Notification(_) => 4,
+            Fatal::Failing(_) => 5,
+        }
+    }
+}
+
+impl std::fmt::Display for Fatal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fatal::Config(e) => write!(f, "Invalid configuration: {}", e),
+            Fatal::Network(e) => write!(f, "{}", e),
+            Fatal::Notification(e) => write!(f, "Notification failed: {}", e),
+            Fatal::Failing(e) => write!(f, "Giving up: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Fatal {}
+
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            let code = e.downcast_ref::<Fatal>().map_or(1, Fatal::exit_code);
+            std::process::ExitCode::from(code)
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = "config.json";
+    if std::env::args().any(|arg| arg == "--init") {
+        return init_config(config_path);
+    }
+    let (mut config, migrations) = load_config(config_path)?;
+    let unknown = unknown_fields(&config);
+    if !unknown.is_empty() {
+        let unknown = unknown.join(", ");
+        if !config.lenient_config {
+            return Err(Fatal::config(format!(
+                "unknown field(s) in {}: {} (set lenient_config to ignore)",
+                config_path, unknown
+            ))
+            .into());
+        }
+        eprintln!("Ignoring unknown field(s) in {}: {}", config_path, unknown);
+    }
+    if !migrations.is_empty() {
+        for change in &migrations {
+            eprintln!("Migrated {}: {}", config_path, change);
+        }
+        save_config(config_path, &config)?;
+    }
+    if let Some(path) = arg_value("--import-alerts") {
+        import::import_alerts(&mut config, &path)?;
+        validate_config(&config).map_err(Fatal::Config)?;
+        save_config(config_path, &config)?;
+        return Ok(());
+    }
+    if let Some(spec) = arg_value("--snooze") {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        config.snooze_until = match spec.as_str() {
+            "off" => None,
+            _ => Some(now + parse_duration(&spec)?),
+        };
+        save_config(config_path, &config)?;
+        match config.snooze_until {
+            Some(until) => println!(
+                "Snoozed all notifications but operational notices until {}",
+                notify::format_time(until, config.timestamp_format())
+            ),
+            None => println!("Notifications are no longer snoozed"),
+        }
+        return Ok(());
+    }
+    validate_config(&config).map_err(Fatal::Config)?;
+    let prune = std::env::args().any(|arg| arg == "--prune-state");
+    if prune || config.prune_state_on_load {
+        let pruned = prune_state(&mut config);
+        for entry in &pruned {
+            println!("Pruned {}", entry);
+        }
+        if prune {
+            if pruned.is_empty() {
+                println!("No stale state found");
+            } else {
+                save_config(config_path, &config)?;
+            }
+            return Ok(());
+        }
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    for change in restore_price_history(&mut config, now) {
+        println!("{}", change);
+    }
+    enforce_request_rate(&mut config);
+    if std::env::args().any(|arg| arg == "--print-config") {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&effective_config(&config)?)?
+        );
+        return Ok(());
+    }
+    let display_names = config.display_names.clone();
+    for currency in config.all_currencies_mut() {
+        currency.display_name = display_names.get(&currency.symbol).cloned();
+        match split_symbol(&currency.symbol) {
+            Some((base, quote)) => {
+                currency.base_asset = Some(base);
+                currency.quote_asset = Some(quote);
+            }
+            None => eprintln!(
+                "Could not determine base/quote assets of {}, asset filters will not apply",
+                currency.symbol
+            ),
+        }
+    }
+    if let Some(spec) = arg_value("--simulate-alert") {
+        let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+        return simulate_alert(&mut config, &spec, dry_run);
+    }
+    if let Some(path) = arg_value("--price-override") {
+        let prices = load_price_override(&path)?;
+        println!(
+            "Using {} prices from {} instead of the exchange",
+            prices.len(),
+            path
+        );
+        config.price_override = Some(prices);
+    }
+    if std::env::args().any(|arg| arg == "--test-source") {
+        let offline = config.price_override.is_none();
+        enable_test_source(&mut config);
+        println!(
+            "Serving {} at a fixed price of {}{}",
+            TEST_SYMBOL,
+            TEST_SYMBOL_PRICE,
+            if offline {
+                ", other symbols get no price"
+            } else {
+                ""
+            }
+        );
+    }
+    let tui = std::env::args().any(|arg| arg == "--tui");
+    if std::env::args().any(|arg| arg == "--re-anchor") {
+        for currency in config.all_currencies_mut() {
+            currency.anchored_threshold = None;
+        }
+        println!("Re-anchoring expression thresholds to the current prices");
+    }
+
+    println!("Bye-Watch Started");
+    install_panic_hook(config_path);
+    config.warmup_until = config
+        .channel_warmup_retry_secs
+        .map(|secs| SystemTime::now() + Duration::from_secs(secs));
+    if config.check_updates {
+        update::check_for_update(config.http_client());
+    }
+    // Job 0 is the top-level list, job `i + 1` is profile `i`.
+    let mut intervals = vec![config.check_interval];
+    if !config.currencies.is_empty() || config.profiles.is_empty() {
+        println!(
+            "Checking {} alerts every {} seconds",
+            config.currencies.len(),
+            config.check_interval
+        );
+    } else {
+        intervals[0] = 0;
+    }
+    for profile in &config.profiles {
+        let interval = profile.check_interval.unwrap_or(config.check_interval);
+        println!(
+            "Profile {}: checking {} alerts every {} seconds",
+            profile.name,
+            profile.currencies.len(),
+            interval
+        );
+        intervals.push(interval);
+    }
+
+    if let Some(max) = config.startup_delay_secs.filter(|max| *max > 0) {
+        use std::hash::BuildHasher;
+        let random = std::collections::hash_map::RandomState::new().hash_one(SystemTime::now());
+        let delay = random % (max + 1);
+        println!(
+            "Delaying the first check by {} seconds (startup_delay_secs {})",
+            delay, max
+        );
+        std::thread::sleep(Duration::from_secs(delay));
+    }
+
+    for (job, interval) in intervals.iter().enumerate() {
+        if *interval == 0 {
+            continue;
+        }
+        let (label, result) = match job {
+            0 => (String::new(), warmup_check(&config)),
+            _ => (
+                format!(" for profile {}", config.profiles[job - 1].name),
+                config.with_profile(job - 1, |config| warmup_check(config)),
+            ),
+        };
+        if let Err(e) = result {
+            if config.strict_startup {
+                let e = format!("Startup check{} failed: {}", label, e);
+                return Err(Fatal::Network(e.into()).into());
+            }
+            eprintln!("Startup check{} failed, continuing anyway: {}", label, e);
+        }
+    }
+
+    // Overridden prices are compared as given
+    let ticks = match config.price_override {
+        Some(_) => Ok(HashMap::new()),
+        None => fetch_price_ticks(&config),
+    };
+    match ticks {
+        Ok(ticks) => {
+            for currency in config.all_currencies_mut() {
+                currency.tick = ticks.get(&currency.symbol).copied();
+            }
+        }
+        Err(e) => eprintln!(
+            "Could not fetch tick sizes, comparing unrounded prices: {}",
+            e
+        ),
+    }
+
+    if let Some(notification) = arm_notice(&mut config) {
+        let result = match config.arm_channel {
+            Some(channel) => notify::notify_via(&mut config, channel, &notification),
+            None => notify(&mut config, &notification),
+        };
+        match result {
+            Ok(()) => mark_armed(&mut config),
+            Err(e) => eprintln!(
+                "Failed to send the newly armed alerts, retrying at the next start: {}",
+                e
+            ),
+        }
+    }
+    if let Some(feed) = &config.announcements {
+        println!(
+            "Polling announcements every {} seconds from {}",
+            feed.interval_secs, feed.url
+        );
+    }
+    let announcements_interval = config.announcements.as_ref().map(|feed| feed.interval_secs);
+    let bind = config.http.as_ref().map(|http| http.bind.clone());
+    let shared = Arc::new(Mutex::new(config));
+    if let Some(bind) = bind {
+        server::spawn(&bind, Arc::clone(&shared))?;
+    }
+
+    let mut next_due = vec![0; intervals.len()];
+    let mut next_announcements = 0;
+    let mut failures = vec![0u32; intervals.len()];
+    loop {
+        let mut config = shared.lock().unwrap();
+        config.snooze_until = read_snooze(config_path, config.snooze_until);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        for job in 0..intervals.len() {
+            if intervals[job] == 0 || next_due[job] > now {
+                continue;
+            }
+            next_due[job] = now + intervals[job];
+            let (label, result) = match job {
+                0 => (String::new(), check_currencies(&mut config)),
+                _ => (
+                    format!(" for profile {}", config.profiles[job - 1].name),
+                    config.with_profile(job - 1, check_currencies),
+                ),
+            };
+            config.stats.checks_total += 1;
+            match result {
+                Ok(_) => {
+                    failures[job] = 0;
+                    println!(
+                        "Check completed{} at {}",
+                        label,
+                        Local::now().format(config.timestamp_format())
+                    )
+                }
+                Err(e) if e.is::<MissingSymbolError>() => {
+                    save_state(config_path, &mut config, now);
+                    return Err(Fatal::Config(e).into());
+                }
+                Err(e) => {
+                    config.stats.check_errors_total += 1;
+                    failures[job] += 1;
+                    eprintln!("Error during check{}: {}", label, e);
+                    if config.error_notify_after == Some(failures[job]) {
+                        let notification = Notification::operational(
+                            "[bye-watch] Checks failing",
+                            format!(
+                                "The last {} checks{} failed, latest error:\n{}",
+                                failures[job], label, e
+                            ),
+                        );
+                        if let Err(e) = notify(&mut config, &notification) {
+                            eprintln!("Failed to send error notification: {}", e);
+                        }
+                    }
+                    if config.max_consecutive_failures == Some(failures[job]) {
+                        let reason =
+                            format!("{} consecutive checks{} failed", failures[job], label);
+                        let notification = Notification::operational(
+                            "[bye-watch] Exiting",
+                            format!("{}, exiting. Latest error:\n{}", reason, e),
+                        );
+                        if let Err(e) = notify(&mut config, &notification) {
+                            eprintln!("Failed to send exit notification: {}", e);
+                        }
+                        save_state(config_path, &mut config, now);
+                        let e = format!("{}, latest error: {}", reason, e);
+                        return Err(Fatal::Failing(e.into()).into());
+                    }
+                }
+            }
+        }
+
+        if let Some(summary) = &config.summary {
+            let channel = summary.channel;
+            let due = parse_time_of_day(&summary.at)
+                .and_then(|minute| summary::last_scheduled(minute, now))
+                .is_some_and(|scheduled| *config.summary_last_sent.get_or_insert(now) < scheduled);
+            if due {
+                config.summary_last_sent = Some(now);
+                let notification = summary::compose(&config);
+                if let Err(e) = notify::notify_via(&mut config, channel, &notification) {
+                    eprintln!("Failed to send price summary: {}", e);
+                }
+            }
+        }
+
+        if let Some(interval) = announcements_interval.filter(|_| next_announcements <= now) {
+            next_announcements = now + interval;
+            if let Err(e) = announcements::poll(&mut config) {
+                eprintln!("Failed to poll announcements: {}", e);
+            }
+        }
+
+        notify::drain_queues(&mut config);
+        if tui {
+            dashboard::render(&config)?;
+        }
+
+        // Keep a `--snooze` issued while the checks ran
+        config.snooze_until = read_snooze(config_path, config.snooze_until);
+        save_state(config_path, &mut config, now);
+        let push = config.pushgateway.as_ref().map(|gateway| {
+            (
+                config.http_client(),
+                gateway.url.clone(),
+                gateway.job.clone(),
+                metrics::render(&config),
+            )
+        });
+        let mqtt = config
+            .mqtt
+            .clone()
+            .map(|mqtt| (mqtt::messages(&config, &mqtt), mqtt));
+        drop(config);
+        if let Some((client, url, job, body)) = push {
+            if let Err(e) = metrics::push(client, &url, &job, body) {
+                eprintln!("Failed to push metrics to {}: {}", url, e);
+            }
+        }
+        if let Some((messages, mqtt)) = mqtt {
+            if let Err(e) = mqtt::publish(&mqtt, &messages) {
+                eprintln!("Failed to publish to MQTT broker {}: {}", mqtt.broker, e);
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let wake_at = next_due
+            .iter()
+            .zip(&intervals)
+            .filter(|(_, interval)| **interval > 0)
+            .map(|(due, _)| *due)
+            .chain(announcements_interval.map(|_| next_announcements))
+            .min()
+            .unwrap_or(now);
+        std::thread::sleep(Duration::from_secs(wake_at.saturating_sub(now)));
+    }
+}
+
+const DEFAULT_WITHOLD_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_STATE_RETRY_SECS: u64 = 300;
+const DEFAULT_HISTORY_MAX_GAP_SECS: u64 = 600;
+
+/// Whether saving the state has been failing.
+#[derive(Debug, Default)]
+struct StateWrites {
+    failing_since: Option<u64>,
+    paused_until: u64,
+}
+
+/// Saves the state after a check. A failed write is not fatal: checks go on
+/// from the state in memory, and the write is retried after
+/// `state_retry_secs`.
+fn save_state(path: &str, config: &mut Config, now: u64) {
+    if now < config.state_writes.paused_until {
+        return;
+    }
+    match save_config(path, config) {
+        Ok(()) => {
+            if let Some(since) = config.state_writes.failing_since.take() {
+                println!(
+                    "Saving state to {} works again, it failed since {}",
+                    path,
+                    notify::format_time(since, config.timestamp_format())
+                );
+            }
+        }
+        Err(e) => {
+            let retry = config.state_retry_secs.unwrap_or(DEFAULT_STATE_RETRY_SECS);
+            eprintln!(
+                "Warning: could not save state to {} ({}), continuing from memory \
+                 and retrying in {}s",
+                path, e, retry
+            );
+            config.state_writes.failing_since.get_or_insert(now);
+            config.state_writes.paused_until = now + retry;
+        }
+    }
+}
+
+/// Writes the configuration and its state back, without the credentials
+/// that came from `secrets_file` and with `variables` references kept.
+fn save_config(path: &str, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let mut value = serde_json::to_value(config)?;
+    secrets::strip(&mut value, &config.secret_paths);
+    variables::restore(&mut value, &config.variable_paths);
+    fs::write(path, serde_json::to_string_pretty(&value)?)?;
+    Ok(())
+}
+
+/// Removes the per-symbol state of symbols that no alert, in any profile,
+/// watches or references any more. Returns a description of every removed
+/// entry.
+fn prune_state(config: &mut Config) -> Vec<String> {
+    let watched: HashSet<String> = config
+        .all_currencies()
+        .flat_map(|c| {
+            std::iter::once(c.symbol.as_str()).chain(c.alert_condition.reference_symbols())
+        })
+        .map(str::to_string)
+        .collect();
+    let mut pruned = Vec::new();
+    let mut prune = |kind: &str, symbol: &String| {
+        let stale = !watched.contains(symbol);
+        if stale {
+            pruned.push(format!("{} of {}", kind, symbol));
+        }
+        !stale
+    };
+    config
+        .price_history
+        .retain(|symbol, _| prune("price history", symbol));
+    config
+        .symbol_last_notified
+        .retain(|symbol, _| prune("last notification time", symbol));
+    config
+        .muted_symbols
+        .retain(|symbol, _| prune("mute", symbol));
+    pruned.sort();
+    pruned
+}
+
+/// Readies the price history loaded from the config for resuming: windows are
+/// cut to what the alerts now need, and with `warm_restart_max_age_secs`
+/// dropped when too old. Returns a description of every change.
+fn restore_price_history(config: &mut Config, now: u64) -> Vec<String> {
+    let capacities = history_capacities(config);
+    let mut changes = Vec::new();
+    config.price_history.retain(|symbol, history| {
+        let latest = history.back().map_or(0, |sample| sample.timestamp);
+        if let Some(max_age) = config.warm_restart_max_age_secs {
+            if now.saturating_sub(latest) > max_age {
+                changes.push(format!(
+                    "Dropped the price history of {}, last sample {}s ago",
+                    symbol,
+                    now.saturating_sub(latest)
+                ));
+                return false;
+            }
+        }
+        let capacity = capacities.get(symbol).copied().unwrap_or(HISTORY_SAMPLES);
+        if history.len() > capacity {
+            changes.push(format!(
+                "Trimmed the price history of {} from {} to {} samples",
+                symbol,
+                history.len(),
+                capacity
+            ));
+            history.drain(..history.len() - capacity);
+        }
+        true
+    });
+    changes.sort();
+    changes
+}
+
+/// Samples to keep of each watched symbol, enough for its longest indicator.
+fn history_capacities(config: &Config) -> HashMap<String, usize> {
+    let mut capacities: HashMap<String, usize> = HashMap::new();
+    for currency in config.all_currencies() {
+        let capacity = capacities
+            .entry(currency.symbol.clone())
+            .or_insert(HISTORY_SAMPLES);
+        *capacity = (*capacity).max(currency.alert_condition.history_len());
+        if let PriceBasis::Twap { minutes } = currency.price_basis {
+            // One sample from before the window, and slack for jitter
+            let checks = minutes * 60 / config.check_interval.max(1);
+            *capacity = (*capacity).max(checks as usize + 2);
+        }
+    }
+    capacities
+}
+
+/// The configuration with every default filled in, as JSON. Runtime state
+/// and secrets are left out.
+fn effective_config(config: &Config) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    use serde_json::{json, Value};
+
+    fn fill(value: &mut Value, defaults: Value) {
+        if let (Some(object), Value::Object(defaults)) = (value.as_object_mut(), defaults) {
+            for (key, default) in defaults {
+                match object.get(&key) {
+                    None | Some(Value::Null) => {
+                        object.insert(key, default);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    fn fill_email(email: &mut Value) {
+        fill(
+            email,
+            json!({
+                "template": notify::DEFAULT_EMAIL_TEMPLATE,
+                "include_chart": false,
+                "pool_size": notify::DEFAULT_POOL_SIZE,
+                "pool_idle_timeout_s": notify::DEFAULT_POOL_IDLE_TIMEOUT_S,
+            }),
+        );
+        email["password"] = json!("********");
+    }
+    fn fill_alerts(alerts: &mut Value, notify_on_clear: bool) {
+        for alert in alerts.as_array_mut().into_iter().flatten() {
+            fill(
+                alert,
+                json!({
+                    "group": null,
+                    "max_repeats": null,
+                    "notify_on_clear": notify_on_clear,
+                    "active_hours": null,
+                    "expires_at": null,
+                    "threshold_file": null,
+                    "require_crossing": false,
+                    "inclusive": false,
+                    "confirm_secs": null,
+                    "confirm_cycles": null,
+                    "reminder_interval_h": null,
+                    "price_basis": PriceBasis::Spot,
+                }),
+            );
+            if let Some(alert) = alert.as_object_mut() {
+                for state in [
+                    "last_alerted",
+                    "anchored_threshold",
+                    "repeat_count",
+                    "ack_token",
+                    "snoozed_until",
+                    "met_since",
+                    "met_cycles",
+                    "last_reminded",
+                    "initialized",
+                    "expired",
+                    "armed",
+                ] {
+                    alert.remove(state);
+                }
+                for tier in alert
+                    .get_mut("tiers")
+                    .and_then(Value::as_array_mut)
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(tier) = tier.as_object_mut() {
+                        for state in [
+                            "hit_at",
+                            "repeat_count",
+                            "met_since",
+                            "last_reminded",
+                            "met_cycles",
+                        ] {
+                            tier.remove(state);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut value = serde_json::to_value(config)?;
+    fill(
+        &mut value,
+        json!({
+            "withold_notification_h": DEFAULT_WITHOLD_SECS,
+            "max_notifications_per_hour": null,
+            "adaptive_cooldown": null,
+            "http": null,
+            "muted_base_assets": [],
+            "muted_quote_assets": [],
+            "min_check_interval": DEFAULT_MIN_CHECK_INTERVAL,
+            "notify_on_clear": false,
+            "compact_numbers": false,
+            "prune_state_on_load": false,
+            "state_retry_secs": DEFAULT_STATE_RETRY_SECS,
+            "warm_restart_max_age_secs": null,
+            "history_min_change_pct": null,
+            "history_max_gap_secs": DEFAULT_HISTORY_MAX_GAP_SECS,
+            "suppress_initial_crossings": false,
+            "retries": notify::DEFAULT_RETRIES,
+            "retry_backoff_ms": notify::DEFAULT_RETRY_BACKOFF_MS,
+            "timeout_secs": notify::DEFAULT_TIMEOUT_SECS,
+            "channel_warmup_retry_secs": null,
+            "strict_startup": false,
+            "notify_on_arm": false,
+            "arm_channel": null,
+            "recovery_notice_after": DEFAULT_RECOVERY_NOTICE_AFTER,
+            "error_notify_after": null,
+            "max_consecutive_failures": null,
+            "symbol_throttle_secs": null,
+            "dedup_overlapping": false,
+            "dedup_window_secs": null,
+            "comparison_epsilon": 0.0,
+        }),
+    );
+    // Split in two, one `json!` of them all exceeds the macro recursion limit
+    fill(
+        &mut value,
+        json!({
+            "footer": false,
+            "instance_name": notify::hostname(),
+            "weight_budget_per_min": SPOT_WEIGHT_LIMIT_PER_MIN,
+            "futures_weight_budget_per_min": FUTURES_WEIGHT_LIMIT_PER_MIN,
+            "depth_limit": DEFAULT_DEPTH_LIMIT,
+            "display_names": {},
+            "price_sources": {},
+            "on_alert_command": [],
+            "local_address": null,
+            "notify_concurrency": null,
+            "channel_order": [],
+            "startup_delay_secs": null,
+            "secrets_file": null,
+            "summary": null,
+            "announcements": null,
+            "timestamp_format": DEFAULT_TIMESTAMP_FORMAT,
+            "binance_base_url": DEFAULT_BINANCE_BASE_URL,
+            "binance_endpoints": [],
+            "webhook": null,
+            "event_webhook": null,
+            "ntfy": null,
+            "telegram": null,
+            "pushgateway": null,
+            "mqtt": null,
+            "check_updates": false,
+            "profiles": [],
+        }),
+    );
+    if let Some(object) = value.as_object_mut() {
+        object.remove("price_history");
+        object.remove("symbol_last_notified");
+        object.remove("sent_fingerprints");
+        object.remove("snooze_until");
+        object.remove("muted_symbols");
+        object.remove("summary_last_sent");
+        object.remove("announcements_seen");
+        object.remove("event_sequence");
+        object.remove("pending_events");
+    }
+    if let Some(token) = value.pointer_mut("/ntfy/token") {
+        *token = json!("********");
+    }
+    if let Some(token) = value.pointer_mut("/telegram/bot_token") {
+        *token = json!("********");
+    }
+    for pointer in [
+        "/webhook/hmac_secret",
+        "/event_webhook/hmac_secret",
+        "/mqtt/password",
+        "/http/token",
+    ] {
+        if let Some(secret) = value.pointer_mut(pointer) {
+            *secret = json!("********");
+        }
+    }
+    fill_email(&mut value["email"]);
+    fill_alerts(&mut value["currencies"], config.notify_on_clear);
+    let channels = json!({
+        "webhook": value["webhook"],
+        "ntfy": value["ntfy"],
+        "telegram": value["telegram"],
+    });
+    for profile in value["profiles"].as_array_mut().into_iter().flatten() {
+        fill(
+            profile,
+            json!({
+                "check_interval": config.check_interval,
+                "email": serde_json::to_value(&config.email)?,
+            }),
+        );
+        fill(profile, channels.clone());
+        fill_email(&mut profile["email"]);
+        fill_alerts(&mut profile["currencies"], config.notify_on_clear);
+        for pointer in ["/ntfy/token", "/telegram/bot_token", "/webhook/hmac_secret"] {
+            if let Some(secret) = profile.pointer_mut(pointer) {
+                *secret = json!("********");
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Sends (or with `dry_run` only prints) a triggered alert for `SYMBOL:price`
+/// through the normal rendering path. Uses the first configured alert on
+/// the symbol, or an `Above` alert at that price if there is none.
+fn simulate_alert(
+    config: &mut Config,
+    spec: &str,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (symbol, raw_price) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("expected SYMBOL:price, got \"{}\"", spec))?;
+    let symbol = symbol.trim().to_uppercase();
+    let price =
+        parse_price(raw_price.trim()).ok_or_else(|| format!("invalid price \"{}\"", raw_price))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    threshold_file::refresh(config);
+
+    let alert = match config.all_currencies().find(|c| c.symbol == symbol) {
+        Some(currency) => currency.fired(AlertEvent::Triggered, price, String::new(), now),
+        None => {
+            let mut currency: CurrencyAlert = serde_json::from_value(serde_json::json!({
+                "symbol": symbol,
+                "threshold": price,
+                "alert_condition": "Above",
+                "last_alerted": null,
+            }))?;
+            currency.display_name = config.display_names.get(&symbol).cloned();
+            currency.fired(AlertEvent::Triggered, price, String::new(), now)
+        }
+    };
+    let group = config
+        .all_currencies()
+        .find(|c| c.symbol == symbol)
+        .and_then(|c| c.group.clone());
+    let suffix = group.map(|g| format!(" ({})", g)).unwrap_or_default();
+    let notification =
+        Notification::alerts(format!("[bye-watch] Price Alert{}", suffix), vec![alert]);
+
+    if dry_run {
+        println!("Subject: {}", notification.subject);
+        println!("{}", notify::render_body(config, &notification, None));
+        return Ok(());
+    }
+    notify(config, &notification).map_err(|e| Fatal::Notification(e).into())
+}
+
+/// Writes a sample config with every default spelled out, refusing to
+/// overwrite an existing file.
+fn init_config(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let sample: Config = serde_json::from_value(serde_json::json!({
+        "config_version": migrate::CONFIG_VERSION,
+        "email": {
+            "username": "first.last@gmail.com",
+            "password": "app-password",
+        },
+        "check_interval": 60,
+        "withold_notification_h": DEFAULT_WITHOLD_SECS,
+        "currencies": [
+            {
+                "symbol": "BTCUSDT",
+                "threshold": 100000.0,
+                "alert_condition": "Above",
+                "last_alerted": null,
+            },
+        ],
+    }))?;
+    let mut value = effective_config(&sample)?;
+    value["email"]["password"] = serde_json::json!("app-password");
+    // Not this machine's hostname, the file may be shared or reused elsewhere
+    value["instance_name"] = serde_json::json!("my-server");
+
+    let mut file = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(format!("{} already exists, not overwriting it", path).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    use std::io::Write;
+    writeln!(file, "{}", serde_json::to_string_pretty(&value)?)?;
+    println!(
+        "Wrote a sample {}, add your email credentials and alerts to get started",
+        path
+    );
+    Ok(())
+}
+
+/// Reads the config at `path`, migrated to the current schema and with its
+/// secrets and variables filled in. Also returns the migrations applied.
+fn load_config(path: &str) -> Result<(Config, Vec<String>), Fatal> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Fatal::config(format!("could not read {}: {}", path, e)))?;
+    let mut value: serde_json::Value = serde_json::from_str(&content).map_err(Fatal::config)?;
+    let migrations = migrate::migrate(&mut value).map_err(Fatal::config)?;
+    let secret_paths = secrets::apply(&mut value).map_err(Fatal::config)?;
+    let variable_paths = variables::apply(&mut value).map_err(Fatal::config)?;
+    let mut config: Config = serde_json::from_value(value).map_err(Fatal::config)?;
+    config.secret_paths = secret_paths;
+    config.variable_paths = variable_paths;
+    Ok((config, migrations))
+}
+
+const PANIC_NOTIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Makes a panic send a best-effort crash notification, using the config as
+/// last written to disk so the possibly poisoned shared config is not touched.
+fn install_panic_hook(config_path: &'static str) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        eprintln!("{}", std::backtrace::Backtrace::force_capture());
+        // A panic while notifying must not recurse into another notification
+        static NOTIFYING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        if NOTIFYING.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+
+        let message = info.to_string();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let result = load_config(config_path)
+                .map_err(|e| e.to_string())
+                .and_then(|(mut config, _)| {
+                    let notification = Notification::operational(
+                        "[bye-watch] Crashed",
+                        format!("bye-watch crashed: {}", message),
+                    );
+                    notify(&mut config, &notification).map_err(|e| e.to_string())
+                });
+            let _ = done_tx.send(result);
+        });
+        match done_rx.recv_timeout(PANIC_NOTIFY_TIMEOUT) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("Failed to send crash notification: {}", e),
+            Err(_) => eprintln!("Timed out sending crash notification"),
+        }
+    }));
+}
+
+/// Paths of every key in the config that matched no field, e.g.
+/// "currencies[2].witdhold_notification_h".
+fn unknown_fields(config: &Config) -> Vec<String> {
+    fn push(paths: &mut Vec<String>, prefix: &str, fields: &UnknownFields) {
+        paths.extend(fields.keys().map(|key| format!("{}{}", prefix, key)));
+    }
+    fn push_alerts(paths: &mut Vec<String>, prefix: &str, alerts: &[CurrencyAlert]) {
+        for (index, alert) in alerts.iter().enumerate() {
+            let prefix = format!("{}currencies[{}].", prefix, index);
+            push(paths, &prefix, &alert.unknown_fields);
+            for (index, tier) in alert.tiers.iter().enumerate() {
+                push(
+                    paths,
+                    &format!("{}tiers[{}].", prefix, index),
+                    &tier.unknown_fields,
+                );
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    push(&mut paths, "", &config.unknown_fields);
+    push(&mut paths, "email.", &config.email.unknown_fields);
+    if let Some(webhook) = &config.webhook {
+        push(&mut paths, "webhook.", &webhook.unknown_fields);
+    }
+    if let Some(webhook) = &config.event_webhook {
+        push(&mut paths, "event_webhook.", &webhook.unknown_fields);
+    }
+    if let Some(telegram) = &config.telegram {
+        push(&mut paths, "telegram.", &telegram.unknown_fields);
+    }
+    if let Some(ntfy) = &config.ntfy {
+        push(&mut paths, "ntfy.", &ntfy.unknown_fields);
+    }
+    if let Some(summary) = &config.summary {
+        push(&mut paths, "summary.", &summary.unknown_fields);
+    }
+    if let Some(feed) = &config.announcements {
+        push(&mut paths, "announcements.", &feed.unknown_fields);
+    }
+    if let Some(cooldown) = &config.adaptive_cooldown {
+        push(&mut paths, "adaptive_cooldown.", &cooldown.unknown_fields);
+    }
+    if let Some(http) = &config.http {
+        push(&mut paths, "http.", &http.unknown_fields);
+    }
+    if let Some(pushgateway) = &config.pushgateway {
+        push(&mut paths, "pushgateway.", &pushgateway.unknown_fields);
+    }
+    if let Some(mqtt) = &config.mqtt {
+        push(&mut paths, "mqtt.", &mqtt.unknown_fields);
+    }
+    push_alerts(&mut paths, "", &config.currencies);
+    for (index, profile) in config.profiles.iter().enumerate() {
+        let prefix = format!("profiles[{}].", index);
+        push(&mut paths, &prefix, &profile.unknown_fields);
+        if let Some(email) = &profile.email {
+            push(
+                &mut paths,
+                &format!("{}email.", prefix),
+                &email.unknown_fields,
+            );
+        }
+        if let Some(webhook) = &profile.webhook {
+            push(
+                &mut paths,
+                &format!("{}webhook.", prefix),
+                &webhook.unknown_fields,
+            );
+        }
+        if let Some(telegram) = &profile.telegram {
+            push(
+                &mut paths,
+                &format!("{}telegram.", prefix),
+                &telegram.unknown_fields,
+            );
+        }
+        if let Some(ntfy) = &profile.ntfy {
+            push(
+                &mut paths,
+                &format!("{}ntfy.", prefix),
+                &ntfy.unknown_fields,
+            );
+        }
+        push_alerts(&mut paths, &prefix, &profile.currencies);
+    }
+    paths
+}
+
+/// Parses durations like `90s`, `30m`, `2h` or `1d` into seconds.
+fn parse_duration(spec: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let split = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(spec.len());
+    let (amount, unit) = spec.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid duration \"{}\", expected e.g. 2h", spec))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("Unknown duration unit \"{}\" in \"{}\"", unit, spec).into()),
+    };
+    Ok(amount * unit_secs)
+}
+
+/// The `snooze_until` currently in the config file, which `--snooze` updates
+/// behind the back of a running daemon. Falls back to `current` if the file
+/// cannot be read.
+fn read_snooze(config_path: &str, current: Option<u64>) -> Option<u64> {
+    let Some(value) = fs::read_to_string(config_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+    else {
+        return current;
+    };
+    value
+        .get("snooze_until")
+        .and_then(serde_json::Value::as_u64)
+}
+
+/// Value following `name` on the command line, e.g. `--import-alerts <file>`.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%d-%m-%Y %H:%M:%S";
+
+const DEFAULT_BINANCE_BASE_URL: &str = "https://api.binance.com";
+
+fn fetch_prices(config: &Config) -> Result<Vec<BinancePrice>, Box<dyn std::error::Error>> {
+    let prices: Vec<BinancePrice> = match &config.price_override {
+        Some(prices) => prices
+            .iter()
+            .map(|(symbol, price)| BinancePrice {
+                symbol: symbol.clone(),
+                price: price.clone(),
+            })
+            .collect(),
+        None => {
+            config.spend_spot_weight("ticker/price", PRICE_TICKER_WEIGHT);
+            let client = config.http_client().build()?;
+            let response = config.binance_get(&client, "/api/v3/ticker/price", &[])?;
+            if !response.status().is_success() {
+                return Err(format!("Failed to fetch prices: HTTP {}", response.status()).into());
+            }
+            response.json()?
+        }
+    };
+
+    let currency_symbols: Vec<String> = config
+        .currencies
+        .iter()
+        .flat_map(|c| {
+            std::iter::once(c.symbol.as_str()).chain(c.alert_condition.reference_symbols())
+        })
+        .map(str::to_string)
+        .collect();
+
+    let filtered_prices: Vec<BinancePrice> = prices
+        .into_iter()
+        .filter(|price_data| currency_symbols.contains(&price_data.symbol))
+        .collect();
+
+    Ok(filtered_prices)
+}
+
+/// Symbol `--test-source` always quotes at `TEST_SYMBOL_PRICE`, so that an
+/// alert on it fires deterministically without touching the network.
+const TEST_SYMBOL: &str = "TESTUSDT";
+const TEST_SYMBOL_PRICE: &str = "100";
+
+/// Adds the test symbol to the price override, starting one without any other
+/// prices unless `--price-override` gave some.
+fn enable_test_source(config: &mut Config) {
+    config
+        .price_override
+        .get_or_insert_with(HashMap::new)
+        .insert(TEST_SYMBOL.to_string(), TEST_SYMBOL_PRICE.to_string());
+}
+
+/// Reads a `--price-override` file: a JSON object of symbol to price, given
+/// as a number or a decimal string.
+fn load_price_override(path: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read price override {}: {}", path, e))?;
+    let entries: HashMap<String, serde_json::Value> = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a JSON object of prices: {}", path, e))?;
+    entries
+        .into_iter()
+        .map(|(symbol, value)| {
+            let price = match value {
+                serde_json::Value::String(price) => price,
+                serde_json::Value::Number(price) => price.to_string(),
+                other => other.to_string(),
+            };
+            match parse_price(&price) {
+                Some(_) => Ok((symbol.to_uppercase(), price)),
+                None => Err(format!(
+                    "{}: price \"{}\" for {} is not a number",
+                    path, price, symbol
+                )
+                .into()),
+            }
+        })
+        .collect()
+}
+
+/// Spot prices with `price_sources` applied: every symbol listing sources
+/// takes its price from the first of them that delivers one. Only fails if
+/// Binance fails and no fallback could stand in.
+fn fetch_prices_with_fallback(
+    config: &Config,
+) -> Result<(Vec<BinancePrice>, FallbackSources), Box<dyn std::error::Error>> {
+    let binance = fetch_prices(config);
+    if config.price_sources.is_empty() || config.price_override.is_some() {
+        return Ok((binance?, HashMap::new()));
+    }
+    let (mut prices, binance_error) = match binance {
+        Ok(prices) => (prices, None),
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+
+    let mut fallback_sources = HashMap::new();
+    for (symbol, sources) in &config.price_sources {
+        let from_binance = prices.iter().position(|p| &p.symbol == symbol);
+        let mut failures = Vec::new();
+        let mut price = None;
+        for &source in sources {
+            let result = match source {
+                PriceSource::Binance => from_binance
+                    .map(|index| prices[index].price.clone())
+                    .ok_or_else(|| {
+                        binance_error
+                            .clone()
+                            .unwrap_or_else(|| "no price returned".to_string())
+                    }),
+                PriceSource::Kraken => {
+                    kraken::fetch_price(config.http_client(), symbol).map_err(|e| e.to_string())
+                }
+            };
+            match result {
+                Ok(value) => {
+                    price = Some((source, value));
+                    break;
+                }
+                Err(e) => failures.push(format!("{}: {}", source, e)),
+            }
+        }
+        if let Some(index) = from_binance {
+            prices.remove(index);
+        }
+        match price {
+            Some((source, value)) => {
+                if !failures.is_empty() {
+                    println!(
+                        "Price for {} from {} ({})",
+                        symbol,
+                        source,
+                        failures.join("; ")
+                    );
+                }
+                if source != PriceSource::Binance {
+                    fallback_sources.insert(symbol.clone(), source);
+                }
+                prices.push(BinancePrice {
+                    symbol: symbol.clone(),
+                    price: value,
+                });
+            }
+            None => eprintln!(
+                "No source delivered a price for {} ({})",
+                symbol,
+                failures.join("; ")
+            ),
+        }
+    }
+
+    match binance_error {
+        Some(e) if fallback_sources.is_empty() => Err(e.into()),
+        Some(e) => {
+            eprintln!(
+                "Failed to fetch Binance prices, using fallbacks only: {}",
+                e
+            );
+            Ok((prices, fallback_sources))
+        }
+        None => Ok((prices, fallback_sources)),
+    }
+}
+
+/// Quotes for `ArbSpread` alerts. A failed quote only skips its alerts, so
+/// it is logged rather than failing the check.
+fn fetch_exchange_prices(config: &Config, quotes: &[(&str, PriceSource)]) -> ExchangePrices {
+    let mut prices = HashMap::new();
+    for &(symbol, source) in quotes {
+        let price = match source {
+            PriceSource::Kraken => kraken::fetch_price(config.http_client(), symbol),
+            // Binance prices come with the regular fetch
+            PriceSource::Binance => continue,
+        };
+        match price {
+            Ok(price) => {
+                prices.insert((symbol.to_string(), source), price);
+            }
+            Err(e) => eprintln!("Failed to fetch {} price for {}: {}", source, symbol, e),
+        }
+    }
+    prices
+}
+
+/// Fetches prices once before entering the loop, so that connectivity
+/// problems and unknown symbols are reported immediately.
+fn warmup_check(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let prices =
+        fetch_prices(config).map_err(|e| format!("could not reach the exchange: {}", e))?;
+
+    let mut missing: Vec<&str> = config
+        .currencies
+        .iter()
+        .filter(|c| !c.is_futures())
+        .flat_map(|c| {
+            std::iter::once(c.symbol.as_str()).chain(c.alert_condition.reference_symbols())
+        })
+        .filter(|symbol| !prices.iter().any(|p| p.symbol == *symbol))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    if !missing.is_empty() {
+        return Err(format!("no price data for {}", missing.join(", ")).into());
+    }
+
+    println!(
+        "Startup check passed, all {} symbols resolved",
+        prices.len()
+    );
+    let spot_only = config
+        .currencies
+        .iter()
+        .all(|c| c.price_basis.is_spot() && !c.is_futures());
+    // Other price bases need their own requests, which only the preview uses
+    let market = if spot_only {
+        None
+    } else {
+        fetch_market_data(config)
+            .map_err(|e| eprintln!("Previewing spot prices only: {}", e))
+            .ok()
+    };
+    let market = market.unwrap_or(MarketData {
+        prices,
+        ..Default::default()
+    });
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    for row in startup_preview(config, &market, now) {
+        println!("{}", row);
+    }
+    Ok(())
+}
+
+/// With `notify_on_arm`, a notice listing the alerts added since the last
+/// run, which `mark_armed` records as announced once it was sent. Alerts that
+/// already saw a price before the option was turned on count as announced.
+fn arm_notice(config: &mut Config) -> Option<Notification> {
+    if !config.notify_on_arm {
+        return None;
+    }
+    let mut armed = Vec::new();
+    for currency in config.all_currencies_mut() {
+        if currency.armed {
+            continue;
+        }
+        match currency.initialized {
+            Some(_) => currency.armed = true,
+            None => armed.push(currency.rule()),
+        }
+    }
+    if armed.is_empty() {
+        return None;
+    }
+    println!("Newly armed: {}", armed.join(", "));
+    Some(Notification::text(
+        "[bye-watch] Now watching",
+        format!("Now watching:\n\n{}", armed.join("\n")),
+    ))
+}
+
+/// Records every alert as announced by `arm_notice`.
+fn mark_armed(config: &mut Config) {
+    for currency in config.all_currencies_mut() {
+        currency.armed = true;
+    }
+}
+
+/// One row per alert comparing its threshold to the live price of its price
+/// basis, so the startup log shows what the monitor sees before anything
+/// fires. Only plain Above/Below thresholds can be judged from that price
+/// alone, the other conditions are left to the first check.
+fn startup_preview(config: &Config, market: &MarketData, current_time: u64) -> Vec<String> {
+    let twaps = twaps(config, market, current_time);
+    let mut rows = vec![format!(
+        "{:<14} {:<24} {:>14} {:>16}  WOULD FIRE?",
+        "SYMBOL", "CONDITION", "THRESHOLD", "PRICE"
+    )];
+    for currency in &config.currencies {
+        let price = basis_price(currency, market, &twaps)
+            .and_then(|raw| parse_price(raw).map(|value| (raw.as_str(), value)));
+        let threshold = match currency.alert_condition {
+            AlertCondition::Above | AlertCondition::Below if currency.tiers.is_empty() => {
+                currency.threshold()
+            }
+            _ => None,
+        };
+        let verdict = match (price, threshold) {
+            (None, _) => "no price".to_string(),
+            (Some(_), None) => "on first check".to_string(),
+            (Some((_, price)), Some(threshold)) => {
+                let on_tick = |value: f64| currency.tick.map_or(value, |tick| tick.round(value));
+                let (price, threshold) = (on_tick(price), on_tick(threshold));
+                let met = match (&currency.alert_condition, currency.inclusive) {
+                    (AlertCondition::Above, true) => price >= threshold,
+                    (AlertCondition::Above, false) => price > threshold,
+                    (_, true) => price <= threshold,
+                    (_, false) => price < threshold,
+                };
+                let distance = (price - threshold) / threshold * 100.0;
+                format!(
+                    "{} ({:+.2}% from threshold)",
+                    if met { "yes" } else { "no" },
+                    distance
+                )
+            }
+        };
+        rows.push(format!(
+            "{:<14} {:<24} {:>14} {:>16}  {}",
+            currency.name(),
+            currency.alert_condition.to_string(),
+            threshold.map_or_else(|| "-".to_string(), |t| t.to_string()),
+            price.map_or("-", |(raw, _)| raw),
+            verdict
+        ));
+    }
+    rows
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeInfo {
+    symbols: Vec<ExchangeSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeSymbol {
+    symbol: String,
+    filters: Vec<serde_json::Value>,
+}
+
+/// Fetches the price tick of every spot symbol from exchangeInfo.
+fn fetch_price_ticks(
+    config: &Config,
+) -> Result<HashMap<String, PriceTick>, Box<dyn std::error::Error>> {
+    let mut symbols: Vec<&str> = config
+        .all_currencies()
+        .filter(|c| !c.is_futures())
+        .map(|c| c.symbol.as_str())
+        .collect();
+    symbols.sort_unstable();
+    symbols.dedup();
+    if symbols.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    config.spend_spot_weight("exchangeInfo", EXCHANGE_INFO_WEIGHT);
+    let client = config.http_client().build()?;
+    let symbols = serde_json::to_string(&symbols)?;
+    let response = config.binance_get(&client, "/api/v3/exchangeInfo", &[("symbols", &symbols)])?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch exchangeInfo: HTTP {}", response.status()).into());
+    }
+
+    let info: ExchangeInfo = response.json()?;
+    Ok(info
+        .symbols
+        .into_iter()
+        .filter_map(|entry| {
+            let tick = entry
+                .filters
+                .iter()
+                .find(|filter| filter["filterType"] == "PRICE_FILTER")
+                .and_then(|filter| filter["tickSize"].as_str())
+                .and_then(PriceTick::parse)?;
+            Some((entry.symbol, tick))
+        })
+        .collect())
+}
+
+/// Distinct symbols of the alerts whose condition satisfies `filter`.
+fn symbols_where(
+    currencies: &[CurrencyAlert],
+    filter: impl Fn(&AlertCondition) -> bool,
+) -> Vec<&str> {
+    let mut symbols: Vec<&str> = currencies
+        .iter()
+        .filter(|c| filter(&c.alert_condition))
+        .map(|c| c.symbol.as_str())
+        .collect();
+    symbols.sort_unstable();
+    symbols.dedup();
+    symbols
+}
+
+/// Fetches the (heavier) 24hr ticker, only for symbols whose alerts need it.
+fn fetch_daily_tickers(
+    config: &Config,
+    symbols: &[&str],
+) -> Result<Vec<BinanceTicker24h>, Box<dyn std::error::Error>> {
+    if symbols.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    config.spend_spot_weight("ticker/24hr", daily_ticker_weight(symbols.len()));
+    let client = config.http_client().build()?;
+    let symbols = serde_json::to_string(&symbols)?;
+    let response = config.binance_get(&client, "/api/v3/ticker/24hr", &[("symbols", &symbols)])?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch 24hr tickers: HTTP {}", response.status()).into());
+    }
+
+    Ok(response.json()?)
+}
+
+const DEFAULT_DEPTH_LIMIT: u32 = 20;
+/// Limits accepted by `/api/v3/depth`.
+const DEPTH_LIMITS: [u32; 8] = [5, 10, 20, 50, 100, 500, 1000, 5000];
+
+fn fetch_depth(
+    config: &Config,
+    symbols: &[&str],
+) -> Result<HashMap<String, BinanceDepth>, Box<dyn std::error::Error>> {
+    if symbols.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = config.http_client().build()?;
+    let limit = config.depth_limit.unwrap_or(DEFAULT_DEPTH_LIMIT);
+    let mut depth = HashMap::new();
+    for &symbol in symbols {
+        config.spend_spot_weight("depth", depth_weight(limit));
+        let response = config.binance_get(
+            &client,
+            "/api/v3/depth",
+            &[("symbol", symbol), ("limit", &limit.to_string())],
+        )?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to fetch order book for {}: HTTP {}",
+                symbol,
+                response.status()
+            )
+            .into());
+        }
+        depth.insert(symbol.to_string(), response.json()?);
+    }
+    Ok(depth)
+}
+
+/// Time of the latest trade of each of `symbols`, for `DataStale` alerts.
+fn fetch_last_trades(
+    config: &Config,
+    symbols: &[&str],
+) -> Result<HashMap<String, Option<u64>>, Box<dyn std::error::Error>> {
+    if symbols.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = config.http_client().build()?;
+    let mut last_trades = HashMap::new();
+    for &symbol in symbols {
+        config.spend_spot_weight("trades", TRADES_WEIGHT);
+        let response = config.binance_get(
+            &client,
+            "/api/v3/trades",
+            &[("symbol", symbol), ("limit", "1")],
+        )?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to fetch the last trade of {}: HTTP {}",
+                symbol,
+                response.status()
+            )
+            .into());
+        }
+        let trades: Vec<BinanceTrade> = response.json()?;
+        let time = trades.last().map(|trade| (trade.time / 1000).max(0) as u64);
+        last_trades.insert(symbol.to_string(), time);
+    }
+    Ok(last_trades)
+}
+
+/// The last `limit` candles of `symbol`, each
+/// `[open time, open, high, low, close, volume, close time, ...]`.
+fn fetch_klines(
+    config: &Config,
+    client: &reqwest::blocking::Client,
+    symbol: &str,
+    interval: &str,
+    limit: usize,
+) -> Result<Vec<Vec<serde_json::Value>>, Box<dyn std::error::Error>> {
+    config.spend_spot_weight("klines", KLINES_WEIGHT);
+    let response = config.binance_get(
+        client,
+        "/api/v3/klines",
+        &[
+            ("symbol", symbol),
+            ("interval", interval),
+            ("limit", &limit.to_string()),
+        ],
+    )?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch {} klines for {}: HTTP {}",
+            interval,
+            symbol,
+            response.status()
+        )
+        .into());
+    }
+    Ok(response.json()?)
+}
+
+/// VWAP over every candle, `None` without any traded volume.
+fn vwap(candles: &[Vec<serde_json::Value>]) -> Option<f64> {
+    let field = |candle: &[serde_json::Value], i: usize| {
+        candle.get(i).and_then(|v| v.as_str()).and_then(parse_price)
+    };
+    let (mut turnover, mut volume) = (0.0, 0.0);
+    for candle in candles {
+        let typical = (field(candle, 2)? + field(candle, 3)? + field(candle, 4)?) / 3.0;
+        let traded = field(candle, 5)?;
+        turnover += typical * traded;
+        volume += traded;
+    }
+    (volume > 0.0).then(|| turnover / volume)
+}
+
+/// VWAP for every symbol, interval and period count in `requests`. Symbols
+/// with fewer candles than asked for, or no volume, are left out.
+fn fetch_vwaps(
+    config: &Config,
+    requests: &[(&str, &str, usize)],
+) -> Result<Vwaps, Box<dyn std::error::Error>> {
+    if requests.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = config.http_client().build()?;
+    let mut vwaps = HashMap::new();
+    for &(symbol, interval, periods) in requests {
+        let candles = fetch_klines(config, &client, symbol, interval, periods)?;
+        match vwap(&candles).filter(|_| candles.len() >= periods) {
+            Some(vwap) => {
+                vwaps.insert(
+                    (symbol.to_string(), interval.to_string(), periods),
+                    format!("{:.8}", vwap),
+                );
+            }
+            None => eprintln!(
+                "Not enough {} klines to compute a {}-period VWAP for {} ({} candles)",
+                interval,
+                periods,
+                symbol,
+                candles.len()
+            ),
+        }
+    }
+    Ok(vwaps)
+}
+
+/// Close price of the most recent closed candle for every symbol and
+/// interval in `klines`.
+fn fetch_candle_closes(
+    config: &Config,
+    klines: &[(&str, &str)],
+) -> Result<HashMap<(String, String), String>, Box<dyn std::error::Error>> {
+    if klines.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = config.http_client().build()?;
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+    let mut closes = HashMap::new();
+    for &(symbol, interval) in klines {
+        // The last candle is usually still open, the one before is closed
+        let candles = fetch_klines(config, &client, symbol, interval, 2)?;
+        let close = candles
+            .iter()
+            .rev()
+            .find(|candle| candle.get(6).and_then(|t| t.as_i64()) < Some(now_ms))
+            .and_then(|candle| candle.get(4)?.as_str());
+        match close {
+            Some(close) => {
+                closes.insert(
+                    (symbol.to_string(), interval.to_string()),
+                    close.to_string(),
+                );
+            }
+            None => eprintln!("No closed {} candle found for {}", interval, symbol),
+        }
+    }
+    Ok(closes)
+}
+
+const DEFAULT_RECOVERY_NOTICE_AFTER: u32 = 3;
+
+/// A streak of failed fetches, started at `since`.
+#[derive(Debug, Clone, Copy)]
+struct Outage {
+    since: u64,
+    failures: u32,
+}
+
+/// The endpoints one check has to call beyond the spot price list, worked out
+/// from the conditions that are configured.
+#[derive(Debug, Default, PartialEq)]
+struct FetchPlan<'a> {
+    daily_tickers: Vec<&'a str>,
+    depth: Vec<&'a str>,
+    /// Symbols of every `DataStale` alert.
+    last_trades: Vec<&'a str>,
+    premium_index: Vec<&'a str>,
+    /// Symbol and interval of every `CandleClose` price basis.
+    klines: Vec<(&'a str, &'a str)>,
+    /// Symbol, interval and periods of every `Vwap` price basis.
+    vwap: Vec<(&'a str, &'a str, usize)>,
+    /// Symbol and exchange of every non-Binance quote an `ArbSpread` needs.
+    exchange_prices: Vec<(&'a str, PriceSource)>,
+}
+
+impl<'a> FetchPlan<'a> {
+    fn new(currencies: &'a [CurrencyAlert]) -> Self {
+        FetchPlan {
+            daily_tickers: symbols_where(currencies, AlertCondition::needs_daily_ticker),
+            depth: symbols_where(currencies, AlertCondition::needs_depth),
+            last_trades: symbols_where(currencies, AlertCondition::needs_last_trade),
+            premium_index: {
+                let mut symbols: Vec<&str> = currencies
+                    .iter()
+                    .filter(|c| c.is_futures() || c.alert_condition.needs_index_price())
+                    .map(|c| c.symbol.as_str())
+                    .collect();
+                symbols.sort_unstable();
+                symbols.dedup();
+                symbols
+            },
+            klines: {
+                let mut klines: Vec<(&str, &str)> = currencies
+                    .iter()
+                    .filter_map(|c| match &c.price_basis {
+                        PriceBasis::CandleClose { interval } => {
+                            Some((c.symbol.as_str(), interval.as_str()))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                klines.sort_unstable();
+                klines.dedup();
+                klines
+            },
+            vwap: {
+                let mut vwap: Vec<(&str, &str, usize)> = currencies
+                    .iter()
+                    .filter_map(|c| match &c.price_basis {
+                        PriceBasis::Vwap { interval, periods } => {
+                            Some((c.symbol.as_str(), interval.as_str(), *periods))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                vwap.sort_unstable();
+                vwap.dedup();
+                vwap
+            },
+            exchange_prices: {
+                let mut quotes: Vec<(&str, PriceSource)> = currencies
+                    .iter()
+                    .flat_map(|c| {
+                        c.alert_condition
+                            .exchanges()
+                            .into_iter()
+                            .map(|source| (c.symbol.as_str(), source))
+                    })
+                    .filter(|(_, source)| *source != PriceSource::Binance)
+                    .collect();
+                quotes.sort_unstable();
+                quotes.dedup();
+                quotes
+            },
+        }
+    }
+}
+
+/// Waits for one fetch of [`fetch_market_data`]. Errors cross the thread
+/// boundary as strings because boxed errors are not `Send`.
+fn join_fetch<T>(
+    handle: std::thread::ScopedJoinHandle<'_, Result<T, String>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    handle
+        .join()
+        .map_err(|_| "A market data fetch panicked")?
+        .map_err(Into::into)
+}
+
+/// Runs every request in the [`FetchPlan`] at the same time, so a check costs
+/// about as long as its slowest endpoint rather than the sum of all of them.
+fn fetch_market_data(config: &Config) -> Result<MarketData, Box<dyn std::error::Error>> {
+    let plan = FetchPlan::new(&config.currencies);
+    if config.price_override.is_some() {
+        return overridden_market_data(config, &plan);
+    }
+    if !plan.premium_index.is_empty() {
+        config.spend_futures_weight("premiumIndex", PREMIUM_INDEX_WEIGHT);
+    }
+    std::thread::scope(|scope| {
+        let prices = scope.spawn(|| fetch_prices_with_fallback(config).map_err(|e| e.to_string()));
+        let daily_tickers = scope
+            .spawn(|| fetch_daily_tickers(config, &plan.daily_tickers).map_err(|e| e.to_string()));
+        let premium_index = scope.spawn(|| {
+            futures::fetch_premium_index(config.http_client(), &plan.premium_index)
+                .map_err(|e| e.to_string())
+        });
+        let depth = scope.spawn(|| fetch_depth(config, &plan.depth).map_err(|e| e.to_string()));
+        let last_trades =
+            scope.spawn(|| fetch_last_trades(config, &plan.last_trades).map_err(|e| e.to_string()));
+        let candle_closes =
+            scope.spawn(|| fetch_candle_closes(config, &plan.klines).map_err(|e| e.to_string()));
+        let vwaps = scope.spawn(|| fetch_vwaps(config, &plan.vwap).map_err(|e| e.to_string()));
+        let exchange_prices =
+            scope.spawn(|| Ok(fetch_exchange_prices(config, &plan.exchange_prices)));
+        let (prices, fallback_sources) = join_fetch(prices)?;
+        Ok(MarketData {
+            prices,
+            fallback_sources,
+            daily_tickers: join_fetch(daily_tickers)?,
+            premium_index: join_fetch(premium_index)?,
+            depth: join_fetch(depth)?,
+            last_trades: join_fetch(last_trades)?,
+            candle_closes: join_fetch(candle_closes)?,
+            vwaps: join_fetch(vwaps)?,
+            exchange_prices: join_fetch(exchange_prices)?,
+        })
+    })
+}
+
+/// Market data of a `--price-override` run, without any request: mark
+/// prices, candle closes and VWAPs take the overridden price as given, the
+/// 24hr tickers, order books, last trades and other exchanges' quotes stay
+/// empty, so `DataStale` alerts are skipped.
+fn overridden_market_data(
+    config: &Config,
+    plan: &FetchPlan,
+) -> Result<MarketData, Box<dyn std::error::Error>> {
+    let prices = fetch_prices(config)?;
+    let price = |symbol: &str| {
+        prices
+            .iter()
+            .find(|p| p.symbol == symbol)
+            .map(|p| p.price.clone())
+    };
+    let premium_index = plan
+        .premium_index
+        .iter()
+        .filter_map(|&symbol| {
+            let price = price(symbol)?;
+            Some(futures::PremiumIndex {
+                symbol: symbol.to_string(),
+                mark_price: price.clone(),
+                index_price: price,
+                last_funding_rate: "0".to_string(),
+                next_funding_time: 0,
+            })
+        })
+        .collect();
+    let candle_closes = plan
+        .klines
+        .iter()
+        .filter_map(|&(symbol, interval)| {
+            Some(((symbol.to_string(), interval.to_string()), price(symbol)?))
+        })
+        .collect();
+    let vwaps = plan
+        .vwap
+        .iter()
+        .filter_map(|&(symbol, interval, periods)| {
+            Some((
+                (symbol.to_string(), interval.to_string(), periods),
+                price(symbol)?,
+            ))
+        })
+        .collect();
+    Ok(MarketData {
+        premium_index,
+        candle_closes,
+        vwaps,
+        prices,
+        ..Default::default()
+    })
+}
+
+fn check_currencies(config: &mut Config) -> Result<(), Box<dyn std::error::Error>> {
+    let current_time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let market = match fetch_market_data(config) {
+        Ok(market) => market,
+        Err(e) => {
+            let outage = config.outage.get_or_insert(Outage {
+                since: current_time,
+                failures: 0,
+            });
+            outage.failures += 1;
+            return Err(e);
+        }
+    };
+    if let Some(outage) = config.outage.take() {
+        let notice_after = config
+            .recovery_notice_after
+            .unwrap_or(DEFAULT_RECOVERY_NOTICE_AFTER);
+        if outage.failures >= notice_after {
+            let downtime = current_time.saturating_sub(outage.since);
+            let message = format!(
+                "Connectivity restored, monitoring resumed.\n\
+                 Market data was unavailable for {}m {}s ({} failed checks) since {}.",
+                downtime / 60,
+                downtime % 60,
+                outage.failures,
+                notify::format_time(outage.since, config.timestamp_format())
+            );
+            let notice = Notification::operational("[bye-watch] Connectivity restored", message);
+            // The alerts are checked all the same, they matter most right now
+            if let Err(e) = notify(config, &notice) {
+                eprintln!("Failed to send recovery notification: {}", e);
+            }
+        }
+    }
+
+    threshold_file::refresh(config);
+    let notified: Vec<Notified> = config.currencies.iter().map(|c| c.notified()).collect();
+    let fired = evaluate_alerts(config, &market, current_time)?;
+    config.stats.alerts_fired_total += fired
+        .values()
+        .flatten()
+        .filter(|alert| alert.event == AlertEvent::Triggered)
+        .count() as u64;
+    let fired = hold_back(config, &notified, fired, current_time);
+    let groups: Vec<(Option<String>, Vec<FiredAlert>)> = fired.into_iter().collect();
+    for (at, (group, alerts)) in groups.iter().enumerate() {
+        if let Err(e) = notify_group(config, group, alerts, current_time) {
+            // Undelivered alerts fire again on the next check
+            for alert in groups[at..].iter().flat_map(|(_, alerts)| alerts) {
+                if let Some(index) = alert.index {
+                    config.currencies[index].restore(&notified[index]);
+                }
+            }
+            return Err(e);
+        }
+        // Only once delivered, restored alerts would be published twice
+        events::publish(config, alerts);
+    }
+
+    match config.missing_symbol.take() {
+        Some(symbol) => Err(MissingSymbolError(symbol).into()),
+        None => Ok(()),
+    }
+}
+
+/// Drops the fired alerts that are not to be sent (yet): overlapping, duplicate
+/// and throttled ones. Duplicate and throttled alerts are rolled back to
+/// `notified` so they fire again on the next check instead of being lost.
+fn hold_back(
+    config: &mut Config,
+    notified: &[Notified],
+    fired: FiredByGroup,
+    current_time: u64,
+) -> FiredByGroup {
+    let fired = collapse_overlapping(config, fired);
+    let candidates = triggered_indices(&fired);
+    let fired = dedup_alerts(config, fired, current_time);
+    let fired = throttle_symbols(config, fired, current_time);
+    for index in candidates.difference(&triggered_indices(&fired)) {
+        config.currencies[*index].restore(&notified[*index]);
+    }
+    fired
+}
+
+/// Records the delivered triggered `alerts` for `symbol_throttle_secs` and
+/// `dedup_window_secs`.
+fn record_sent(config: &mut Config, alerts: &[FiredAlert], current_time: u64) {
+    for alert in alerts {
+        if config.symbol_throttle_secs.is_some() {
+            config
+                .symbol_last_notified
+                .insert(alert.symbol.clone(), current_time);
+        }
+        if config.dedup_window_secs.is_some() {
+            config
+                .sent_fingerprints
+                .insert(alert_fingerprint(alert), current_time);
+        }
+    }
+}
+
+/// Positions of the alerts that triggered in `fired`.
+fn triggered_indices(fired: &FiredByGroup) -> HashSet<usize> {
+    fired
+        .values()
+        .flatten()
+        .filter(|alert| alert.event == AlertEvent::Triggered)
+        .filter_map(|alert| alert.index)
+        .collect()
+}
+
+/// Sends the triggered, reminder and cleared alerts of `group`, each kind as
+/// its own notification.
+fn notify_group(
+    config: &mut Config,
+    group: &Option<String>,
+    alerts: &[FiredAlert],
+    current_time: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let suffix = group
+        .as_ref()
+        .map(|g| format!(" ({})", g))
+        .unwrap_or_default();
+    let of = |event: AlertEvent| -> Vec<FiredAlert> {
+        alerts
+            .iter()
+            .filter(|a| a.event == event)
+            .cloned()
+            .collect()
+    };
+    let (triggered, reminders, cleared) = (
+        of(AlertEvent::Triggered),
+        of(AlertEvent::Reminder),
+        of(AlertEvent::Cleared),
+    );
+    if !triggered.is_empty() {
+        let subject = format!("[bye-watch] Price Alert{}", suffix);
+        let notification = Notification::alerts(subject, triggered);
+        notify(config, &notification)?;
+        record_sent(config, &notification.alerts, current_time);
+        for alert in &notification.alerts {
+            hook::run(&config.on_alert_command, alert);
+        }
+    }
+    if !reminders.is_empty() {
+        let subject = format!("[bye-watch] Still Active{}", suffix);
+        notify(config, &Notification::reminders(subject, reminders))?;
+    }
+    if !cleared.is_empty() {
+        let subject = format!("[bye-watch] All Clear{}", suffix);
+        notify(config, &Notification::cleared(subject, cleared))?;
+    }
+    Ok(())
+}
+
+type FiredByGroup = BTreeMap<Option<String>, Vec<FiredAlert>>;
+
+/// Applies `dedup_overlapping`: of the triggered Above (or Below) alerts of a
+/// symbol and price basis in a group, keeps the one with the highest (or
+/// lowest) threshold.
+fn collapse_overlapping(config: &Config, fired: FiredByGroup) -> FiredByGroup {
+    if !config.dedup_overlapping {
+        return fired;
+    }
+    let kind = |alert: &FiredAlert| {
+        let currency = config.currencies.get(alert.index?)?;
+        match currency.alert_condition {
+            AlertCondition::Above | AlertCondition::Below => {
+                Some((&currency.alert_condition, &currency.price_basis))
+            }
+            _ => None,
+        }
+    };
+    let mut collapsed = FiredByGroup::new();
+    for (group, alerts) in fired {
+        let mut kept: Vec<FiredAlert> = Vec::new();
+        for alert in alerts {
+            let Some(key) = kind(&alert) else {
+                kept.push(alert);
+                continue;
+            };
+            let above = *key.0 == AlertCondition::Above;
+            let (Some(threshold), AlertEvent::Triggered) = (alert.threshold, alert.event) else {
+                kept.push(alert);
+                continue;
+            };
+            let overlapping = kept.iter_mut().find(|k| {
+                k.event == AlertEvent::Triggered
+                    && k.symbol == alert.symbol
+                    && kind(k) == Some(key)
+                    && k.threshold.is_some()
+            });
+            let Some(other) = overlapping else {
+                kept.push(alert);
+                continue;
+            };
+            let other_threshold = other.threshold.unwrap_or(threshold);
+            let more_extreme = if above {
+                threshold > other_threshold
+            } else {
+                threshold < other_threshold
+            };
+            let (dropped, into) = if more_extreme {
+                let dropped = std::mem::replace(other, alert);
+                (dropped.rule, other.rule.clone())
+            } else {
+                (alert.rule, other.rule.clone())
+            };
+            println!("Collapsed {} into the overlapping {}", dropped, into);
+        }
+        collapsed.insert(group, kept);
+    }
+    collapsed
+}
+
+/// Applies `symbol_throttle_secs`: all triggered alerts of a symbol in one
+/// check go out together with its first one, and a symbol notified within
+/// the window is not notified again.
+fn throttle_symbols(config: &mut Config, fired: FiredByGroup, current_time: u64) -> FiredByGroup {
+    let Some(window) = config.symbol_throttle_secs else {
+        return fired;
+    };
+    let mut throttled = FiredByGroup::new();
+    let mut placed: HashMap<String, Option<String>> = HashMap::new();
+    for (group, alerts) in fired {
+        for alert in alerts {
+            if alert.event != AlertEvent::Triggered {
+                throttled.entry(group.clone()).or_default().push(alert);
+                continue;
+            }
+            if let Some(first_group) = placed.get(&alert.symbol) {
+                throttled
+                    .entry(first_group.clone())
+                    .or_default()
+                    .push(alert);
+                continue;
+            }
+            let recent = config
+                .symbol_last_notified
+                .get(&alert.symbol)
+                .is_some_and(|last| current_time.saturating_sub(*last) < window);
+            if recent {
+                println!(
+                    "Not notifying {}, {} was already notified within {} seconds",
+                    alert.rule, alert.symbol, window
+                );
+                continue;
+            }
+            placed.insert(alert.symbol.clone(), group.clone());
+            throttled.entry(group.clone()).or_default().push(alert);
+        }
+    }
+    throttled
+}
+
+/// Identifies an alert by its symbol, condition, threshold and price rounded
+/// to four significant digits. FNV-1a, so it stays stable across builds.
+fn alert_fingerprint(alert: &FiredAlert) -> String {
+    let key = format!(
+        "{}|{}|{:?}|{:.3e}",
+        alert.symbol, alert.condition, alert.threshold, alert.price
+    );
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Applies `dedup_window_secs`: drops triggered alerts identical to one that
+/// was sent within the window or to another one of this check.
+fn dedup_alerts(config: &mut Config, fired: FiredByGroup, current_time: u64) -> FiredByGroup {
+    let Some(window) = config.dedup_window_secs else {
+        return fired;
+    };
+    config
+        .sent_fingerprints
+        .retain(|_, sent| current_time.saturating_sub(*sent) < window);
+    let mut deduped = FiredByGroup::new();
+    let mut fingerprints = HashSet::new();
+    for (group, alerts) in fired {
+        for alert in alerts {
+            if alert.event == AlertEvent::Triggered {
+                let fingerprint = alert_fingerprint(&alert);
+                if !fingerprints.insert(fingerprint.clone()) {
+                    continue;
+                }
+                if let Some(sent) = config.sent_fingerprints.get(&fingerprint) {
+                    println!(
+                        "Not notifying {}, an identical alert was sent at {}",
+                        alert.rule,
+                        notify::format_time(*sent, config.timestamp_format())
+                    );
+                    continue;
+                }
+            }
+            deduped.entry(group.clone()).or_default().push(alert);
+        }
+    }
+    deduped
+}
+
+/// Mean and population standard deviation of `values`.
+fn mean_stddev(values: impl Iterator<Item = f64> + Clone) -> Option<(f64, f64)> {
+    let count = values.clone().count();
+    if count == 0 {
+        return None;
+    }
+    let mean = values.clone().sum::<f64>() / count as f64;
+    let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+    Some((mean, variance.sqrt()))
+}
+
+/// The `pct`th percentile of `values`, linearly interpolated between ranks.
+fn percentile(values: impl Iterator<Item = f64>, pct: f64) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(f64::total_cmp);
+    let rank = pct / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+/// The last `periods` prices of `history`, `None` while still warming up.
+fn last_prices(
+    history: Option<&VecDeque<PriceSample>>,
+    periods: usize,
+) -> Option<impl Iterator<Item = f64> + Clone + '_> {
+    let history = history?;
+    if history.len() < periods {
+        return None;
+    }
+    Some(
+        history
+            .iter()
+            .skip(history.len() - periods)
+            .map(|s| s.price),
+    )
+}
+
+/// Parses a price reported by the exchange, rejecting anything that is not a
+/// finite number so it can never trigger an alert.
+fn parse_price(raw: &str) -> Option<f64> {
+    raw.parse::<f64>().ok().filter(|price| price.is_finite())
+}
+
+/// Logs why the met condition of `rule` did not fire.
+fn log_suppressed(rule: &str, reason: Suppressed, withold_secs: u64) {
+    match reason {
+        Suppressed::Confirming { held, confirm } => println!(
+            "Condition met for {}, confirming ({}/{} seconds)",
+            rule, held, confirm
+        ),
+        Suppressed::ConfirmingCycles { met, needed } => println!(
+            "Condition met for {}, confirming ({}/{} checks)",
+            rule, met, needed
+        ),
+        Suppressed::InitialCrossing => println!(
+            "Alert condition met for {} since its first sample, waiting for it to cross again",
+            rule
+        ),
+        Suppressed::Snoozed { until } => println!(
+            "Alert condition met for {}, but acknowledged and snoozed until {}",
+            rule, until,
+        ),
+        Suppressed::RepeatsExhausted { count } => println!(
+            "Alert condition met for {}, but already alerted {} times, waiting for it to clear",
+            rule, count,
+        ),
+        Suppressed::Withheld => println!(
+            "Alert condition met for {}, but already alerted within {:.2} hours",
+            rule,
+            withold_secs as f64 / 3600.0,
+        ),
+    }
+}
+
+/// Clamps the check-wide timestamps that are later than `now`, like
+/// `AlertState::rewind` does for each alert. Returns whether anything moved.
+fn rewind_state(config: &mut Config, now: u64) -> bool {
+    let mut moved = false;
+    for time in config
+        .symbol_last_notified
+        .values_mut()
+        .chain(config.sent_fingerprints.values_mut())
+        .chain(
+            config
+                .price_history
+                .values_mut()
+                .flat_map(|history| history.iter_mut().rev().map(|sample| &mut sample.timestamp)),
+        )
+        .filter(|time| **time > now)
+    {
+        *time = now;
+        moved = true;
+    }
+    moved
+}
+
+/// Evaluates every alert against freshly fetched data, updating alert state
+/// and returning the alerts that fired or cleared, keyed by alert group.
+fn evaluate_alerts(
+    config: &mut Config,
+    market: &MarketData,
+    current_time: u64,
+) -> Result<FiredByGroup, Box<dyn std::error::Error>> {
+    let mut fired = FiredByGroup::new();
+    config.missing_symbol = None;
+    if rewind_state(config, current_time) {
+        eprintln!(
+            "Warning: the clock went backward, throttle, deduplication or price history \
+             state was in the future; restarting it from now"
+        );
+    }
+    let timestamp_format = config.timestamp_format().to_string();
+    let epsilon = config.comparison_epsilon.unwrap_or(0.0);
+    let minute_of_day = Local
+        .timestamp_opt(current_time as i64, 0)
+        .single()
+        .map(|t| t.hour() * 60 + t.minute());
+    config
+        .muted_symbols
+        .retain(|_, until| *until > current_time);
+    let muted: Vec<(bool, Option<u64>)> = config
+        .currencies
+        .iter()
+        .map(|c| {
+            (
+                config.is_muted(c),
+                config.muted_symbols.get(&c.symbol).copied(),
+            )
+        })
+        .collect();
+    let twaps = twaps(config, market, current_time);
+    for (index, (currency, (muted, muted_until))) in
+        config.currencies.iter_mut().zip(muted).enumerate()
+    {
+        currency.last_price = None;
+        currency.condition_met = false;
+        let expired = currency
+            .expires_at
+            .as_deref()
+            .and_then(|at| parse_expiry(at).ok())
+            .is_some_and(|at| at <= current_time);
+        if expired {
+            if !currency.expired {
+                currency.expired = true;
+                println!(
+                    "Alert expired: {} (expires_at {}), no longer checking it",
+                    currency.rule(),
+                    currency.expires_at.as_deref().unwrap_or_default()
+                );
+            }
+            config.stats.suppressed("expired");
+            continue;
+        }
+        // A later expires_at revives the alert
+        currency.expired = false;
+        if muted {
+            println!("Skipping {}, its asset is muted", currency.rule());
+            config.stats.suppressed("muted");
+            continue;
+        }
+        if let Some(until) = muted_until {
+            println!(
+                "Skipping {}, muted until {}",
+                currency.rule(),
+                notify::format_time(until, &timestamp_format)
+            );
+            config.stats.suppressed("muted");
+            continue;
+        }
+        let inactive = currency
+            .active_hours
+            .as_deref()
+            .and_then(|window| parse_active_hours(window).ok())
+            .zip(minute_of_day)
+            .is_some_and(|(window, minute)| !in_active_hours(window, minute));
+        if inactive {
+            println!(
+                "Skipping {}, outside its active hours {}",
+                currency.rule(),
+                currency.active_hours.as_deref().unwrap_or_default()
+            );
+            config.stats.suppressed("inactive_hours");
+            continue;
+        }
+        let premium = market
+            .premium_index
+            .iter()
+            .find(|p| p.symbol == currency.symbol);
+        let raw_price = basis_price(currency, market, &twaps);
+        if let Some(raw_price) = raw_price {
+            config.warned_missing_symbols.remove(&currency.symbol);
+            let Some(price) = parse_price(raw_price) else {
+                eprintln!(
+                    "Ignoring invalid price \"{}\" for {}, treating as no data",
+                    raw_price, currency.symbol
+                );
+                continue;
+            };
+            currency.last_price = Some(price);
+            if let (Some(Threshold::Expression(expr)), None, None) = (
+                &currency.threshold,
+                currency.threshold.as_ref().and_then(Threshold::fixed),
+                currency.anchored_threshold,
+            ) {
+                let anchored = resolve_threshold_expression(expr, price)?;
+                if !anchored.is_finite() {
+                    return Err(format!(
+                        "{}: threshold \"{}\" resolved to {}",
+                        currency.symbol, expr, anchored
+                    )
+                    .into());
+                }
+                println!(
+                    "Anchored {} threshold \"{}\" to {:.2} (price {})",
+                    currency.symbol, expr, anchored, price
+                );
+                currency.anchored_threshold = Some(anchored);
+            }
+            let history = config.price_history.get(&currency.symbol);
+            let mut withold_time_secs = config.withold_notification_h.unwrap_or(
+                DEFAULT_WITHOLD_SECS, // Default to 24 hours if not specified
+            );
+            if let Some(cooldown) = &config.adaptive_cooldown {
+                withold_time_secs = cooldown.withold_secs(withold_time_secs, history);
+            }
+            if !currency.tiers.is_empty() {
+                let first_seen = *currency.initialized.get_or_insert(current_time);
+                let policy = currency.policy(
+                    withold_time_secs,
+                    config.suppress_initial_crossings.then_some(first_seen),
+                );
+                let (mut hit, mut reminded, mut cleared) = (Vec::new(), Vec::new(), Vec::new());
+                for (index, decision) in currency.evaluate_tiers(price, &policy, current_time) {
+                    let tier = &currency.tiers[index];
+                    match decision {
+                        AlertDecision::Fire => hit.push(index),
+                        AlertDecision::Remind(_) => reminded.push(tier.describe()),
+                        AlertDecision::Clear => cleared.push(tier.describe()),
+                        AlertDecision::Suppress(reason) => {
+                            config.stats.suppressed(reason.reason());
+                            let rule = format!("{} tier {}", currency.rule(), tier.label);
+                            log_suppressed(&rule, reason, withold_time_secs);
+                        }
+                        AlertDecision::NoOp => {}
+                    }
+                }
+                currency.condition_met = currency.tiers.iter().any(|t| t.hit_at.is_some());
+                currency.last_alerted = currency.tiers.iter().filter_map(|t| t.hit_at).max();
+                let tier_alert = |currency: &CurrencyAlert, event, prefix, labels: &[String]| {
+                    let details: String = labels
+                        .iter()
+                        .map(|label| format!("{}: {}\n", prefix, label))
+                        .collect();
+                    currency.fired(event, price, details, current_time)
+                };
+                if let Some(&furthest) = hit.last() {
+                    let labels: Vec<String> =
+                        hit.iter().map(|&i| currency.tiers[i].describe()).collect();
+                    println!("Tiers hit for {}: {}", currency.rule(), labels.join(", "));
+                    let ack_url = currency.new_ack_url(config.http.as_ref());
+                    let mut alert =
+                        tier_alert(currency, AlertEvent::Triggered, "Tier hit", &labels);
+                    alert.threshold = Some(currency.tiers[furthest].threshold);
+                    alert.ack_url = ack_url;
+                    fired
+                        .entry(currency.group.clone())
+                        .or_default()
+                        .push(FiredAlert {
+                            index: Some(index),
+                            ..alert
+                        });
+                }
+                if !reminded.is_empty() {
+                    println!("Reminding that tiers of {} are still met", currency.rule());
+                    let alert =
+                        tier_alert(currency, AlertEvent::Reminder, "Tier still met", &reminded);
+                    fired
+                        .entry(currency.group.clone())
+                        .or_default()
+                        .push(FiredAlert {
+                            index: Some(index),
+                            ..alert
+                        });
+                }
+                if !cleared.is_empty() {
+                    println!(
+                        "Tiers of {} no longer met: {}",
+                        currency.rule(),
+                        cleared.join(", ")
+                    );
+                    if currency.notify_on_clear.unwrap_or(config.notify_on_clear) {
+                        let alert =
+                            tier_alert(currency, AlertEvent::Cleared, "Tier cleared", &cleared);
+                        fired
+                            .entry(currency.group.clone())
+                            .or_default()
+                            .push(FiredAlert {
+                                index: Some(index),
+                                ..alert
+                            });
+                    }
+                }
+                continue;
+            }
+            let daily_ticker = market
+                .daily_tickers
+                .iter()
+                .find(|t| t.symbol == currency.symbol);
+            let daily_change = daily_ticker.and_then(|t| parse_price(&t.price_change_percent));
+            let velocity = history
+                .and_then(|history| history.back())
+                .and_then(|previous| {
+                    previous.velocity_to(&PriceSample {
+                        price,
+                        timestamp: current_time,
+                    })
+                });
+            let funding_rate = premium.and_then(|p| parse_price(&p.last_funding_rate));
+            let funding_details = |rate: f64| {
+                let next = premium
+                    .and_then(|p| Local.timestamp_millis_opt(p.next_funding_time).single())
+                    .map(|t| t.format(&timestamp_format).to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                format!(
+                    "Funding rate: {:.4}% (next funding {})\n",
+                    rate * 100.0,
+                    next
+                )
+            };
+            let depth = market
+                .depth
+                .get(&currency.symbol)
+                .and_then(BinanceDepth::volumes);
+            let on_tick = |value: f64| currency.tick.map_or(value, |tick| tick.round(value));
+            let reference_price = currency
+                .alert_condition
+                .reference_symbols()
+                .first()
+                .and_then(|reference| market.prices.iter().find(|p| p.symbol == *reference))
+                .and_then(|p| parse_price(&p.price));
+            let previous_price = history.and_then(|h| h.back()).map(|sample| sample.price);
+            let evaluation = match currency.alert_condition {
+                AlertCondition::Above | AlertCondition::Below => currency.threshold().map(|t| {
+                    let on_side = |p: f64| match currency.alert_condition {
+                        AlertCondition::Above if currency.inclusive => on_tick(p) >= on_tick(t),
+                        AlertCondition::Above => on_tick(p) > on_tick(t),
+                        _ if currency.inclusive => on_tick(p) <= on_tick(t),
+                        _ => on_tick(p) < on_tick(t),
+                    };
+                    // Once crossed, the alert stays armed until it clears
+                    let armed = !currency.require_crossing
+                        || currency.last_alerted.is_some()
+                        || previous_price.is_some_and(|p| !on_side(p));
+                    // Inside the dead band the previous state holds
+                    let in_band = epsilon > 0.0 && (on_tick(price) - on_tick(t)).abs() <= epsilon;
+                    let met = if in_band {
+                        currency.met_since.is_some()
+                    } else {
+                        on_side(price)
+                    };
+                    (met && armed, String::new())
+                }),
+                AlertCondition::DailyChangeAbove { pct } => {
+                    daily_change.map(|c| (c > pct, format!("24h change: {:.2}%\n", c)))
+                }
+                AlertCondition::DailyChangeBelow { pct } => {
+                    daily_change.map(|c| (c < pct, format!("24h change: {:.2}%\n", c)))
+                }
+                AlertCondition::Velocity { pct_per_min } => {
+                    velocity.map(|v| (v.abs() > pct_per_min, format!("Velocity: {:.3}%/min\n", v)))
+                }
+                AlertCondition::FundingAbove { pct } => {
+                    funding_rate.map(|r| (r * 100.0 > pct, funding_details(r)))
+                }
+                AlertCondition::FundingBelow { pct } => {
+                    funding_rate.map(|r| (r * 100.0 < pct, funding_details(r)))
+                }
+                AlertCondition::PercentileBreak {
+                    periods,
+                    percentile: pct,
+                } => last_prices(history, periods)
+                    .and_then(|prices| percentile(prices, pct))
+                    .map(|value| {
+                        let breached = if pct >= 50.0 {
+                            price > value
+                        } else {
+                            price < value
+                        };
+                        (
+                            breached,
+                            format!("p{} of {} checks: {:.2}\n", pct, periods, value),
+                        )
+                    }),
+                AlertCondition::SmaSpread { short, long, pct } => last_prices(history, long)
+                    .zip(last_prices(history, short))
+                    .and_then(|(long_prices, short_prices)| {
+                        Some((mean_stddev(short_prices)?.0, mean_stddev(long_prices)?.0))
+                    })
+                    .map(|(short_sma, long_sma)| {
+                        let gap = (short_sma - long_sma) / long_sma * 100.0;
+                        (
+                            gap.abs() > pct,
+                            format!(
+                                "SMA {}: {:.2}\nSMA {}: {:.2}\nGap: {:+.2}%\n",
+                                short, short_sma, long, long_sma, gap
+                            ),
+                        )
+                    }),
+                AlertCondition::BollingerBreak { periods, k } => last_prices(history, periods)
+                    .and_then(mean_stddev)
+                    .map(|(mean, stddev)| {
+                        let (lower, upper) = (mean - k * stddev, mean + k * stddev);
+                        (
+                            price < lower || price > upper,
+                            format!(
+                                "Bollinger bands: {:.2} - {:.2} (mean {:.2})\n",
+                                lower, upper, mean
+                            ),
+                        )
+                    }),
+                AlertCondition::DepthImbalance { ratio, side } => depth.map(|(bids, asks)| {
+                    let triggered = match side {
+                        DepthSide::Bid => bids / asks > ratio,
+                        DepthSide::Ask => asks / bids > ratio,
+                        DepthSide::Either => bids / asks > ratio || asks / bids > ratio,
+                    };
+                    (
+                        triggered,
+                        format!(
+                            "Order book depth: {} bid / {} ask (ratio {:.2})\n",
+                            bids,
+                            asks,
+                            bids / asks
+                        ),
+                    )
+                }),
+                AlertCondition::DataStale { max_age_secs } => market
+                    .last_trades
+                    .get(&currency.symbol)
+                    .map(|&updated| match updated {
+                        Some(updated) => {
+                            let age = current_time.saturating_sub(updated);
+                            (
+                                age > max_age_secs,
+                                format!(
+                                    "Exchange data age: {}s (last trade {})\n",
+                                    age,
+                                    notify::format_time(updated, &timestamp_format)
+                                ),
+                            )
+                        }
+                        None => (true, "Exchange data age: no trade on record\n".to_string()),
+                    }),
+                AlertCondition::NearHigh { pct } | AlertCondition::NearLow { pct } => daily_ticker
+                    .and_then(|ticker| {
+                        let near_high =
+                            matches!(currency.alert_condition, AlertCondition::NearHigh { .. });
+                        let (name, extreme) = if near_high {
+                            ("high", &ticker.high_price)
+                        } else {
+                            ("low", &ticker.low_price)
+                        };
+                        let extreme = parse_price(extreme).filter(|e| *e > 0.0)?;
+                        let distance = (price - extreme).abs() / extreme * 100.0;
+                        Some((
+                            distance <= pct,
+                            format!("24h {}: {} ({:.2}% away)\n", name, extreme, distance),
+                        ))
+                    }),
+                AlertCondition::TradeCountAbove { threshold } => daily_ticker.map(|ticker| {
+                    (
+                        ticker.count > threshold,
+                        format!("24h trades: {}\n", ticker.count),
+                    )
+                }),
+                AlertCondition::PriceBreakWithVolume {
+                    side,
+                    price: level,
+                    min_volume,
+                } => daily_ticker
+                    .and_then(|ticker| parse_price(&ticker.quote_volume))
+                    .map(|volume| {
+                        let broken = match side {
+                            BreakSide::Above => on_tick(price) > on_tick(level),
+                            BreakSide::Below => on_tick(price) < on_tick(level),
+                        };
+                        (
+                            broken && volume > min_volume,
+                            format!(
+                                "Price: {}\n24h volume: {} (minimum {})\n",
+                                raw_price, volume, min_volume
+                            ),
+                        )
+                    }),
+                AlertCondition::ArbSpread {
+                    exchange_a,
+                    exchange_b,
+                    pct,
+                } => {
+                    let quote = |source: PriceSource| {
+                        let raw = match source {
+                            // Unless `price_sources` replaced it by a fallback
+                            PriceSource::Binance => market
+                                .prices
+                                .iter()
+                                .find(|p| p.symbol == currency.symbol)
+                                .filter(|_| !market.fallback_sources.contains_key(&currency.symbol))
+                                .map(|p| &p.price),
+                            _ => market
+                                .exchange_prices
+                                .get(&(currency.symbol.clone(), source)),
+                        };
+                        raw.and_then(|raw| parse_price(raw)).filter(|p| *p > 0.0)
+                    };
+                    quote(exchange_a).zip(quote(exchange_b)).map(|(a, b)| {
+                        let spread = (a - b).abs() / a.min(b) * 100.0;
+                        (
+                            spread > pct,
+                            format!(
+                                "{}: {}\n{}: {}\nSpread: {:.2}%\n",
+                                exchange_a, a, exchange_b, b, spread
+                            ),
+                        )
+                    })
+                }
+                AlertCondition::Formula { ref formula } => {
+                    let price_of = |symbol: &str| {
+                        market
+                            .prices
+                            .iter()
+                            .find(|p| p.symbol == symbol)
+                            .and_then(|p| parse_price(&p.price))
+                    };
+                    match formula::parse(formula).and_then(|f| f.evaluate(price_of)) {
+                        Ok((holds, value)) => Some((holds, format!("Formula value: {}\n", value))),
+                        Err(e) => {
+                            eprintln!("{}: {}, skipping", currency.rule(), e);
+                            None
+                        }
+                    }
+                }
+                AlertCondition::IndexDeviation { pct } => premium
+                    .and_then(|p| parse_price(&p.index_price))
+                    .filter(|index| *index > 0.0)
+                    .map(|index| {
+                        let deviation = (price - index) / index * 100.0;
+                        (
+                            deviation.abs() > pct,
+                            format!(
+                                "Spot: {}\nIndex: {}\nDeviation: {:+.2}%\n",
+                                price, index, deviation
+                            ),
+                        )
+                    }),
+                AlertCondition::Depeg { peg, tolerance } => {
+                    let deviation = price - peg;
+                    let depegged = deviation.abs() > tolerance;
+                    let details = if depegged {
+                        format!(
+                            "{} depegged to {} ({:+.2}% from {})\n",
+                            currency.name(),
+                            raw_price,
+                            deviation / peg * 100.0,
+                            peg
+                        )
+                    } else {
+                        format!(
+                            "{} back within {} of {} at {}\n",
+                            currency.name(),
+                            tolerance,
+                            peg,
+                            raw_price
+                        )
+                    };
+                    Some((depegged, details))
+                }
+                AlertCondition::RelativeAbove {
+                    ref reference,
+                    factor,
+                }
+                | AlertCondition::RelativeBelow {
+                    ref reference,
+                    factor,
+                } => reference_price.map(|reference_price| {
+                    let threshold = factor * reference_price;
+                    let triggered = match currency.alert_condition {
+                        AlertCondition::RelativeAbove { .. } => price > threshold,
+                        _ => price < threshold,
+                    };
+                    (
+                        triggered,
+                        format!(
+                            "{} price: {}\nThreshold: {} x {} = {:.8}\n",
+                            reference, reference_price, factor, reference_price, threshold
+                        ),
+                    )
+                }),
+            };
+            let Some((alert_triggered, mut details)) = evaluation else {
+                match currency.alert_condition {
+                    AlertCondition::Velocity { .. } => {
+                        println!(
+                            "Waiting for a second sample to evaluate {}",
+                            currency.rule()
+                        )
+                    }
+                    AlertCondition::DailyChangeAbove { .. }
+                    | AlertCondition::DailyChangeBelow { .. }
+                    | AlertCondition::NearHigh { .. }
+                    | AlertCondition::NearLow { .. }
+                    | AlertCondition::TradeCountAbove { .. }
+                    | AlertCondition::PriceBreakWithVolume { .. } => {
+                        eprintln!("No 24h ticker data found for {}", currency.symbol)
+                    }
+                    AlertCondition::DataStale { .. } => {
+                        eprintln!("No last trade found for {}", currency.symbol)
+                    }
+                    AlertCondition::FundingAbove { .. } | AlertCondition::FundingBelow { .. } => {
+                        eprintln!("No funding rate found for {}", currency.symbol)
+                    }
+                    AlertCondition::BollingerBreak { periods, .. }
+                    | AlertCondition::PercentileBreak { periods, .. }
+                    | AlertCondition::SmaSpread { long: periods, .. } => println!(
+                        "Warming up {}, {}/{} samples",
+                        currency.rule(),
+                        history.map_or(0, |h| h.len()),
+                        periods
+                    ),
+                    AlertCondition::DepthImbalance { .. } => {
+                        eprintln!("No order book depth found for {}", currency.symbol)
+                    }
+                    AlertCondition::ArbSpread {
+                        exchange_a,
+                        exchange_b,
+                        ..
+                    } => eprintln!(
+                        "Missing the {} or {} price of {}, skipping {}",
+                        exchange_a,
+                        exchange_b,
+                        currency.symbol,
+                        currency.rule()
+                    ),
+                    AlertCondition::RelativeAbove { ref reference, .. }
+                    | AlertCondition::RelativeBelow { ref reference, .. } => eprintln!(
+                        "No price for reference {}, skipping {}",
+                        reference,
+                        currency.rule()
+                    ),
+                    AlertCondition::Above | AlertCondition::Below => {
+                        eprintln!("No threshold available for {}", currency.rule())
+                    }
+                    AlertCondition::IndexDeviation { .. } => {
+                        eprintln!("No index price found for {}", currency.symbol)
+                    }
+                    // already said which leg is missing
+                    AlertCondition::Formula { .. } => {}
+                    // always evaluated once a price is known
+                    AlertCondition::Depeg { .. } => {}
+                }
+                continue;
+            };
+            let first_seen = *currency.initialized.get_or_insert(current_time);
+            let spot_price =
+                !currency.alert_condition.needs_premium_index() && currency.price_basis.is_spot();
+            if let Some(source) = market.fallback_sources.get(&currency.symbol) {
+                if spot_price {
+                    details.push_str(&format!("Price source: {}\n", source));
+                }
+            }
+            if currency.price_basis == PriceBasis::Mark {
+                details.push_str(&format!(
+                    "Mark price: {}\n",
+                    config.number_format.apply(raw_price)
+                ));
+            }
+            let policy = currency.policy(
+                withold_time_secs,
+                config.suppress_initial_crossings.then_some(first_seen),
+            );
+            let mut state = alert::AlertState {
+                last_alerted: currency.last_alerted,
+                repeat_count: currency.repeat_count,
+                met_since: currency.met_since,
+                last_reminded: currency.last_reminded,
+                met_cycles: currency.met_cycles,
+            };
+            if state.rewind(current_time) {
+                eprintln!(
+                    "Warning: the clock went backward, state of {} was in the future; \
+                     restarting its windows from now",
+                    currency.rule()
+                );
+            }
+            let previous_alerted = currency.last_alerted;
+            let (decision, state) = alert::evaluate(state, &policy, alert_triggered, current_time);
+            currency.last_alerted = state.last_alerted;
+            currency.repeat_count = state.repeat_count;
+            currency.met_since = state.met_since;
+            currency.last_reminded = state.last_reminded;
+            currency.met_cycles = state.met_cycles;
+            currency.condition_met = alert_triggered
+                && !matches!(
+                    decision,
+                    AlertDecision::Suppress(
+                        Suppressed::Confirming { .. } | Suppressed::ConfirmingCycles { .. }
+                    )
+                );
+
+            let reason = match decision {
+                AlertDecision::Suppress(reason) | AlertDecision::Remind(reason) => Some(reason),
+                _ => None,
+            };
+            if let AlertDecision::Suppress(reason) = decision {
+                config.stats.suppressed(reason.reason());
+            }
+            if let Some(reason) = reason {
+                log_suppressed(&currency.rule(), reason, withold_time_secs);
+            }
+
+            match decision {
+                AlertDecision::Fire => {
+                    println!(
+                        "Alert triggered for {}. Current price {} (now: {}, last alerted: {}, withold_time_secs: {})",
+                        currency.rule(),
+                        config.number_format.apply(raw_price),
+                        current_time,
+                        previous_alerted.unwrap_or(0),
+                        withold_time_secs,
+                    );
+                    let ack_url = currency.new_ack_url(config.http.as_ref());
+                    let mut alert =
+                        currency.fired(AlertEvent::Triggered, price, details, current_time);
+                    alert.ack_url = ack_url;
+                    fired
+                        .entry(currency.group.clone())
+                        .or_default()
+                        .push(FiredAlert {
+                            index: Some(index),
+                            ..alert
+                        });
+                }
+                AlertDecision::Remind(_) => {
+                    println!("Reminding that {} is still met", currency.rule());
+                    let alert = currency.fired(AlertEvent::Reminder, price, details, current_time);
+                    fired
+                        .entry(currency.group.clone())
+                        .or_default()
+                        .push(FiredAlert {
+                            index: Some(index),
+                            ..alert
+                        });
+                }
+                AlertDecision::Suppress(_) => {}
+                AlertDecision::Clear => {
+                    println!(
+                        "Condition no longer met for {}, resetting alert status",
+                        currency.rule()
+                    );
+                    if currency.notify_on_clear.unwrap_or(config.notify_on_clear) {
+                        let alert =
+                            currency.fired(AlertEvent::Cleared, price, details, current_time);
+                        fired
+                            .entry(currency.group.clone())
+                            .or_default()
+                            .push(FiredAlert {
+                                index: Some(index),
+                                ..alert
+                            });
+                    }
+                }
+                AlertDecision::NoOp => println!(
+                    "Alert condition NOT met for {}, current price: {}",
+                    currency.rule(),
+                    config.number_format.apply(raw_price)
+                ),
+            }
+        } else if let PriceBasis::Vwap { .. } = currency.price_basis {
+            // `fetch_vwaps` already said why there is no VWAP yet
+            println!("Skipping {} until its VWAP is available", currency.rule());
+        } else if let (PriceBasis::Twap { minutes }, true) = (
+            &currency.price_basis,
+            market.prices.iter().any(|p| p.symbol == currency.symbol),
+        ) {
+            println!(
+                "Skipping {} until {} minutes of price history are recorded",
+                currency.rule(),
+                minutes
+            );
+        } else {
+            match config.missing_symbol_policy {
+                MissingSymbolPolicy::WarnOnce => {
+                    if config
+                        .warned_missing_symbols
+                        .insert(currency.symbol.clone())
+                    {
+                        eprintln!(
+                            "No price data found for {} (further warnings suppressed)",
+                            currency.symbol
+                        );
+                    }
+                }
+                MissingSymbolPolicy::WarnEveryCycle => {
+                    eprintln!("No price data found for {}", currency.symbol);
+                }
+                // Raised by `check_currencies` once the other alerts are sent
+                MissingSymbolPolicy::Error => {
+                    eprintln!("No price data found for {}", currency.symbol);
+                    config
+                        .missing_symbol
+                        .get_or_insert_with(|| currency.symbol.clone());
+                }
+            }
+        }
+    }
+
+    let capacities = history_capacities(config);
+    for price in &market.prices {
+        if let Some(value) = parse_price(&price.price) {
+            let capacity = capacities
+                .get(&price.symbol)
+                .copied()
+                .unwrap_or(HISTORY_SAMPLES);
+            let history = config
+                .price_history
+                .entry(price.symbol.clone())
+                .or_default();
+            // Another job, or a restart within the same second, already
+            // recorded this cycle
+            if history
+                .back()
+                .is_some_and(|last| last.timestamp >= current_time)
+            {
+                continue;
+            }
+            if let (Some(min_change), Some(last)) = (config.history_min_change_pct, history.back())
+            {
+                let moved = ((value - last.price) / last.price).abs() * 100.0 >= min_change;
+                let max_gap = config
+                    .history_max_gap_secs
+                    .unwrap_or(DEFAULT_HISTORY_MAX_GAP_SECS);
+                if !moved && current_time - last.timestamp < max_gap {
+                    continue;
+                }
+            }
+            while history.len() >= capacity {
+                history.pop_front();
+            }
+            history.push_back(PriceSample {
+                price: value,
+                timestamp: current_time,
+            });
+        }
+    }
+
+    Ok(fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(currencies: Vec<CurrencyAlert>) -> Config {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "withold_notification_h": null,
+            "currencies": [],
+        }))
+        .unwrap();
+        config.currencies = currencies;
+        config
+    }
+
+    /// A file name in the temp directory no other test or test run uses.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bye-watch-{}-{}", std::process::id(), name))
+    }
+
+    fn alert(symbol: &str, condition: AlertCondition, threshold: f64) -> CurrencyAlert {
+        serde_json::from_value(serde_json::json!({
+            "symbol": symbol,
+            "threshold": threshold,
+            "alert_condition": condition,
+            "last_alerted": null,
+        }))
+        .unwrap()
+    }
+
+    fn price(symbol: &str, price: &str) -> BinancePrice {
+        BinancePrice {
+            symbol: symbol.to_string(),
+            price: price.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_price_rejects_non_finite_values() {
+        assert_eq!(parse_price("123.45"), Some(123.45));
+        assert_eq!(parse_price("NaN"), None);
+        assert_eq!(parse_price("inf"), None);
+        assert_eq!(parse_price("-infinity"), None);
+        assert_eq!(parse_price("garbage"), None);
+    }
+
+    #[test]
+    fn non_finite_price_does_not_fire() {
+        for raw in ["NaN", "inf", "-inf"] {
+            let mut config = test_config(vec![
+                alert("BTCEUR", AlertCondition::Above, 100.0),
+                alert("ETHEUR", AlertCondition::Below, 100.0),
+            ]);
+            let market = MarketData {
+                prices: vec![price("BTCEUR", raw), price("ETHEUR", raw)],
+                ..Default::default()
+            };
+
+            let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+
+            assert!(fired.is_empty(), "{} fired an alert", raw);
+            for currency in &config.currencies {
+                assert_eq!(currency.last_alerted, None);
+                assert_eq!(currency.last_price, None);
+                assert!(!currency.condition_met);
+            }
+            assert!(config.price_history.is_empty());
+        }
+    }
+
+    #[test]
+    fn finite_price_still_fires() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+
+        assert_eq!(fired.len(), 1);
+        assert_eq!(config.currencies[0].last_alerted, Some(1_000));
+    }
+
+    #[test]
+    fn non_finite_thresholds_are_rejected() {
+        for threshold in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut currency = alert("BTCEUR", AlertCondition::Above, 0.0);
+            currency.threshold = Some(Threshold::Value(threshold));
+            assert!(validate_config(&test_config(vec![currency])).is_err());
+        }
+
+        let mut currency = alert("BTCEUR", AlertCondition::Above, 0.0);
+        currency.alert_condition = AlertCondition::Velocity {
+            pct_per_min: f64::NAN,
+        };
+        assert!(validate_config(&test_config(vec![currency])).is_err());
+
+        let config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn number_format_groups_thousands() {
+        assert_eq!(NumberFormat::Plain.apply("1234567.89"), "1234567.89");
+        assert_eq!(NumberFormat::European.apply("1234.56"), "1.234,56");
+        assert_eq!(NumberFormat::European.apply("-1234567.5"), "-1.234.567,5");
+        assert_eq!(NumberFormat::English.apply("123.45"), "123.45");
+        assert_eq!(NumberFormat::English.apply("1000"), "1,000");
+    }
+
+    #[test]
+    fn compact_numbers_use_suffixes_from_a_thousand() {
+        assert_eq!(NumberFormat::Plain.compact(999.99), None);
+        assert_eq!(NumberFormat::Plain.compact(65_432.1).unwrap(), "65.43K");
+        assert_eq!(
+            NumberFormat::European.compact(1_234_567.891).unwrap(),
+            "1,23M"
+        );
+        assert_eq!(
+            NumberFormat::English.compact(-2.5e12).unwrap(),
+            "-2,500.00B"
+        );
+    }
+
+    #[test]
+    fn active_hours_wrap_past_midnight() {
+        let day = parse_active_hours("09:30-16:00").unwrap();
+        assert_eq!(day, (570, 960));
+        assert!(in_active_hours(day, 600));
+        assert!(!in_active_hours(day, 960));
+
+        let night = parse_active_hours("22:00-02:00").unwrap();
+        assert!(in_active_hours(night, 23 * 60));
+        assert!(in_active_hours(night, 60));
+        assert!(!in_active_hours(night, 12 * 60));
+
+        assert!(parse_active_hours("9-17").is_err());
+        assert!(parse_active_hours("24:00-01:00").is_err());
+    }
+
+    #[test]
+    fn price_tick_precision_and_rounding() {
+        let tick = PriceTick::parse("0.01000000").unwrap();
+        assert_eq!(tick.decimals, 2);
+        assert_eq!(tick.round(50000.004), 50000.0);
+        assert_eq!(tick.round(0.1 + 0.2), 0.3);
+        assert_eq!(PriceTick::parse("1.00000000").unwrap().decimals, 0);
+        assert_eq!(PriceTick::parse("0").map(|t| t.decimals), None);
+    }
+
+    #[test]
+    fn require_crossing_ignores_an_already_satisfied_condition() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.currencies[0].require_crossing = true;
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_000).unwrap();
+        assert!(fired.is_empty());
+        let fired = evaluate_alerts(&mut config, &market("160"), 1_060).unwrap();
+        assert!(fired.is_empty());
+        evaluate_alerts(&mut config, &market("90"), 1_120).unwrap();
+        let fired = evaluate_alerts(&mut config, &market("110"), 1_180).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+        evaluate_alerts(&mut config, &market("120"), 1_240).unwrap();
+        assert!(config.currencies[0].condition_met);
+    }
+
+    #[test]
+    fn confirm_secs_debounces_short_spikes() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.currencies[0].confirm_secs = Some(60);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+
+        assert!(evaluate_alerts(&mut config, &market("150"), 1_000)
+            .unwrap()
+            .is_empty());
+        evaluate_alerts(&mut config, &market("90"), 1_030).unwrap();
+        assert!(evaluate_alerts(&mut config, &market("150"), 1_060)
+            .unwrap()
+            .is_empty());
+        assert!(evaluate_alerts(&mut config, &market("150"), 1_090)
+            .unwrap()
+            .is_empty());
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_120).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+    }
+
+    #[test]
+    fn symbol_throttle_consolidates_and_suppresses() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("BTCEUR", AlertCondition::Above, 120.0),
+        ]);
+        config.currencies[1].group = Some("big".to_string());
+        config.symbol_throttle_secs = Some(600);
+        config.withold_notification_h = Some(0);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired = throttle_symbols(&mut config, fired, 1_000);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[&None].len(), 2);
+        record_sent(&mut config, &fired[&None], 1_000);
+
+        let fired = evaluate_alerts(&mut config, &market, 1_300).unwrap();
+        assert!(throttle_symbols(&mut config, fired, 1_300).is_empty());
+    }
+
+    #[test]
+    fn throttled_alerts_fire_again_once_the_throttle_ends() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.symbol_throttle_secs = Some(600);
+        config
+            .symbol_last_notified
+            .insert("BTCEUR".to_string(), 1_000);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        let check = |config: &mut Config, now: u64| {
+            let notified: Vec<Notified> = config.currencies.iter().map(|c| c.notified()).collect();
+            let fired = evaluate_alerts(config, &market, now).unwrap();
+            hold_back(config, &notified, fired, now)
+        };
+
+        assert!(check(&mut config, 1_300).is_empty());
+        assert_eq!(config.currencies[0].last_alerted, None);
+        assert_eq!(check(&mut config, 1_600).values().flatten().count(), 1);
+        assert_eq!(config.currencies[0].last_alerted, Some(1_600));
+    }
+
+    #[test]
+    fn overlapping_rules_collapse_into_the_most_extreme() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("BTCEUR", AlertCondition::Above, 120.0),
+            alert("BTCEUR", AlertCondition::Above, 110.0),
+            alert("BTCEUR", AlertCondition::Below, 200.0),
+            alert("ETHEUR", AlertCondition::Above, 100.0),
+            alert("BTCEUR", AlertCondition::Above, 130.0),
+        ]);
+        config.currencies[5].price_basis = PriceBasis::CandleClose {
+            interval: "1h".to_string(),
+        };
+        config.dedup_overlapping = true;
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150"), price("ETHEUR", "150")],
+            candle_closes: HashMap::from([(
+                ("BTCEUR".to_string(), "1h".to_string()),
+                "140".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired = collapse_overlapping(&config, fired);
+        let rules: Vec<&str> = fired[&None].iter().map(|a| a.rule.as_str()).collect();
+        assert_eq!(
+            rules,
+            vec![
+                "BTCEUR Above 120",
+                "BTCEUR Below 200",
+                "ETHEUR Above 100",
+                "BTCEUR Above 130 on 1h close"
+            ]
+        );
+        assert!(config.currencies.iter().all(|c| c.last_alerted.is_some()));
+    }
+
+    #[test]
+    fn comparison_epsilon_keeps_state_near_threshold() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.comparison_epsilon = Some(0.5);
+        let mut check = |raw: &str, now: u64| {
+            let market = MarketData {
+                prices: vec![price("BTCEUR", raw)],
+                ..Default::default()
+            };
+            evaluate_alerts(&mut config, &market, now).unwrap();
+            config.currencies[0].condition_met
+        };
+
+        assert!(!check("100.3", 1_000));
+        assert!(check("100.6", 1_060));
+        assert!(check("99.8", 1_120));
+        assert!(!check("99.4", 1_180));
+    }
+
+    #[test]
+    fn rules_on_the_same_symbol_keep_independent_state() {
+        let mut config = test_config(vec![
+            alert("BTCUSDT", AlertCondition::Above, 100.0),
+            alert("BTCUSDT", AlertCondition::Above, 200.0),
+        ]);
+        let mut check = |raw: &str, now: u64| {
+            let market = MarketData {
+                prices: vec![price("BTCUSDT", raw)],
+                ..Default::default()
+            };
+            let fired = evaluate_alerts(&mut config, &market, now).unwrap();
+            let rules: Vec<String> = fired.values().flatten().map(|a| a.rule.clone()).collect();
+            let alerted: Vec<bool> = config
+                .currencies
+                .iter()
+                .map(|c| c.last_alerted.is_some())
+                .collect();
+            (rules, alerted)
+        };
+
+        assert_eq!(
+            check("150", 1_000),
+            (vec!["BTCUSDT Above 100".to_string()], vec![true, false])
+        );
+        assert_eq!(
+            check("250", 1_060),
+            (vec!["BTCUSDT Above 200".to_string()], vec![true, true])
+        );
+        assert_eq!(check("120", 1_120), (vec![], vec![true, false]));
+        assert_eq!(check("90", 1_180), (vec![], vec![false, false]));
+    }
+
+    #[test]
+    fn weight_tracker_defers_until_budget_frees() {
+        let mut spent = VecDeque::from([(1_000, 30), (1_020, 50)]);
+        assert_eq!(weight::wait_secs(&mut spent, 20, 100, 1_030), 0);
+        assert_eq!(weight::wait_secs(&mut spent, 40, 100, 1_030), 30);
+        assert_eq!(weight::wait_secs(&mut spent, 40, 100, 1_060), 0);
+        assert_eq!(spent.len(), 1);
+        assert_eq!(weight::wait_secs(&mut spent, 500, 100, 1_060), 20);
+    }
+
+    #[test]
+    fn price_history_skips_samples_already_recorded() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+        evaluate_alerts(&mut config, &market("90"), 1_000).unwrap();
+        evaluate_alerts(&mut config, &market("91"), 1_000).unwrap();
+        evaluate_alerts(&mut config, &market("92"), 990).unwrap();
+        evaluate_alerts(&mut config, &market("93"), 1_060).unwrap();
+        let prices: Vec<f64> = config.price_history["BTCEUR"]
+            .iter()
+            .map(|sample| sample.price)
+            .collect();
+        assert_eq!(prices, vec![90.0, 93.0]);
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let values = [5.0, 1.0, 3.0, 2.0, 4.0];
+        assert_eq!(percentile(values.iter().copied(), 50.0), Some(3.0));
+        assert_eq!(percentile(values.iter().copied(), 95.0), Some(4.8));
+        assert_eq!(percentile(values.iter().copied(), 0.0), Some(1.0));
+        assert_eq!(percentile(std::iter::empty(), 50.0), None);
+    }
+
+    #[test]
+    fn reminders_follow_the_interval_while_still_met() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.currencies[0].reminder_interval_h = Some(1);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        let mut events = |now: u64| -> Vec<AlertEvent> {
+            let fired = evaluate_alerts(&mut config, &market, now).unwrap();
+            fired.values().flatten().map(|a| a.event).collect()
+        };
+
+        assert_eq!(events(10_000), vec![AlertEvent::Triggered]);
+        assert_eq!(events(11_000), vec![]);
+        assert_eq!(events(13_600), vec![AlertEvent::Reminder]);
+        assert_eq!(events(15_000), vec![]);
+        assert_eq!(events(17_200), vec![AlertEvent::Reminder]);
+    }
+
+    #[test]
+    fn fetch_plan_requests_each_symbol_once_per_endpoint() {
+        let currencies = vec![
+            alert("BTCEUR", AlertCondition::Above, 1.0),
+            alert("ETHEUR", AlertCondition::DailyChangeAbove { pct: 5.0 }, 0.0),
+            alert(
+                "ETHEUR",
+                AlertCondition::DailyChangeBelow { pct: -5.0 },
+                0.0,
+            ),
+            alert("BTCUSDT", AlertCondition::FundingAbove { pct: 0.01 }, 0.0),
+            alert(
+                "BTCEUR",
+                AlertCondition::DataStale { max_age_secs: 60 },
+                0.0,
+            ),
+        ];
+        let plan = FetchPlan::new(&currencies);
+        assert_eq!(
+            plan,
+            FetchPlan {
+                daily_tickers: vec!["ETHEUR"],
+                depth: Vec::new(),
+                last_trades: vec!["BTCEUR"],
+                premium_index: vec!["BTCUSDT"],
+                klines: Vec::new(),
+                vwap: Vec::new(),
+                exchange_prices: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn suppressed_first_sample_waits_for_the_next_crossing() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.suppress_initial_crossings = true;
+        config.withold_notification_h = Some(0);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_000).unwrap();
+        assert!(fired.is_empty());
+        assert_eq!(config.currencies[0].initialized, Some(1_000));
+
+        // Still above and past the withhold window, but not a new crossing
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_060).unwrap();
+        assert!(fired
+            .values()
+            .flatten()
+            .all(|a| a.event != AlertEvent::Triggered));
+
+        evaluate_alerts(&mut config, &market("50"), 1_120).unwrap();
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_180).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+    }
+
+    #[test]
+    fn channel_retry_settings_fall_back_to_global() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "", "retries": 3 },
+            "check_interval": 60,
+            "withold_notification_h": null,
+            "currencies": [],
+            "retries": 0,
+            "timeout_secs": 5,
+            "webhook": { "url": "http://127.0.0.1/hook", "timeout_secs": 30 },
+        }))
+        .unwrap();
+        let webhook = config.webhook.as_ref().unwrap();
+        assert_eq!(
+            config.email.retry.or(config.retry),
+            RetryPolicy {
+                retries: Some(3),
+                retry_backoff_ms: None,
+                timeout_secs: Some(5),
+            }
+        );
+        assert_eq!(
+            webhook.retry.or(config.retry),
+            RetryPolicy {
+                retries: Some(0),
+                retry_backoff_ms: None,
+                timeout_secs: Some(30),
+            }
+        );
+    }
+
+    #[test]
+    fn snooze_durations_take_a_unit() {
+        assert_eq!(parse_duration("90s").unwrap(), 90);
+        assert_eq!(parse_duration("30m").unwrap(), 1_800);
+        assert_eq!(parse_duration("2h").unwrap(), 7_200);
+        assert_eq!(parse_duration("1d").unwrap(), 86_400);
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("2w").is_err());
+    }
+
+    #[test]
+    fn candle_close_basis_ignores_the_spot_price() {
+        let mut candle = alert("BTCEUR", AlertCondition::Above, 100.0);
+        candle.price_basis = PriceBasis::CandleClose {
+            interval: "5m".to_string(),
+        };
+        let mut config = test_config(vec![candle]);
+        assert_eq!(
+            FetchPlan::new(&config.currencies).klines,
+            vec![("BTCEUR", "5m")]
+        );
+        let market = |close: &str| MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            candle_closes: HashMap::from([(
+                ("BTCEUR".to_string(), "5m".to_string()),
+                close.to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("90"), 1_000).unwrap();
+        assert!(fired.is_empty());
+        let fired = evaluate_alerts(&mut config, &market("110"), 1_300).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+        assert_eq!(config.currencies[0].rule(), "BTCEUR Above 100 on 5m close");
+    }
+
+    #[test]
+    fn identical_alerts_are_deduplicated_across_restarts() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.dedup_window_secs = Some(3_600);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("150"), 1_000).unwrap();
+        let fired = dedup_alerts(&mut config, fired, 1_000);
+        assert_eq!(fired.len(), 1);
+        record_sent(&mut config, &fired[&None], 1_000);
+
+        // A restart loses `last_alerted` but keeps the fingerprints
+        let mut restarted: Config =
+            serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        restarted.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut restarted, &market("150.01"), 1_060).unwrap();
+        assert!(dedup_alerts(&mut restarted, fired, 1_060).is_empty());
+
+        restarted.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut restarted, &market("150"), 4_700).unwrap();
+        assert_eq!(dedup_alerts(&mut restarted, fired, 4_700).len(), 1);
+    }
+
+    #[test]
+    fn fingerprints_are_recorded_once_the_alert_was_sent() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.dedup_window_secs = Some(3_600);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        config
+            .sent_fingerprints
+            .insert("unrelated".to_string(), 1_000);
+
+        // The send failed, so nothing is recorded and the alert is retried
+        let notified: Vec<Notified> = config.currencies.iter().map(|c| c.notified()).collect();
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired = hold_back(&mut config, &notified, fired, 1_000);
+        assert_eq!(fired.values().flatten().count(), 1);
+        assert_eq!(config.sent_fingerprints.len(), 1);
+        config.currencies[0].restore(&notified[0]);
+
+        let fired = evaluate_alerts(&mut config, &market, 1_060).unwrap();
+        let fired = hold_back(&mut config, &notified, fired, 1_060);
+        record_sent(&mut config, &fired[&None], 1_060);
+        assert_eq!(config.sent_fingerprints.len(), 2);
+
+        // A duplicate is rolled back rather than marked as alerted
+        config.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut config, &market, 1_120).unwrap();
+        assert!(hold_back(&mut config, &notified, fired, 1_120).is_empty());
+        assert_eq!(config.currencies[0].last_alerted, None);
+    }
+
+    #[test]
+    fn fallback_price_source_is_named_in_the_alert() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            fallback_sources: HashMap::from([("BTCEUR".to_string(), PriceSource::Kraken)]),
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(alert.details, "Price source: Kraken\n");
+    }
+
+    #[test]
+    fn decimal_string_thresholds_equal_prices_with_the_same_digits() {
+        let mut config: Config = test_config(vec![serde_json::from_value(serde_json::json!({
+            "symbol": "SHIBEUR",
+            "threshold": "0.00012345",
+            "alert_condition": "Above",
+        }))
+        .unwrap()]);
+        validate_config(&config).unwrap();
+        assert_eq!(config.currencies[0].threshold(), Some(0.00012345));
+        assert_eq!(config.currencies[0].rule(), "SHIBEUR Above 0.00012345");
+        let market = |raw: &str| MarketData {
+            prices: vec![price("SHIBEUR", raw)],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("0.00012345"), 1_000).unwrap();
+        assert!(fired.is_empty());
+        assert_eq!(config.currencies[0].anchored_threshold, None);
+        let fired = evaluate_alerts(&mut config, &market("0.00012346"), 1_060).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+
+        assert_eq!(parse_decimal("-12"), Some(-12.0));
+        assert_eq!(parse_decimal("1e5"), None);
+        assert_eq!(parse_decimal("inf"), None);
+        assert_eq!(parse_decimal("."), None);
+    }
+
+    #[test]
+    fn alert_command_gets_the_alert_in_its_environment() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+
+        assert!(hook::command(&[], alert).is_none());
+        let argv = ["plug".to_string(), "on".to_string()];
+        let command = hook::command(&argv, alert).unwrap();
+        assert_eq!(command.get_program(), "plug");
+        let env: HashMap<_, _> = command
+            .get_envs()
+            .map(|(key, value)| (key.to_str().unwrap(), value.unwrap().to_str().unwrap()))
+            .collect();
+        assert_eq!(env["BYE_WATCH_SYMBOL"], "BTCEUR");
+        assert_eq!(env["BYE_WATCH_PRICE"], "150");
+        assert_eq!(env["BYE_WATCH_THRESHOLD"], "100");
+        assert_eq!(env["BYE_WATCH_TIMESTAMP"], "1000");
+    }
+
+    #[test]
+    fn stale_exchange_data_reports_its_age() {
+        let stale = || {
+            test_config(vec![alert(
+                "HALTEUR",
+                AlertCondition::DataStale { max_age_secs: 300 },
+                0.0,
+            )])
+        };
+        let market = |last_trade: Option<u64>| MarketData {
+            prices: vec![price("HALTEUR", "1.5")],
+            last_trades: HashMap::from([("HALTEUR".to_string(), last_trade)]),
+            ..Default::default()
+        };
+        let details = |fired: FiredByGroup| fired.into_values().flatten().next().unwrap().details;
+
+        let mut config = stale();
+        let fired = evaluate_alerts(&mut config, &market(Some(9_800)), 10_000).unwrap();
+        assert!(fired.is_empty());
+        let fired = evaluate_alerts(&mut config, &market(Some(9_600)), 10_000).unwrap();
+        assert!(details(fired).starts_with("Exchange data age: 400s"));
+
+        // Never traded at all is as stale as it gets
+        let fired = evaluate_alerts(&mut stale(), &market(None), 10_000).unwrap();
+        assert_eq!(details(fired), "Exchange data age: no trade on record\n");
+
+        // Without a fetched trade list, as under --price-override, it is skipped
+        let unfetched = MarketData {
+            prices: vec![price("HALTEUR", "1.5")],
+            ..Default::default()
+        };
+        assert!(evaluate_alerts(&mut stale(), &unfetched, 10_000)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn oversized_bodies_leave_out_whole_alerts() {
+        let mut config = test_config(
+            (0..6)
+                .map(|i| alert(&format!("COIN{}EUR", i), AlertCondition::Above, 1.0))
+                .collect(),
+        );
+        let market = MarketData {
+            prices: (0..6)
+                .map(|i| price(&format!("COIN{}EUR", i), "2"))
+                .collect(),
+            ..Default::default()
+        };
+        let alerts: Vec<FiredAlert> = evaluate_alerts(&mut config, &market, 1_000)
+            .unwrap()
+            .into_values()
+            .flatten()
+            .collect();
+        let notification = Notification::alerts("[bye-watch] Price Alert", alerts);
+
+        let full = notify::render_body(&config, &notification, None);
+        assert!(full.contains("COIN5EUR"));
+        let truncated = notify::render_body(&config, &notification, Some(200));
+        assert!(truncated.len() <= 200);
+        assert!(truncated.contains("COIN0EUR"));
+        assert!(!truncated.contains("COIN5EUR"));
+        assert!(truncated.trim_end().ends_with("more alerts truncated"));
+    }
+
+    #[test]
+    fn near_high_and_low_use_the_24h_extremes() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::NearHigh { pct: 1.0 }, 0.0),
+            alert("BTCEUR", AlertCondition::NearLow { pct: 1.0 }, 0.0),
+        ]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "99.5")],
+            daily_tickers: vec![BinanceTicker24h {
+                symbol: "BTCEUR".to_string(),
+                price_change_percent: "0".to_string(),
+                high_price: "100".to_string(),
+                low_price: "90".to_string(),
+                count: 0,
+                quote_volume: "0".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let fired: Vec<FiredAlert> = evaluate_alerts(&mut config, &market, 1_000)
+            .unwrap()
+            .into_values()
+            .flatten()
+            .collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].details, "24h high: 100 (0.50% away)\n");
+        assert!(config.currencies[0].condition_met);
+        assert!(!config.currencies[1].condition_met);
+    }
+
+    #[test]
+    fn muted_symbols_are_skipped_until_the_mute_expires() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.muted_symbols.insert("BTCEUR".to_string(), 2_000);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert!(fired.is_empty());
+        assert_eq!(config.currencies[0].last_price, None);
+
+        let fired = evaluate_alerts(&mut config, &market, 2_000).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+        assert!(config.muted_symbols.is_empty());
+    }
+
+    #[test]
+    fn test_source_fires_without_the_network() {
+        let mut config = test_config(vec![
+            alert(TEST_SYMBOL, AlertCondition::Above, 50.0),
+            alert("BTCEUR", AlertCondition::Above, 50.0),
+        ]);
+        enable_test_source(&mut config);
+        let market = MarketData {
+            prices: fetch_prices(&config).unwrap(),
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired: Vec<&FiredAlert> = fired.values().flatten().collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(
+            (fired[0].symbol.as_str(), fired[0].price),
+            (TEST_SYMBOL, 100.0)
+        );
+    }
+
+    #[test]
+    fn price_override_replaces_the_exchange_ticker() {
+        let path = temp_path("price-override.json");
+        fs::write(
+            &path,
+            r#"{"btceur": 71000.5, "SOLEUR": "90.10", "XRPEUR": 1}"#,
+        )
+        .unwrap();
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 70000.0),
+            alert("SOLEUR", AlertCondition::Below, 100.0),
+        ]);
+        config.price_override = Some(load_price_override(path.to_str().unwrap()).unwrap());
+
+        let mut prices = fetch_prices(&config).unwrap();
+        prices.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        let prices: Vec<(&str, &str)> = prices
+            .iter()
+            .map(|p| (p.symbol.as_str(), p.price.as_str()))
+            .collect();
+        assert_eq!(prices, vec![("BTCEUR", "71000.5"), ("SOLEUR", "90.10")]);
+
+        // Nothing is fetched, every other basis takes the overridden price
+        let mut mark = alert("BTCEUR", AlertCondition::Above, 70000.0);
+        mark.price_basis = PriceBasis::Mark;
+        let mut vwap = alert("SOLEUR", AlertCondition::Below, 100.0);
+        vwap.price_basis = PriceBasis::Vwap {
+            interval: "1h".to_string(),
+            periods: 24,
+        };
+        config.currencies = vec![
+            mark,
+            vwap,
+            alert("SOLEUR", AlertCondition::DailyChangeAbove { pct: 5.0 }, 0.0),
+            alert(
+                "XRPEUR",
+                AlertCondition::DepthImbalance {
+                    ratio: 2.0,
+                    side: DepthSide::Either,
+                },
+                0.0,
+            ),
+        ];
+        let market = fetch_market_data(&config).unwrap();
+        assert_eq!(market.premium_index[0].mark_price, "71000.5");
+        assert_eq!(
+            market.vwaps[&("SOLEUR".to_string(), "1h".to_string(), 24)],
+            "90.10"
+        );
+        assert!(market.daily_tickers.is_empty() && market.depth.is_empty());
+
+        fs::write(&path, r#"{"BTCEUR": "soon"}"#).unwrap();
+        assert!(load_price_override(path.to_str().unwrap()).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn price_at_the_threshold_only_meets_inclusive_alerts() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("BTCEUR", AlertCondition::Below, 100.0),
+        ]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "100.00")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert!(fired.is_empty());
+
+        for currency in &mut config.currencies {
+            currency.inclusive = true;
+        }
+        let fired = evaluate_alerts(&mut config, &market, 1_060).unwrap();
+        assert_eq!(fired.values().flatten().count(), 2);
+        assert!(config.currencies.iter().all(|c| c.condition_met));
+    }
+
+    #[test]
+    fn vwap_weighs_the_typical_price_by_volume() {
+        let candle = |high: &str, low: &str, close: &str, volume: &str| {
+            serde_json::json!([0, "0", high, low, close, volume, 0])
+                .as_array()
+                .unwrap()
+                .clone()
+        };
+        let candles = vec![candle("12", "9", "9", "1"), candle("22", "19", "19", "3")];
+        assert_eq!(vwap(&candles), Some(17.5));
+        assert_eq!(vwap(&[candle("12", "9", "9", "0")]), None);
+        assert_eq!(vwap(&[vec![serde_json::json!(0)]]), None);
+
+        let mut basis = alert("BTCEUR", AlertCondition::Below, 100.0);
+        basis.price_basis = PriceBasis::Vwap {
+            interval: "1h".to_string(),
+            periods: 24,
+        };
+        let mut config = test_config(vec![basis]);
+        config.missing_symbol_policy = MissingSymbolPolicy::Error;
+        assert_eq!(
+            FetchPlan::new(&config.currencies).vwap,
+            vec![("BTCEUR", "1h", 24)]
+        );
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "90")],
+            ..Default::default()
+        };
+        assert!(evaluate_alerts(&mut config, &market, 1_000)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            config.currencies[0].rule(),
+            "BTCEUR Below 100 on 24 x 1h VWAP"
+        );
+    }
+
+    #[test]
+    fn fatal_errors_keep_their_exit_code_when_boxed() {
+        let exit_code =
+            |e: Box<dyn std::error::Error>| e.downcast_ref::<Fatal>().map_or(1, Fatal::exit_code);
+        assert_eq!(exit_code(Fatal::config("bad").into()), 2);
+        assert_eq!(exit_code(Fatal::Network("down".into()).into()), 3);
+        assert_eq!(exit_code(Fatal::Notification("refused".into()).into()), 4);
+        assert_eq!(exit_code(Fatal::Failing("broken".into()).into()), 5);
+        assert_eq!(exit_code("anything else".into()), 1);
+    }
+
+    #[test]
+    fn trade_count_above_reads_the_24h_ticker() {
+        let mut config = test_config(vec![alert(
+            "PEPEEUR",
+            AlertCondition::TradeCountAbove { threshold: 50_000 },
+            0.0,
+        )]);
+        assert_eq!(
+            FetchPlan::new(&config.currencies).daily_tickers,
+            vec!["PEPEEUR"]
+        );
+        let market = |count: u64| MarketData {
+            prices: vec![price("PEPEEUR", "0.00001")],
+            daily_tickers: vec![BinanceTicker24h {
+                symbol: "PEPEEUR".to_string(),
+                price_change_percent: "0".to_string(),
+                high_price: "0.00001".to_string(),
+                low_price: "0.00001".to_string(),
+                count,
+                quote_volume: "0".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        assert!(evaluate_alerts(&mut config, &market(50_000), 1_000)
+            .unwrap()
+            .is_empty());
+        let fired = evaluate_alerts(&mut config, &market(80_000), 1_060).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(alert.details, "24h trades: 80000\n");
+    }
+
+    #[test]
+    fn prune_state_keeps_watched_and_referenced_symbols() {
+        let mut config = test_config(vec![alert(
+            "ETHBTC",
+            AlertCondition::RelativeAbove {
+                reference: "BTCEUR".to_string(),
+                factor: 0.05,
+            },
+            0.0,
+        )]);
+        for symbol in ["ETHBTC", "BTCEUR", "DOGEEUR"] {
+            config
+                .price_history
+                .insert(symbol.to_string(), VecDeque::new());
+        }
+        config
+            .symbol_last_notified
+            .insert("DOGEEUR".to_string(), 1_000);
+        config.muted_symbols.insert("ETHBTC".to_string(), 2_000);
+
+        assert_eq!(
+            prune_state(&mut config),
+            vec![
+                "last notification time of DOGEEUR",
+                "price history of DOGEEUR"
+            ]
+        );
+        let mut kept: Vec<&String> = config.price_history.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["BTCEUR", "ETHBTC"]);
+        assert!(config.symbol_last_notified.is_empty());
+        assert_eq!(config.muted_symbols.len(), 1);
+        assert!(prune_state(&mut config).is_empty());
+    }
+
+    #[test]
+    fn price_break_needs_the_minimum_volume() {
+        let mut config = test_config(vec![alert(
+            "BTCEUR",
+            AlertCondition::PriceBreakWithVolume {
+                side: BreakSide::Above,
+                price: 100.0,
+                min_volume: 1_000_000.0,
+            },
+            0.0,
+        )]);
+        assert_eq!(
+            FetchPlan::new(&config.currencies).daily_tickers,
+            vec!["BTCEUR"]
+        );
+        let market = |raw: &str, volume: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            daily_tickers: vec![BinanceTicker24h {
+                symbol: "BTCEUR".to_string(),
+                price_change_percent: "0".to_string(),
+                high_price: "110".to_string(),
+                low_price: "90".to_string(),
+                count: 0,
+                quote_volume: volume.to_string(),
+            }],
+            ..Default::default()
+        };
+
+        // Broken on thin volume, then enough volume below the level
+        assert!(evaluate_alerts(&mut config, &market("105", "20000"), 1_000)
+            .unwrap()
+            .is_empty());
+        assert!(
+            evaluate_alerts(&mut config, &market("95", "5000000"), 1_060)
+                .unwrap()
+                .is_empty()
+        );
+        let fired = evaluate_alerts(&mut config, &market("105", "5000000"), 1_120).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(
+            alert.details,
+            "Price: 105\n24h volume: 5000000 (minimum 1000000)\n"
+        );
+    }
+
+    #[test]
+    fn suppressions_are_counted_by_reason() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        for now in [1_000, 1_060, 1_120] {
+            evaluate_alerts(&mut config, &market, now).unwrap();
+        }
+        config.muted_symbols.insert("BTCEUR".to_string(), 5_000);
+        evaluate_alerts(&mut config, &market, 1_180).unwrap();
+
+        assert_eq!(
+            config.stats.suppressed_total,
+            BTreeMap::from([("muted", 1), ("withheld", 2)])
+        );
+        assert!(metrics::render(&config)
+            .contains("bye_watch_suppressed_total{reason=\"withheld\"} 2\n"));
+    }
+
+    #[test]
+    fn arb_spread_compares_the_two_exchanges() {
+        let mut config = test_config(vec![alert(
+            "BTCEUR",
+            AlertCondition::ArbSpread {
+                exchange_a: PriceSource::Binance,
+                exchange_b: PriceSource::Kraken,
+                pct: 0.5,
+            },
+            0.0,
+        )]);
+        assert_eq!(
+            FetchPlan::new(&config.currencies).exchange_prices,
+            vec![("BTCEUR", PriceSource::Kraken)]
+        );
+        let market = |kraken: &str| MarketData {
+            prices: vec![price("BTCEUR", "60000")],
+            exchange_prices: HashMap::from([(
+                ("BTCEUR".to_string(), PriceSource::Kraken),
+                kraken.to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        assert!(evaluate_alerts(&mut config, &market("60200"), 1_000)
+            .unwrap()
+            .is_empty());
+        let fired = evaluate_alerts(&mut config, &market("60600"), 1_060).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(
+            alert.details,
+            "Binance: 60000\nKraken: 60600\nSpread: 1.00%\n"
+        );
+    }
+
+    #[test]
+    fn restored_history_is_cut_to_what_the_alerts_need() {
+        let mut config = test_config(vec![alert(
+            "BTCEUR",
+            AlertCondition::BollingerBreak {
+                periods: 100,
+                k: 2.0,
+            },
+            0.0,
+        )]);
+        let samples = |count: u64, last: u64| -> VecDeque<PriceSample> {
+            (0..count)
+                .map(|i| PriceSample {
+                    price: 1.0,
+                    timestamp: last + 1 + i - count,
+                })
+                .collect()
+        };
+        config
+            .price_history
+            .insert("BTCEUR".to_string(), samples(150, 10_000));
+        config
+            .price_history
+            .insert("ETHEUR".to_string(), samples(80, 2_000));
+
+        let changes = restore_price_history(&mut config, 10_060);
+        assert_eq!(
+            changes,
+            vec![
+                "Trimmed the price history of BTCEUR from 150 to 100 samples",
+                "Trimmed the price history of ETHEUR from 80 to 60 samples",
+            ]
+        );
+        assert_eq!(
+            config.price_history["BTCEUR"].back().unwrap().timestamp,
+            10_000
+        );
+
+        config.warm_restart_max_age_secs = Some(3_600);
+        assert_eq!(
+            restore_price_history(&mut config, 10_060),
+            vec!["Dropped the price history of ETHEUR, last sample 8060s ago"]
+        );
+        assert_eq!(config.price_history["BTCEUR"].len(), 100);
+    }
+
+    #[test]
+    fn table_bodies_align_one_row_per_alert() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("A_VERY_LONG_SYMBOLEUR", AlertCondition::Below, 2.0),
+        ]);
+        config.body_format = BodyFormat::Table;
+        config.timestamp_format = Some("%Y".to_string());
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150"), price("A_VERY_LONG_SYMBOLEUR", "1")],
+            ..Default::default()
+        };
+        let alerts: Vec<FiredAlert> = evaluate_alerts(&mut config, &market, 1_000)
+            .unwrap()
+            .into_values()
+            .flatten()
+            .collect();
+        let notification = Notification::alerts("[bye-watch] Price Alert", alerts);
+
+        assert_eq!(
+            notify::render_body(&config, &notification, None),
+            "Found the following crypto alerts\n\n\
+             SYMBOL         | CONDITION | THRESHOLD | PRICE  | TIME\n\
+             -------------- | --------- | --------- | ------ | ----\n\
+             BTCEUR         | Above     | 100       | 150.00 | 1970\n\
+             A_VERY_LONG_S~ | Below     | 2         | 1.00   | 1970\n"
+        );
+        let truncated = notify::render_body(&config, &notification, Some(240));
+        assert!(truncated.len() <= 240);
+        assert!(truncated.contains("BTCEUR"));
+        assert!(truncated.ends_with("...1 more alerts truncated\n"));
+
+        // Escaping lengthens every row, the limit applies to the escaped text
+        let escaped = notification.render_table(
+            ParseMode::MarkdownV2,
+            NumberFormat::Plain,
+            false,
+            "%Y",
+            Some(300),
+        );
+        assert!(escaped.len() <= 300);
+        assert!(escaped.contains("BTCEUR"));
+        assert!(escaped.ends_with("\\.\\.\\.1 more alerts truncated\n"));
+    }
+
+    #[test]
+    fn tiers_hit_together_are_sent_as_one_alert() {
+        let mut tiered = alert("BTCEUR", AlertCondition::Above, 0.0);
+        tiered.tiers = [
+            (70_000.0, "sell 25%"),
+            (75_000.0, "sell 50%"),
+            (80_000.0, "rest"),
+        ]
+        .into_iter()
+        .map(|(threshold, label)| Tier {
+            threshold,
+            label: label.to_string(),
+            severity: None,
+            ..Default::default()
+        })
+        .collect();
+        tiered.tiers[2].severity = Some("high".to_string());
+        let mut config = test_config(vec![tiered]);
+        assert_eq!(
+            config.currencies[0].rule(),
+            "BTCEUR Above tiers 70000/75000/80000"
+        );
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market("76000"), 1_000).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(
+            alert.details,
+            "Tier hit: sell 25% at 70000\nTier hit: sell 50% at 75000\n"
+        );
+        assert_eq!(alert.threshold, Some(75_000.0));
+
+        // Hit tiers don't fire again, the next one does
+        let fired = evaluate_alerts(&mut config, &market("81000"), 1_060).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(alert.details, "Tier hit: rest at 80000 [high]\n");
+
+        // Falling back re-arms the tiers above the price
+        assert!(evaluate_alerts(&mut config, &market("72000"), 1_120)
+            .unwrap()
+            .is_empty());
+        let fired = evaluate_alerts(&mut config, &market("76000"), 1_180).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(alert.details, "Tier hit: sell 50% at 75000\n");
+    }
+
+    #[test]
+    fn twap_weighs_samples_by_how_long_they_stood() {
+        let history: VecDeque<PriceSample> = [(0, 100.0), (60, 110.0), (90, 130.0)]
+            .into_iter()
+            .map(|(timestamp, price)| PriceSample { price, timestamp })
+            .collect();
+        let latest = PriceSample {
+            price: 200.0,
+            timestamp: 120,
+        };
+        // 100 for 30s of the window, 110 for 30s and 130 for 30s
+        assert_eq!(
+            twap(&history, latest, 90),
+            Some((100.0 + 110.0 + 130.0) / 3.0)
+        );
+        assert_eq!(twap(&history, latest, 121), None);
+
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 105.0)]);
+        config.currencies[0].price_basis = PriceBasis::Twap { minutes: 2 };
+        config.price_history.insert("BTCEUR".to_string(), history);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "200")],
+            ..Default::default()
+        };
+        // 100 for 60s and 110 for 30s, 130 for 30s: (6000 + 3300 + 3900) / 120
+        let fired = evaluate_alerts(&mut config, &market, 120).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        assert_eq!(alert.price, 110.0);
+        assert_eq!(alert.rule, "BTCEUR Above 105 on 2m TWAP");
+    }
+
+    #[test]
+    fn adaptive_cooldown_scales_with_volatility() {
+        let cooldown = AdaptiveCooldown {
+            reference_pct: 1.0,
+            min_secs: 600,
+            max_secs: 7_200,
+            periods: 2,
+            unknown_fields: UnknownFields::new(),
+        };
+        let history = |a: f64, b: f64| -> VecDeque<PriceSample> {
+            [a, b]
+                .into_iter()
+                .map(|price| PriceSample {
+                    price,
+                    timestamp: 0,
+                })
+                .collect()
+        };
+        // Standard deviations of 1%, 2% and 0.1% of the mean
+        assert_eq!(
+            cooldown.withold_secs(3_600, Some(&history(99.0, 101.0))),
+            3_600
+        );
+        assert_eq!(
+            cooldown.withold_secs(3_600, Some(&history(98.0, 102.0))),
+            1_800
+        );
+        assert_eq!(
+            cooldown.withold_secs(3_600, Some(&history(99.9, 100.1))),
+            7_200
+        );
+        assert_eq!(
+            cooldown.withold_secs(3_600, Some(&history(50.0, 150.0))),
+            600
+        );
+        assert_eq!(cooldown.withold_secs(3_600, None), 3_600);
+    }
+
+    #[test]
+    fn binance_requests_fail_over_to_the_next_endpoint() {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let body = r#"[{"symbol":"BTCEUR","price":"60000"}]"#;
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 1.0)]);
+        config.binance_endpoints = vec![
+            "http://127.0.0.1:1".to_string(),
+            format!("http://127.0.0.1:{}", port),
+        ];
+        let prices = fetch_prices(&config).unwrap();
+        server.join().unwrap();
+        assert_eq!(prices[0].price, "60000");
+        // The next request starts at the second endpoint
+        assert_eq!(
+            config
+                .next_endpoint
+                .load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+    }
+
+    #[test]
+    fn undelivered_events_stay_queued_in_sequence() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("ETHEUR", AlertCondition::Above, 100.0),
+        ]);
+        config.event_webhook = Some(EventWebhookConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            hmac_secret: None,
+            retry: RetryPolicy {
+                retries: Some(0),
+                ..Default::default()
+            },
+            unknown_fields: UnknownFields::new(),
+        });
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150"), price("ETHEUR", "150")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        events::publish(&mut config, fired.values().flatten());
+        events::publish(&mut config, &[]);
+
+        assert_eq!(config.event_sequence, 2);
+        let sequences: Vec<&serde_json::Value> = config
+            .pending_events
+            .iter()
+            .map(|event| &event["sequence"])
+            .collect();
+        assert_eq!(sequences, [1, 2]);
+        assert_eq!(config.pending_events[0]["event"], "triggered");
+        assert_eq!(config.pending_events[0]["rule"], "BTCEUR Above 100");
+    }
+
+    #[test]
+    fn failed_state_writes_pause_and_retry() {
+        let mut config = test_config(vec![]);
+        config.state_retry_secs = Some(60);
+        let dir = temp_path("state-test");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.json");
+        let path = path.to_str().unwrap();
+
+        save_state(path, &mut config, 1_000);
+        assert_eq!(config.state_writes.failing_since, Some(1_000));
+        assert_eq!(config.state_writes.paused_until, 1_060);
+
+        fs::create_dir(&dir).unwrap();
+        save_state(path, &mut config, 1_030);
+        assert!(!std::path::Path::new(path).exists());
+        save_state(path, &mut config, 1_060);
+        assert!(std::path::Path::new(path).exists());
+        assert_eq!(config.state_writes.failing_since, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn history_records_only_moves_or_after_the_max_gap() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 1_000.0)]);
+        config.history_min_change_pct = Some(1.0);
+        config.history_max_gap_secs = Some(300);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+        for (now, raw) in [
+            (1_000, "100"),
+            (1_060, "100.5"),
+            (1_120, "99"),
+            (1_180, "99.5"),
+        ] {
+            evaluate_alerts(&mut config, &market(raw), now).unwrap();
+        }
+        evaluate_alerts(&mut config, &market("99.5"), 1_420).unwrap();
+        let recorded: Vec<(u64, f64)> = config.price_history["BTCEUR"]
+            .iter()
+            .map(|sample| (sample.timestamp, sample.price))
+            .collect();
+        assert_eq!(recorded, vec![(1_000, 100.0), (1_120, 99.0), (1_420, 99.5)]);
+    }
+
+    #[test]
+    fn startup_preview_judges_thresholds_against_the_live_price() {
+        let mut mark = alert("BTCUSDT", AlertCondition::Above, 100.0);
+        mark.price_basis = PriceBasis::Mark;
+        let mut candle = alert("BTCEUR", AlertCondition::Above, 100.0);
+        candle.price_basis = PriceBasis::CandleClose {
+            interval: "1h".to_string(),
+        };
+        let config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("ETHEUR", AlertCondition::Below, 50.0),
+            alert("BTCEUR", AlertCondition::Velocity { pct_per_min: 1.0 }, 0.0),
+            alert("XRPEUR", AlertCondition::Above, 1.0),
+            mark,
+            candle,
+        ]);
+        let market = MarketData {
+            prices: vec![
+                price("BTCEUR", "110"),
+                price("ETHEUR", "55"),
+                price("BTCUSDT", "110"),
+            ],
+            premium_index: vec![futures::PremiumIndex {
+                symbol: "BTCUSDT".to_string(),
+                mark_price: "90".to_string(),
+                index_price: "90".to_string(),
+                last_funding_rate: "0".to_string(),
+                next_funding_time: 0,
+            }],
+            candle_closes: HashMap::from([(
+                ("BTCEUR".to_string(), "1h".to_string()),
+                "95".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let rows = startup_preview(&config, &market, 1_000);
+        assert_eq!(rows.len(), 7);
+        assert!(
+            rows[1].ends_with("yes (+10.00% from threshold)"),
+            "{}",
+            rows[1]
+        );
+        assert!(
+            rows[2].ends_with("no (+10.00% from threshold)"),
+            "{}",
+            rows[2]
+        );
+        assert!(rows[3].ends_with("on first check"), "{}", rows[3]);
+        assert!(rows[4].ends_with("no price"), "{}", rows[4]);
+        assert!(
+            rows[5].ends_with("no (-10.00% from threshold)"),
+            "{}",
+            rows[5]
+        );
+        assert!(
+            rows[6].ends_with("no (-5.00% from threshold)"),
+            "{}",
+            rows[6]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_listed_by_path_and_kept_on_save() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "", "retries": 2 },
+            "check_interval": 60,
+            "witdhold_notification_h": 3600,
+            "currencies": [{
+                "symbol": "BTCEUR",
+                "threshold": 100.0,
+                "alert_condition": "Above",
+                "last_alerted": null,
+                "inclusve": true,
+                "tiers": [{ "threshold": 110.0, "label": "first", "severty": "high" }]
+            }],
+            "summary": { "at": "08:00", "chanel": "Ntfy" },
+            "mqtt": { "broker": "localhost:1883", "username": "ha", "pasword": "x" }
+        }))
+        .unwrap();
+        assert_eq!(
+            unknown_fields(&config),
+            vec![
+                "witdhold_notification_h",
+                "summary.chanel",
+                "mqtt.pasword",
+                "currencies[0].inclusve",
+                "currencies[0].tiers[0].severty",
+            ]
+        );
+        assert_eq!(config.email.retry.retries, Some(2));
+        config.currencies[0].unknown_fields.clear();
+        let saved = serde_json::to_value(&config).unwrap();
+        assert_eq!(saved["witdhold_notification_h"], 3600);
+        assert!(saved["currencies"][0].get("inclusve").is_none());
+    }
+
+    #[test]
+    fn sma_spread_fires_on_the_gap_between_both_averages() {
+        let condition = || AlertCondition::SmaSpread {
+            short: 2,
+            long: 4,
+            pct: 5.0,
+        };
+        let mut config = test_config(vec![
+            alert("BTCEUR", condition(), 0.0),
+            alert("ETHEUR", condition(), 0.0),
+        ]);
+        for (symbol, prices) in [
+            ("BTCEUR", [100.0, 100.0, 112.0, 112.0]),
+            ("ETHEUR", [100.0, 100.0, 110.0, 110.0]),
+        ] {
+            let history = prices
+                .iter()
+                .zip(1_000..)
+                .map(|(&price, timestamp)| PriceSample { price, timestamp })
+                .collect();
+            config.price_history.insert(symbol.to_string(), history);
+        }
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "112"), price("ETHEUR", "110")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 2_000).unwrap();
+        let fired: Vec<&FiredAlert> = fired.values().flatten().collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].symbol, "BTCEUR");
+        assert!(
+            fired[0]
+                .details
+                .contains("SMA 2: 112.00\nSMA 4: 106.00\nGap: +5.66%\n"),
+            "{}",
+            fired[0].details
+        );
+    }
+
+    #[test]
+    fn mark_price_basis_compares_the_futures_mark_price() {
+        let mut config = test_config(vec![alert("BTCUSDT", AlertCondition::Above, 100.0)]);
+        config.currencies[0].price_basis = PriceBasis::Mark;
+        let plan = FetchPlan::new(&config.currencies);
+        assert_eq!(plan.premium_index, vec!["BTCUSDT"]);
+        let market = MarketData {
+            prices: vec![price("BTCUSDT", "99")],
+            premium_index: vec![futures::PremiumIndex {
+                symbol: "BTCUSDT".to_string(),
+                mark_price: "101".to_string(),
+                index_price: "100.5".to_string(),
+                last_funding_rate: "0.0001".to_string(),
+                next_funding_time: 0,
+            }],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let alert = &fired[&None][0];
+        assert_eq!(alert.rule, "BTCUSDT Above 100 on mark price");
+        assert_eq!(alert.price, 101.0);
+        assert_eq!(alert.details, "Mark price: 101\n");
+    }
+
+    #[test]
+    fn index_deviation_compares_spot_to_the_index_price() {
+        let mut config = test_config(vec![
+            alert("BTCUSDT", AlertCondition::IndexDeviation { pct: 1.0 }, 0.0),
+            alert("ETHUSDT", AlertCondition::IndexDeviation { pct: 1.0 }, 0.0),
+        ]);
+        assert_eq!(
+            FetchPlan::new(&config.currencies).premium_index,
+            vec!["BTCUSDT", "ETHUSDT"]
+        );
+        let index = |symbol: &str, index_price: &str| futures::PremiumIndex {
+            symbol: symbol.to_string(),
+            mark_price: "0".to_string(),
+            index_price: index_price.to_string(),
+            last_funding_rate: "0".to_string(),
+            next_funding_time: 0,
+        };
+        let market = MarketData {
+            prices: vec![price("BTCUSDT", "98"), price("ETHUSDT", "99.5")],
+            premium_index: vec![index("BTCUSDT", "100"), index("ETHUSDT", "100")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired: Vec<&FiredAlert> = fired.values().flatten().collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].symbol, "BTCUSDT");
+        assert_eq!(
+            fired[0].details,
+            "Spot: 98\nIndex: 100\nDeviation: -2.00%\n"
+        );
+    }
+
+    #[test]
+    fn formula_fetches_and_evaluates_every_leg() {
+        let formula = |text: &str| AlertCondition::Formula {
+            formula: text.to_string(),
+        };
+        let mut config = test_config(vec![
+            alert("BTCUSDT", formula("BTCUSDT / BTCEUR > 1.04"), 0.0),
+            alert("BTCUSDT", formula("BTCUSDT - SOLUSDT > 0"), 0.0),
+        ]);
+        validate_config(&config).unwrap();
+        config.price_override = Some(HashMap::from([
+            ("BTCUSDT".to_string(), "41800".to_string()),
+            ("BTCEUR".to_string(), "40000".to_string()),
+            ("XRPEUR".to_string(), "1".to_string()),
+        ]));
+        let mut prices = fetch_prices(&config).unwrap();
+        prices.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        let symbols: Vec<&str> = prices.iter().map(|p| p.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["BTCEUR", "BTCUSDT"]);
+
+        let market = MarketData {
+            prices,
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fired: Vec<&FiredAlert> = fired.values().flatten().collect();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].rule, "BTCUSDT formula BTCUSDT / BTCEUR > 1.04");
+        assert_eq!(fired[0].details, "Formula value: 1.045\n");
+
+        config.currencies[0].alert_condition = formula("BTCUSDT >");
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn announcements_notify_once_and_not_the_backlog() {
+        let mut config = test_config(vec![]);
+        config.announcements = serde_json::from_value(serde_json::json!({
+            "keywords": ["will list"],
+        }))
+        .unwrap();
+        let feed = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| announcements::Announcement {
+                    id: id.to_string(),
+                    title: format!("Binance Will List {}", id),
+                    link: None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert!(announcements::new_matches(&mut config, feed(&["B", "A"])).is_empty());
+        let mut newer = feed(&["D", "C", "B"]);
+        newer[0].title = "Binance Will Delist D".to_string();
+        let titles: Vec<String> = announcements::new_matches(&mut config, newer)
+            .into_iter()
+            .map(|announcement| announcement.title)
+            .collect();
+        assert_eq!(titles, ["Binance Will List C"]);
+        // Not sent yet, so it is offered again
+        assert_eq!(
+            announcements::new_matches(&mut config, feed(&["D", "C"])).len(),
+            1
+        );
+        announcements::mark_seen(&mut config, "C");
+        assert!(announcements::new_matches(&mut config, feed(&["D", "C"])).is_empty());
+        assert_eq!(config.announcements_seen, ["A", "B", "D", "C"]);
+    }
+
+    #[test]
+    fn expired_alerts_are_skipped_and_marked_once() {
+        let mut config = test_config(vec![alert("BTCUSDT", AlertCondition::Above, 50_000.0)]);
+        config.currencies[0].expires_at = Some("2026-03-10T12:00:00Z".to_string());
+        let market = MarketData {
+            prices: vec![price("BTCUSDT", "60000")],
+            ..Default::default()
+        };
+        let noon = parse_expiry("2026-03-10T12:00:00Z").unwrap();
+
+        let fired = evaluate_alerts(&mut config, &market, noon - 1).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+        assert!(!config.currencies[0].expired);
+        config.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut config, &market, noon).unwrap();
+        assert_eq!(fired.values().flatten().count(), 0);
+        assert!(config.currencies[0].expired);
+
+        let day = Local.with_ymd_and_hms(2026, 3, 11, 0, 0, 0).unwrap();
+        assert_eq!(parse_expiry("2026-03-10"), Ok(day.timestamp() as u64));
+        assert_eq!(
+            parse_expiry("2026-03-10 23:30"),
+            Ok(day.timestamp() as u64 - 30 * 60)
+        );
+        assert!(parse_expiry("Friday").is_err());
+        config.currencies[0].expires_at = Some("10/03/2026".to_string());
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn only_alerts_added_since_the_last_run_are_announced() {
+        let mut config = test_config(vec![
+            alert("BTCUSDT", AlertCondition::Above, 70_000.0),
+            alert("ETHUSDT", AlertCondition::Below, 2_000.0),
+        ]);
+        assert!(arm_notice(&mut config).is_none());
+        assert!(!config.currencies[0].armed);
+
+        config.notify_on_arm = true;
+        config.currencies[1].initialized = Some(1_000);
+        let notice = arm_notice(&mut config).unwrap();
+        assert_eq!(notice.message.lines().count(), 3);
+        assert!(notice.message.contains("BTCUSDT"));
+        assert!(!notice.message.contains("ETHUSDT"));
+        // Until the notice was sent, the next start announces it again
+        assert!(!config.currencies[0].armed);
+        assert!(config.currencies[1].armed);
+        assert_eq!(arm_notice(&mut config).unwrap().message, notice.message);
+        mark_armed(&mut config);
+        assert!(config.currencies.iter().all(|currency| currency.armed));
+
+        config
+            .currencies
+            .push(alert("SOLUSDT", AlertCondition::Above, 200.0));
+        let notice = arm_notice(&mut config).unwrap();
+        mark_armed(&mut config);
+        assert!(
+            notice.message.ends_with("SOLUSDT Above 200"),
+            "{}",
+            notice.message
+        );
+        assert!(!notice.message.contains("BTCUSDT"));
+        assert!(arm_notice(&mut config).is_none());
+    }
+
+    #[test]
+    fn configs_load_with_their_secrets_and_variables() {
+        let secrets = temp_path("load-secrets.json");
+        fs::write(&secrets, r#"{"email_password": "hunter2"}"#).unwrap();
+        let path = temp_path("load-config.json");
+        let config = serde_json::json!({
+            "secrets_file": secrets,
+            "email": { "username": "test@example.com" },
+            "check_interval": 60,
+            "withold_notification_h": null,
+            "variables": { "target": 70000 },
+            "currencies": [
+                { "symbol": "BTCEUR", "threshold": "${target}", "alert_condition": "Above", "last_alerted": null }
+            ],
+        });
+        fs::write(&path, config.to_string()).unwrap();
+
+        let (config, _) = load_config(path.to_str().unwrap()).unwrap();
+        assert_eq!(config.email.password, "hunter2");
+        assert_eq!(config.currencies[0].threshold(), Some(70_000.0));
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&secrets).unwrap();
+    }
+
+    #[test]
+    fn missing_symbols_fail_the_check_after_the_others_fired() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 50_000.0),
+            alert("NOPEEUR", AlertCondition::Above, 1.0),
+        ]);
+        config.missing_symbol_policy = MissingSymbolPolicy::Error;
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "60000")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert_eq!(fired.values().flatten().count(), 1);
+        assert_eq!(config.currencies[0].last_alerted, Some(1_000));
+        assert_eq!(config.missing_symbol.as_deref(), Some("NOPEEUR"));
+
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "60000"), price("NOPEEUR", "2")],
+            ..Default::default()
+        };
+        evaluate_alerts(&mut config, &market, 2_000).unwrap();
+        assert_eq!(config.missing_symbol, None);
+    }
+
+    #[test]
+    fn tiers_go_through_the_alert_state_machine() {
+        let mut tiered = alert("BTCEUR", AlertCondition::Above, 0.0);
+        tiered.tiers = vec![Tier {
+            threshold: 70_000.0,
+            label: "sell".to_string(),
+            ..Default::default()
+        }];
+        tiered.confirm_cycles = Some(2);
+        tiered.notify_on_clear = Some(true);
+        let mut config = test_config(vec![tiered]);
+        let market = |raw: &str| MarketData {
+            prices: vec![price("BTCEUR", raw)],
+            ..Default::default()
+        };
+        let events = |config: &mut Config, raw: &str, now: u64| -> Vec<AlertEvent> {
+            evaluate_alerts(config, &market(raw), now)
+                .unwrap()
+                .values()
+                .flatten()
+                .map(|alert| alert.event)
+                .collect()
+        };
+
+        assert!(events(&mut config, "71000", 1_000).is_empty());
+        assert_eq!(config.currencies[0].last_alerted, None);
+        assert_eq!(
+            events(&mut config, "71000", 1_060),
+            vec![AlertEvent::Triggered]
+        );
+        assert_eq!(config.currencies[0].last_alerted, Some(1_060));
+
+        config.currencies[0].snoozed_until = Some(10_000);
+        assert_eq!(
+            events(&mut config, "69000", 1_120),
+            vec![AlertEvent::Cleared]
+        );
+        events(&mut config, "71000", 1_180);
+        assert!(events(&mut config, "71000", 1_240).is_empty());
+        assert_eq!(config.currencies[0].last_alerted, None);
+    }
+
+    #[test]
+    fn throttle_from_the_future_restarts_from_now() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.symbol_throttle_secs = Some(600);
+        config
+            .symbol_last_notified
+            .insert("BTCEUR".to_string(), 5_000);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert!(throttle_symbols(&mut config, fired, 1_000).is_empty());
+        assert_eq!(config.symbol_last_notified["BTCEUR"], 1_000);
+        config.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut config, &market, 1_600).unwrap();
+        assert_eq!(throttle_symbols(&mut config, fired, 1_600).len(), 1);
+    }
+
+    #[test]
+    fn fingerprints_from_the_future_expire_from_now() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.dedup_window_secs = Some(3_600);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let fingerprint = alert_fingerprint(&fired[&None][0]);
+        config.sent_fingerprints.insert(fingerprint.clone(), 9_000);
+
+        config.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert!(dedup_alerts(&mut config, fired, 1_000).is_empty());
+        assert_eq!(config.sent_fingerprints[&fingerprint], 1_000);
+        config.currencies[0].last_alerted = None;
+        let fired = evaluate_alerts(&mut config, &market, 4_600).unwrap();
+        assert_eq!(dedup_alerts(&mut config, fired, 4_600).len(), 1);
+    }
+
+    #[test]
+    fn price_history_from_the_future_is_recorded_again() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.price_history.insert(
+            "BTCEUR".to_string(),
+            VecDeque::from([PriceSample {
+                price: 90.0,
+                timestamp: 5_000,
+            }]),
+        );
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "150")],
+            ..Default::default()
+        };
+
+        evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        assert_eq!(config.price_history["BTCEUR"][0].timestamp, 1_000);
+        evaluate_alerts(&mut config, &market, 1_060).unwrap();
+        let history = &config.price_history["BTCEUR"];
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].timestamp, 1_060);
+    }
+
+    #[test]
+    fn profiles_notify_through_their_own_channels() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.ntfy = Some(serde_json::from_value(serde_json::json!({"topic": "crypto"})).unwrap());
+        config.profiles = serde_json::from_value(serde_json::json!([
+            {"name": "fx", "currencies": [], "telegram": {"bot_token": "t", "chat_id": "1"}},
+            {"name": "stocks", "currencies": [], "ntfy": {"topic": "stocks"}},
+        ]))
+        .unwrap();
+
+        config.with_profile(0, |config| {
+            assert!(config.currencies.is_empty());
+            assert!(config.telegram.is_some());
+            assert_eq!(config.ntfy.as_ref().unwrap().topic, "crypto");
+        });
+        config.with_profile(1, |config| {
+            assert!(config.telegram.is_none());
+            assert_eq!(config.ntfy.as_ref().unwrap().topic, "stocks");
+        });
+        assert_eq!(config.currencies.len(), 1);
+        assert!(config.telegram.is_none());
+        assert!(config.profiles[0].telegram.is_some());
+        assert_eq!(config.ntfy.as_ref().unwrap().topic, "crypto");
+        assert_eq!(config.profiles[1].ntfy.as_ref().unwrap().topic, "stocks");
+        validate_config(&config).unwrap();
+
+        config.profiles[1].check_interval = Some(0);
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn above_and_below_alerts_need_a_threshold() {
+        let currency: CurrencyAlert = serde_json::from_value(serde_json::json!({
+            "symbol": "BTCEUR",
+            "alert_condition": "Below",
+        }))
+        .unwrap();
+        assert!(validate_config(&test_config(vec![currency])).is_err());
+
+        let tiered: CurrencyAlert = serde_json::from_value(serde_json::json!({
+            "symbol": "BTCEUR",
+            "alert_condition": "Above",
+            "tiers": [{ "label": "first", "threshold": 100.0 }],
+        }))
+        .unwrap();
+        let velocity = alert("BTCEUR", AlertCondition::Velocity { pct_per_min: 1.0 }, 0.0);
+        assert!(validate_config(&test_config(vec![tiered, velocity])).is_ok());
+    }
+
+    #[test]
+    fn mqtt_topics_tell_alerts_with_the_same_rule_apart() {
+        let mut config = test_config(vec![
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+            alert("BTCEUR", AlertCondition::Above, 100.0),
+        ]);
+        let mut mqtt: MqttConfig =
+            serde_json::from_value(serde_json::json!({ "broker": "localhost:1883" })).unwrap();
+        assert_eq!(mqtt.topic_prefix, "bye-watch");
+        let topics: Vec<String> = mqtt::messages(&config, &mqtt)
+            .into_iter()
+            .map(|(topic, _)| topic)
+            .filter(|topic| topic.ends_with("/state"))
+            .collect();
+        assert_eq!(topics.len(), 2);
+        assert_ne!(topics[0], topics[1]);
+
+        mqtt.password = Some("secret".to_string());
+        config.mqtt = Some(mqtt);
+        assert!(validate_config(&config).is_err());
+        config.mqtt.as_mut().unwrap().username = Some("ha".to_string());
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn thresholds_are_rendered_in_the_number_format() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 65_000.5)]);
+        let market = MarketData {
+            prices: vec![price("BTCEUR", "70000")],
+            ..Default::default()
+        };
+        let fired = evaluate_alerts(&mut config, &market, 1_000).unwrap();
+        let alert = fired.values().flatten().next().unwrap();
+        let rendered = notify::render_alert(
+            "{threshold} / {price}",
+            ParseMode::Plain,
+            alert,
+            NumberFormat::European,
+            false,
+            "%H:%M",
+        );
+        assert_eq!(rendered, "65.000,5 / 70.000,00");
+    }
+
+    #[test]
+    fn init_writes_a_sample_without_the_hostname() {
+        let path = temp_path("init.json").to_string_lossy().into_owned();
+        let _ = fs::remove_file(&path);
+        init_config(&path).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["instance_name"], "my-server");
+        assert!(init_config(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn alerts_fire_even_if_the_recovery_notice_fails() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Fails the recovery notice, takes the alert
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in ["500 Internal Server Error", "200 OK"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut line, mut length) = (String::new(), 0);
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.webhook = Some(
+            serde_json::from_value(serde_json::json!({
+                "url": format!("http://127.0.0.1:{}", port),
+                "retries": 0,
+            }))
+            .unwrap(),
+        );
+        config.channel_mode = ChannelMode::FirstSuccess;
+        config.channel_order = vec![Channel::Webhook];
+        config.price_override = Some(HashMap::from([("BTCEUR".to_string(), "150".to_string())]));
+        config.outage = Some(Outage {
+            since: 0,
+            failures: DEFAULT_RECOVERY_NOTICE_AFTER,
+        });
+
+        check_currencies(&mut config).unwrap();
+        let bodies = server.join().unwrap();
+        assert!(bodies[0].contains("Connectivity restored"), "{}", bodies[0]);
+        assert!(bodies[1].contains("BTCEUR"), "{}", bodies[1]);
+        assert!(config.currencies[0].last_alerted.is_some());
+        assert!(config.outage.is_none());
+    }
+
+    #[test]
+    fn undelivered_alerts_publish_no_events() {
+        let mut config = test_config(vec![alert("BTCEUR", AlertCondition::Above, 100.0)]);
+        config.webhook = Some(
+            serde_json::from_value(serde_json::json!({
+                "url": "http://127.0.0.1:1",
+                "retries": 0,
+            }))
+            .unwrap(),
+        );
+        config.channel_mode = ChannelMode::FirstSuccess;
+        config.channel_order = vec![Channel::Webhook];
+        config.event_webhook = Some(EventWebhookConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            hmac_secret: None,
+            retry: RetryPolicy {
+                retries: Some(0),
+                ..Default::default()
+            },
+            unknown_fields: UnknownFields::new(),
+        });
+        config.price_override = Some(HashMap::from([("BTCEUR".to_string(), "150".to_string())]));
+
+        // The alert fires again on each check, but never reaches the audit log
+        assert!(check_currencies(&mut config).is_err());
+        assert!(check_currencies(&mut config).is_err());
+        assert_eq!(config.event_sequence, 0);
+        assert!(config.pending_events.is_empty());
+        assert_eq!(config.currencies[0].last_alerted, None);
+    }
+}
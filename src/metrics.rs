@@ -0,0 +1,101 @@
+//! Prometheus text-format metrics, pushed to a Pushgateway after each cycle.
+
+use crate::Config;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Counters kept since startup.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub checks_total: u64,
+    pub check_errors_total: u64,
+    pub alerts_fired_total: u64,
+    /// Alert checks and notifications held back, by reason.
+    pub suppressed_total: BTreeMap<&'static str, u64>,
+}
+
+impl Stats {
+    pub fn suppressed(&mut self, reason: &'static str) {
+        *self.suppressed_total.entry(reason).or_default() += 1;
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the current prices, alert states and counters.
+pub fn render(config: &Config) -> String {
+    let mut out = String::new();
+    let prices: BTreeMap<&str, f64> = config
+        .all_currencies()
+        .filter_map(|c| Some((c.symbol.as_str(), c.last_price?)))
+        .collect();
+
+    out.push_str("# TYPE bye_watch_price gauge\n");
+    for (symbol, price) in prices {
+        let _ = writeln!(
+            out,
+            "bye_watch_price{{symbol=\"{}\"}} {}",
+            escape(symbol),
+            price
+        );
+    }
+    out.push_str("# TYPE bye_watch_condition_met gauge\n");
+    for currency in config.all_currencies() {
+        let _ = writeln!(
+            out,
+            "bye_watch_condition_met{{symbol=\"{}\",rule=\"{}\"}} {}",
+            escape(&currency.symbol),
+            escape(&currency.rule()),
+            u8::from(currency.condition_met)
+        );
+    }
+    let counters = [
+        ("bye_watch_checks_total", config.stats.checks_total),
+        (
+            "bye_watch_check_errors_total",
+            config.stats.check_errors_total,
+        ),
+        (
+            "bye_watch_alerts_fired_total",
+            config.stats.alerts_fired_total,
+        ),
+    ];
+    for (name, value) in counters {
+        let _ = writeln!(out, "# TYPE {} counter\n{} {}", name, name, value);
+    }
+    out.push_str("# TYPE bye_watch_suppressed_total counter\n");
+    for (reason, value) in &config.stats.suppressed_total {
+        let _ = writeln!(
+            out,
+            "bye_watch_suppressed_total{{reason=\"{}\"}} {}",
+            reason, value
+        );
+    }
+    out
+}
+
+/// Replaces the metrics of `job` on the Pushgateway at `url`.
+pub fn push(
+    client: reqwest::blocking::ClientBuilder,
+    url: &str,
+    job: &str,
+    body: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = client.timeout(Duration::from_secs(10)).build()?;
+    let url = format!("{}/metrics/job/{}", url.trim_end_matches('/'), job);
+    let response = client
+        .put(url)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(body)
+        .send()?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()).into());
+    }
+    Ok(())
+}
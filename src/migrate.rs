@@ -0,0 +1,93 @@
+//! Upgrades config.json files written by older versions before they are
+//! parsed. Each step moves the file up one `config_version`.
+
+use serde_json::Value;
+
+pub const CONFIG_VERSION: u64 = 1;
+
+/// Migrates `config` in place to `CONFIG_VERSION`, returning a description
+/// of every change made.
+pub fn migrate(config: &mut Value) -> Result<Vec<String>, String> {
+    let version = match config.get("config_version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("config_version {} is not a number", version))?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "config_version {} is newer than this binary supports ({}), please upgrade",
+            version, CONFIG_VERSION
+        ));
+    }
+
+    let mut changes = Vec::new();
+    if version < 1 {
+        normalize_condition_names(config, &mut changes);
+    }
+    if version < CONFIG_VERSION {
+        config["config_version"] = CONFIG_VERSION.into();
+        changes.push(format!("config_version {} -> {}", version, CONFIG_VERSION));
+    }
+    Ok(changes)
+}
+
+/// Calls `f` for every alert, top-level and in profiles.
+fn for_each_alert(config: &mut Value, mut f: impl FnMut(&mut Value)) {
+    fn each(list: Option<&mut Value>, f: &mut impl FnMut(&mut Value)) {
+        if let Some(list) = list.and_then(Value::as_array_mut) {
+            list.iter_mut().for_each(f);
+        }
+    }
+    each(config.get_mut("currencies"), &mut f);
+    if let Some(profiles) = config.get_mut("profiles").and_then(Value::as_array_mut) {
+        for profile in profiles {
+            each(profile.get_mut("currencies"), &mut f);
+        }
+    }
+}
+
+/// v1: condition names are case sensitive, so earlier spellings such as
+/// "above" or {"daily_change_above": ...} become "Above" and
+/// {"DailyChangeAbove": ...}.
+fn normalize_condition_names(config: &mut Value, changes: &mut Vec<String>) {
+    for_each_alert(config, |alert| {
+        let symbol = alert["symbol"].as_str().unwrap_or("?").to_string();
+        let renamed = match alert.get_mut("alert_condition") {
+            Some(Value::String(name)) => rename(name),
+            Some(Value::Object(object)) if object.len() == 1 => {
+                let (mut name, params) = std::mem::take(object).into_iter().next().unwrap();
+                let renamed = rename(&mut name);
+                object.insert(name, params);
+                renamed
+            }
+            _ => None,
+        };
+        if let Some((old, new)) = renamed {
+            changes.push(format!(
+                "{}: alert_condition \"{}\" -> \"{}\"",
+                symbol, old, new
+            ));
+        }
+    });
+}
+
+/// "daily_change_above" -> "DailyChangeAbove", returning the old and new
+/// names when anything changed.
+fn rename(name: &mut String) -> Option<(String, String)> {
+    let camel: String = name
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    if camel == *name {
+        return None;
+    }
+    let old = std::mem::replace(name, camel);
+    Some((old, name.clone()))
+}
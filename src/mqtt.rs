@@ -0,0 +1,205 @@
+//! Prices and alert states published to an MQTT broker after every check,
+//! with Home Assistant discovery messages so the entities create themselves.
+//! Speaks just enough MQTT 3.1.1 to publish retained QoS 0 messages.
+
+use crate::{Config, MqttConfig};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_SECS: u16 = 60;
+
+/// A retained message, topic and payload.
+pub type Message = (String, String);
+
+/// Discovery and state messages for every symbol's last price and every
+/// alert's condition. Alerts sharing a rule are told apart by a "_2", "_3"
+/// ... suffix in the order they are configured.
+pub fn messages(config: &Config, mqtt: &MqttConfig) -> Vec<Message> {
+    let prefix = mqtt.topic_prefix.trim_end_matches('/');
+    let discovery = mqtt.discovery_prefix.trim_end_matches('/');
+    let mut messages = Vec::new();
+    let mut symbols: Vec<&str> = Vec::new();
+    for currency in config.all_currencies() {
+        let Some(price) = currency.last_price else {
+            continue;
+        };
+        if symbols.contains(&currency.symbol.as_str()) {
+            continue;
+        }
+        symbols.push(&currency.symbol);
+        let id = format!("bye_watch_{}_price", slug(&currency.symbol));
+        let state_topic = format!("{}/{}/price", prefix, currency.symbol);
+        let mut entity = serde_json::json!({
+            "name": format!("{} price", currency.name()),
+            "unique_id": id,
+            "state_topic": state_topic,
+        });
+        if let Some(quote) = &currency.quote_asset {
+            entity["unit_of_measurement"] = quote.clone().into();
+        }
+        messages.push((
+            format!("{}/sensor/{}/config", discovery, id),
+            entity.to_string(),
+        ));
+        messages.push((state_topic, price.to_string()));
+    }
+    let mut rules: HashMap<String, usize> = HashMap::new();
+    for currency in config.all_currencies() {
+        let mut rule = currency.rule();
+        let mut key = slug(&rule);
+        let count = rules.entry(key.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            rule = format!("{} ({})", rule, count);
+            key = format!("{}_{}", key, count);
+        }
+        let id = format!("bye_watch_{}", key);
+        let state_topic = format!("{}/alert/{}/state", prefix, key);
+        let entity = serde_json::json!({
+            "name": rule,
+            "unique_id": id,
+            "state_topic": state_topic,
+        });
+        messages.push((
+            format!("{}/binary_sensor/{}/config", discovery, id),
+            entity.to_string(),
+        ));
+        let state = if currency.condition_met { "ON" } else { "OFF" };
+        messages.push((state_topic, state.to_string()));
+    }
+    messages
+}
+
+/// Lowercase letters and digits of `text`, anything else as "_".
+fn slug(text: &str) -> String {
+    text.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect()
+}
+
+/// Connects to the broker, publishes `messages` retained and disconnects.
+pub fn publish(mqtt: &MqttConfig, messages: &[Message]) -> Result<(), Box<dyn std::error::Error>> {
+    let address = mqtt
+        .broker
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("{} did not resolve", mqtt.broker))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    stream.write_all(&connect_packet(mqtt))?;
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack)?;
+    match connack {
+        [0x20, 2, _, 0] => {}
+        [0x20, 2, _, code] => {
+            return Err(format!("broker refused the connection ({})", code).into())
+        }
+        _ => return Err("broker did not acknowledge the connection".into()),
+    }
+    for (topic, payload) in messages {
+        let mut body = string(topic);
+        body.extend_from_slice(payload.as_bytes());
+        // PUBLISH, QoS 0, retained
+        stream.write_all(&packet(0x31, &body))?;
+    }
+    stream.write_all(&packet(0xE0, &[]))?;
+    Ok(())
+}
+
+fn connect_packet(mqtt: &MqttConfig) -> Vec<u8> {
+    let mut flags = 0x02; // clean session
+    if mqtt.username.is_some() {
+        flags |= 0x80;
+    }
+    if mqtt.password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = string("MQTT");
+    body.extend_from_slice(&[4, flags]);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    body.extend(string(&format!("bye-watch-{}", std::process::id())));
+    for field in [&mqtt.username, &mqtt.password].into_iter().flatten() {
+        body.extend(string(field));
+    }
+    packet(0x10, &body)
+}
+
+/// A length prefixed UTF-8 string.
+fn string(text: &str) -> Vec<u8> {
+    let mut bytes = (text.len() as u16).to_be_bytes().to_vec();
+    bytes.extend_from_slice(text.as_bytes());
+    bytes
+}
+
+/// A packet of `kind` with its remaining length, seven bits per byte.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn messages_are_published_retained_after_connecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broker = listener.local_addr().unwrap().to_string();
+        let received = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0; 2];
+            stream.read_exact(&mut header).unwrap();
+            let mut connect = vec![0; header[1] as usize];
+            stream.read_exact(&mut connect).unwrap();
+            stream.write_all(&[0x20, 2, 0, 0]).unwrap();
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).unwrap();
+            (header[0], connect, rest)
+        });
+
+        let mqtt: MqttConfig = serde_json::from_value(serde_json::json!({
+            "broker": broker,
+            "username": "ha",
+            "password": "secret",
+        }))
+        .unwrap();
+        let messages = vec![("bye-watch/BTCEUR/price".to_string(), "71000.5".to_string())];
+        publish(&mqtt, &messages).unwrap();
+
+        let (kind, connect, rest) = received.join().unwrap();
+        assert_eq!(kind, 0x10);
+        assert_eq!(&connect[..8], b"\x00\x04MQTT\x04\xC2");
+        assert!(connect.ends_with(b"\x00\x02ha\x00\x06secret"));
+        let mut publish = packet(0x31, &string("bye-watch/BTCEUR/price"));
+        publish[1] += 7;
+        publish.extend_from_slice(b"71000.5");
+        publish.extend_from_slice(&[0xE0, 0]);
+        assert_eq!(rest, publish);
+    }
+
+    #[test]
+    fn remaining_lengths_use_seven_bits_per_byte() {
+        assert_eq!(&packet(0x30, &[0; 127])[..2], &[0x30, 0x7F]);
+        assert_eq!(&packet(0x30, &[0; 321])[..3], &[0x30, 0xC1, 0x02]);
+    }
+}
@@ -0,0 +1,1475 @@
+use crate::{
+    chart, BodyFormat, Channel, ChannelMode, Config, EmailConfig, NtfyConfig, NumberFormat,
+    ParseMode, RetryPolicy, TelegramConfig, WebhookConfig, WebhookPayload,
+};
+use chrono::{Local, TimeZone};
+use lettre::message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::{authentication::Credentials, PoolConfig};
+use lettre::{Message, SmtpTransport, Transport};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default per-alert email template, matching the historical hardcoded body.
+pub const DEFAULT_EMAIL_TEMPLATE: &str =
+    "\n{rule}\nCurrent price: {price}\n{details}Time: {time}\n{ack}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEvent {
+    Triggered,
+    /// A previously alerted condition is still met, see `reminder_interval_h`.
+    Reminder,
+    /// A previously alerted condition is no longer met.
+    Cleared,
+}
+
+impl AlertEvent {
+    /// "warning" for triggered alerts, "info" for reminders and cleared ones.
+    pub fn severity(self) -> &'static str {
+        match self {
+            AlertEvent::Triggered => "warning",
+            AlertEvent::Reminder | AlertEvent::Cleared => "info",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertEvent::Triggered => "triggered",
+            AlertEvent::Reminder => "reminder",
+            AlertEvent::Cleared => "cleared",
+        }
+    }
+}
+
+/// An alert that fired (or cleared) during a check, ready to be rendered by
+/// any channel.
+#[derive(Debug, Clone)]
+pub struct FiredAlert {
+    pub symbol: String,
+    /// Display name of the symbol, the symbol itself if it has no alias.
+    pub name: String,
+    pub rule: String,
+    pub condition: String,
+    pub threshold: Option<f64>,
+    pub price: f64,
+    /// Extra condition specific lines, each terminated by a newline.
+    pub details: String,
+    pub timestamp: u64,
+    /// Link snoozing this alert, when the HTTP endpoint is enabled.
+    pub ack_url: Option<String>,
+    /// Price precision of the symbol's tick size, if known.
+    pub decimals: Option<usize>,
+    pub event: AlertEvent,
+    /// Position of the alert in its watch list, `None` for simulated ones.
+    pub index: Option<usize>,
+}
+
+/// Something to deliver through the notification channels: either a digest
+/// of fired alerts, or a plain message when `alerts` is empty.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub subject: String,
+    pub message: String,
+    pub alerts: Vec<FiredAlert>,
+    /// About bye-watch itself, such as failing checks or a crash, so it is
+    /// sent even while notifications are snoozed.
+    pub operational: bool,
+}
+
+impl Notification {
+    pub fn text(subject: impl Into<String>, message: impl Into<String>) -> Self {
+        Notification {
+            subject: subject.into(),
+            message: message.into(),
+            alerts: Vec::new(),
+            operational: false,
+        }
+    }
+
+    pub fn operational(subject: impl Into<String>, message: impl Into<String>) -> Self {
+        Notification {
+            operational: true,
+            ..Notification::text(subject, message)
+        }
+    }
+
+    pub fn alerts(subject: impl Into<String>, alerts: Vec<FiredAlert>) -> Self {
+        Notification {
+            subject: subject.into(),
+            message: "Found the following crypto alerts".to_string(),
+            alerts,
+            operational: false,
+        }
+    }
+
+    pub fn reminders(subject: impl Into<String>, alerts: Vec<FiredAlert>) -> Self {
+        Notification {
+            subject: subject.into(),
+            message: "[REMINDER] The following alerts are still active".to_string(),
+            alerts,
+            operational: false,
+        }
+    }
+
+    pub fn cleared(subject: impl Into<String>, alerts: Vec<FiredAlert>) -> Self {
+        Notification {
+            subject: subject.into(),
+            message: "[CLEARED] The following alerts are no longer active".to_string(),
+            alerts,
+            operational: false,
+        }
+    }
+
+    /// Renders the body, formatting each alert with `template`. Alerts that
+    /// would take the body past `max_len` bytes are left out and counted in a
+    /// closing line instead.
+    pub fn render(
+        &self,
+        template: &str,
+        mode: ParseMode,
+        number_format: NumberFormat,
+        compact: bool,
+        timestamp_format: &str,
+        max_len: Option<usize>,
+    ) -> String {
+        let message = escape(mode, &self.message);
+        if self.alerts.is_empty() {
+            return message;
+        }
+        let truncated =
+            |left: usize| escape(mode, &format!("\n...{} more alerts truncated\n", left));
+        let mut body = format!("{}\n\n ", message);
+        for (index, alert) in self.alerts.iter().enumerate() {
+            let rendered = render_alert(
+                template,
+                mode,
+                alert,
+                number_format,
+                compact,
+                timestamp_format,
+            );
+            let after = self.alerts.len() - index - 1;
+            let needed =
+                body.len() + rendered.len() + if after > 0 { truncated(after).len() } else { 0 };
+            if max_len.is_some_and(|max| needed > max) {
+                let left = self.alerts.len() - index;
+                eprintln!(
+                    "\"{}\" exceeds {} bytes, leaving out {} of {} alerts",
+                    self.subject,
+                    max_len.unwrap_or_default(),
+                    left,
+                    self.alerts.len()
+                );
+                body.push_str(&truncated(left));
+                break;
+            }
+            body.push_str(&rendered);
+        }
+        body
+    }
+}
+
+/// Widest each table column gets before its cells are cut, so rows stay
+/// readable on narrow channels.
+const TABLE_COLUMNS: [(&str, usize); 5] = [
+    ("SYMBOL", 14),
+    ("CONDITION", 24),
+    ("THRESHOLD", 12),
+    ("PRICE", 14),
+    ("TIME", 20),
+];
+
+impl Notification {
+    /// The alerts as an aligned plain text table below the message, escaped
+    /// for `mode`. Like `render`, rows past `max_len` bytes of escaped text
+    /// are counted in a closing line.
+    pub fn render_table(
+        &self,
+        mode: ParseMode,
+        number_format: NumberFormat,
+        compact: bool,
+        timestamp_format: &str,
+        max_len: Option<usize>,
+    ) -> String {
+        let rows: Vec<[String; 5]> = self
+            .alerts
+            .iter()
+            .map(|alert| {
+                let price = Some(alert.price)
+                    .filter(|_| compact)
+                    .and_then(|price| number_format.compact(price))
+                    .unwrap_or_else(|| {
+                        number_format.apply(&format!(
+                            "{:.*}",
+                            alert.decimals.unwrap_or(2),
+                            alert.price
+                        ))
+                    });
+                [
+                    alert.name.clone(),
+                    alert.condition.clone(),
+                    alert
+                        .threshold
+                        .map_or_else(|| "-".to_string(), |t| number_format.apply(&t.to_string())),
+                    price,
+                    format_time(alert.timestamp, timestamp_format),
+                ]
+            })
+            .collect();
+        let mut widths = TABLE_COLUMNS.map(|(header, _)| header.chars().count());
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count()).min(TABLE_COLUMNS[i].1);
+            }
+        }
+        let line = |cells: [&str; 5]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| {
+                    let cell = if cell.chars().count() > width {
+                        let cut: String = cell.chars().take(width - 1).collect();
+                        format!("{}~", cut)
+                    } else {
+                        cell.to_string()
+                    };
+                    format!("{:<width$}", cell, width = width)
+                })
+                .collect();
+            escape(mode, &format!("{}\n", cells.join(" | ").trim_end()))
+        };
+
+        let mut table = escape(mode, &format!("{}\n\n", self.message));
+        table.push_str(&line(TABLE_COLUMNS.map(|(header, _)| header)));
+        let rule = widths.map(|width| "-".repeat(width));
+        table.push_str(&line(rule.each_ref().map(String::as_str)));
+        let truncated = |left: usize| escape(mode, &format!("...{} more alerts truncated\n", left));
+        for (index, row) in rows.iter().enumerate() {
+            let rendered = line(row.each_ref().map(String::as_str));
+            let after = rows.len() - index - 1;
+            let needed =
+                table.len() + rendered.len() + if after > 0 { truncated(after).len() } else { 0 };
+            if max_len.is_some_and(|max| needed > max) {
+                eprintln!(
+                    "\"{}\" exceeds {} bytes, leaving out {} of {} alerts",
+                    self.subject,
+                    max_len.unwrap_or_default(),
+                    after + 1,
+                    rows.len()
+                );
+                table.push_str(&truncated(after + 1));
+                break;
+            }
+            table.push_str(&rendered);
+        }
+        table
+    }
+}
+
+/// Local wall-clock rendering of a unix timestamp with a strftime `format`.
+pub fn format_time(timestamp: u64, format: &str) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|t| t.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Fills in the placeholders `{symbol}`, `{rule}`, `{condition}`,
+/// `{threshold}`, `{price}`, `{price_full}`, `{details}`, `{time}` and
+/// `{ack}`, escaping their values for `mode`. `{price}` is shortened when
+/// `compact` is set, `{price_full}` never is.
+pub fn render_alert(
+    template: &str,
+    mode: ParseMode,
+    alert: &FiredAlert,
+    number_format: NumberFormat,
+    compact: bool,
+    timestamp_format: &str,
+) -> String {
+    let threshold = alert
+        .threshold
+        .map(|t| number_format.apply(&t.to_string()))
+        .unwrap_or_else(|| "-".to_string());
+    let time = format_time(alert.timestamp, timestamp_format);
+    let price_full =
+        number_format.apply(&format!("{:.*}", alert.decimals.unwrap_or(2), alert.price));
+    let price = Some(alert.price)
+        .filter(|_| compact)
+        .and_then(|price| number_format.compact(price))
+        .unwrap_or_else(|| price_full.clone());
+    let ack = alert
+        .ack_url
+        .as_ref()
+        .map(|url| format!("Snooze: {}\n", url))
+        .unwrap_or_default();
+
+    template
+        .replace("{symbol}", &escape(mode, &alert.name))
+        .replace("{rule}", &escape(mode, &alert.rule))
+        .replace("{condition}", &escape(mode, &alert.condition))
+        .replace("{threshold}", &escape(mode, &threshold))
+        .replace("{price}", &escape(mode, &price))
+        .replace("{price_full}", &escape(mode, &price_full))
+        .replace("{details}", &escape(mode, &alert.details))
+        .replace("{time}", &escape(mode, &time))
+        .replace("{ack}", &escape(mode, &ack))
+}
+
+/// Characters Telegram's MarkdownV2 reserves. Escaping them with a backslash
+/// is valid anywhere, including inside code spans.
+const MARKDOWN_V2_RESERVED: &str = "\\_*[]()~`>#+-=|{}.!";
+
+/// `text` made safe to embed in `mode` markup.
+fn escape(mode: ParseMode, text: &str) -> String {
+    match mode {
+        ParseMode::Plain => text.to_string(),
+        ParseMode::Html => html_escape(text),
+        ParseMode::MarkdownV2 => {
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                if MARKDOWN_V2_RESERVED.contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+    }
+}
+
+/// Token bucket shared by every outgoing notification, refilled continuously
+/// so that at most `capacity` notifications go out per hour.
+#[derive(Debug, Default)]
+pub struct TokenBucket {
+    tokens: f64,
+    last_refill: Option<u64>,
+    pub exhausted_notified: bool,
+}
+
+impl TokenBucket {
+    pub fn try_take(&mut self, capacity: u32, now: u64) -> bool {
+        self.try_take_within(capacity, 3600, now)
+    }
+
+    /// Like `try_take`, for at most `capacity` per `period` seconds.
+    fn try_take_within(&mut self, capacity: u32, period: u64, now: u64) -> bool {
+        let capacity = capacity as f64;
+        self.tokens = match self.last_refill {
+            Some(last) => {
+                let elapsed = now.saturating_sub(last) as f64;
+                (self.tokens + elapsed * capacity / period as f64).min(capacity)
+            }
+            None => capacity,
+        };
+        self.last_refill = Some(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+pub fn notify(
+    config: &mut Config,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    notify_through(config, None, notification)
+}
+
+/// Like `notify`, but through `channel` alone.
+pub fn notify_via(
+    config: &mut Config,
+    channel: Channel,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    notify_through(config, Some(channel), notification)
+}
+
+fn notify_through(
+    config: &mut Config,
+    only: Option<Channel>,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let snoozed = config.snooze_until.filter(|&until| now < until);
+    if let Some(until) = snoozed.filter(|_| !notification.operational) {
+        println!(
+            "Notifications snoozed until {}, dropping \"{}\"",
+            format_time(until, config.timestamp_format()),
+            notification.subject
+        );
+        config.stats.suppressed("notifications_snoozed");
+        return Ok(());
+    }
+    if let Some(max_per_hour) = config.max_notifications_per_hour {
+        if !config.notification_budget.try_take(max_per_hour, now) {
+            eprintln!(
+                "Notification budget of {} per hour exhausted, dropping \"{}\"",
+                max_per_hour, notification.subject
+            );
+            config.stats.suppressed("budget_exhausted");
+            if !config.notification_budget.exhausted_notified {
+                config.notification_budget.exhausted_notified = true;
+                let notice = Notification::text(
+                    "[bye-watch] Notification budget exceeded",
+                    format!(
+                        "More than {} notifications were requested within the last hour.\n\
+                         Further notifications are dropped until the budget refills.",
+                        max_per_hour
+                    ),
+                );
+                deliver(config, &notice, None)?;
+            }
+            return Ok(());
+        }
+        config.notification_budget.exhausted_notified = false;
+    }
+
+    deliver(config, notification, only)
+}
+
+/// The notification body as every plain text channel sends it, at most
+/// `max_len` bytes long as far as whole alerts allow.
+pub fn render_body(config: &Config, notification: &Notification, max_len: Option<usize>) -> String {
+    let template = config
+        .email
+        .template
+        .as_deref()
+        .unwrap_or(DEFAULT_EMAIL_TEMPLATE);
+    render_body_as(config, notification, template, ParseMode::Plain, max_len)
+}
+
+/// The body in `mode` markup, with alerts formatted by `template`.
+fn render_body_as(
+    config: &Config,
+    notification: &Notification,
+    template: &str,
+    mode: ParseMode,
+    max_len: Option<usize>,
+) -> String {
+    let footer = config.footer.then(|| {
+        let host = hostname();
+        let footer = match (&config.instance_name, &host) {
+            (Some(name), Some(host)) if name != host => format!("{} ({})", name, host),
+            (Some(name), _) => name.clone(),
+            (None, Some(host)) => host.clone(),
+            (None, None) => "unknown host".to_string(),
+        };
+        format!(
+            "\n\n{}\n",
+            escape(mode, &format!("— sent by bye-watch@{}", footer))
+        )
+    });
+    let footer_len = footer.as_ref().map_or(0, String::len);
+    let max_len = max_len.map(|max| max.saturating_sub(footer_len));
+    let body = if config.body_format == BodyFormat::Table && !notification.alerts.is_empty() {
+        // Code blocks keep the columns aligned where markup is rendered
+        let (open, close) = match mode {
+            ParseMode::Plain => ("", ""),
+            ParseMode::MarkdownV2 => ("```\n", "```"),
+            ParseMode::Html => ("<pre>", "</pre>"),
+        };
+        let table = notification.render_table(
+            mode,
+            config.number_format,
+            config.compact_numbers,
+            config.timestamp_format(),
+            max_len.map(|max| max.saturating_sub(open.len() + close.len())),
+        );
+        format!("{}{}{}", open, table, close)
+    } else {
+        notification.render(
+            template,
+            mode,
+            config.number_format,
+            config.compact_numbers,
+            config.timestamp_format(),
+            max_len,
+        )
+    };
+    match footer {
+        Some(footer) => format!("{}{}", body.trim_end(), footer),
+        None => body,
+    }
+}
+
+/// This machine's hostname, if it can be determined.
+pub fn hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Sends `notification` through every configured channel, or `only` one.
+/// Rate limited channels take it from the back of their queue.
+fn deliver(
+    config: &mut Config,
+    notification: &Notification,
+    only: Option<Channel>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if only.is_none() && config.channel_mode == ChannelMode::FirstSuccess {
+        return deliver_first(config, notification);
+    }
+    let wanted = |channel| only.is_none_or(|only| only == channel);
+    for channel in QUEUED_CHANNELS {
+        if !wanted(channel) || rate_limit_per_min(config, channel).is_none() {
+            continue;
+        }
+        let queue = config.channel_queues.entry(channel).or_default();
+        if queue.pending.len() >= MAX_QUEUED {
+            if let Some(dropped) = queue.pending.pop_front() {
+                eprintln!(
+                    "{:?} queue is full, dropping \"{}\"",
+                    channel, dropped.subject
+                );
+            }
+        }
+        queue.pending.push_back(notification.clone());
+    }
+    send(config, Some(notification), wanted(Channel::Email))
+}
+
+/// Tries each channel of `channel_order` until one delivers `notification`,
+/// failing only if none did.
+fn deliver_first(
+    config: &mut Config,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for channel in channel_order(config) {
+        match send_one(config, channel, notification) {
+            Ok(()) => {
+                println!("Delivered \"{}\" via {:?}", notification.subject, channel);
+                return Ok(());
+            }
+            Err(e) => eprintln!(
+                "Failed to send via {:?}, trying the next channel: {}",
+                channel, e
+            ),
+        }
+    }
+    Err(format!("no channel delivered \"{}\"", notification.subject).into())
+}
+
+/// The channels `FirstSuccess` tries, in order.
+fn channel_order(config: &Config) -> Vec<Channel> {
+    if !config.channel_order.is_empty() {
+        return config.channel_order.clone();
+    }
+    [
+        Channel::Email,
+        Channel::Webhook,
+        Channel::Ntfy,
+        Channel::Telegram,
+    ]
+    .into_iter()
+    .filter(|&channel| is_configured(config, channel))
+    .collect()
+}
+
+pub fn is_configured(config: &Config, channel: Channel) -> bool {
+    channel == Channel::Email || rate_limit_per_min(config, channel).is_some()
+}
+
+/// Sends `notification` through `channel` right away, a rate limited channel
+/// out of tokens counting as a failure so the next one is tried instead.
+fn send_one(
+    config: &mut Config,
+    channel: Channel,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(limit) = rate_limit_per_min(config, channel) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let queue = config.channel_queues.entry(channel).or_default();
+        if !queue.bucket.try_take_within(limit, 60, now) {
+            return Err(format!("rate limit of {} per minute reached", limit).into());
+        }
+    }
+    let shared = &*config;
+    match channel {
+        Channel::Email => {
+            let body = render_body(shared, notification, shared.email.max_body_len);
+            let charts = if shared.email.include_chart {
+                render_charts(shared, &notification.alerts)
+            } else {
+                Vec::new()
+            };
+            println!("{}", body);
+            let username = shared.email.username.clone();
+            let cached = config.mailers.remove(&username);
+            let sent = send_email(config, cached, &notification.subject, &body, &charts)?;
+            config.mailers.insert(username, sent);
+            Ok(())
+        }
+        Channel::Webhook => {
+            let webhook = shared.webhook.as_ref().ok_or("webhook is not configured")?;
+            let body = render_body(shared, notification, webhook.max_body_len);
+            let retry = webhook.retry.or(shared.retry);
+            send_webhook(
+                shared.http_client(),
+                webhook,
+                retry,
+                shared.warmup_until,
+                notification,
+                &body,
+            )
+        }
+        Channel::Ntfy => {
+            let ntfy = shared.ntfy.as_ref().ok_or("ntfy is not configured")?;
+            let max_len = ntfy.max_body_len.unwrap_or(NTFY_MAX_BODY_LEN);
+            let body = render_body(shared, notification, Some(max_len));
+            let retry = ntfy.retry.or(shared.retry);
+            send_ntfy(
+                shared.http_client(),
+                ntfy,
+                retry,
+                shared.warmup_until,
+                notification,
+                &body,
+            )
+        }
+        Channel::Telegram => {
+            let telegram = shared
+                .telegram
+                .as_ref()
+                .ok_or("telegram is not configured")?;
+            send_telegram(shared, telegram, notification)
+        }
+    }
+}
+
+/// Sends whatever the channels' rate limits allow of their queues, so queued
+/// messages drain between checks too.
+pub fn drain_queues(config: &mut Config) {
+    if config
+        .channel_queues
+        .values()
+        .any(|q| !q.pending.is_empty())
+    {
+        // Without an email, failures are only logged
+        let _ = send(config, None, false);
+    }
+}
+
+/// Channels with a `rate_limit_per_min`, all but email.
+const QUEUED_CHANNELS: [Channel; 3] = [Channel::Webhook, Channel::Ntfy, Channel::Telegram];
+/// Discord's webhook limit.
+const WEBHOOK_RATE_LIMIT_PER_MIN: u32 = 30;
+/// ntfy.sh's sustained rate of one message every 5 seconds.
+const NTFY_RATE_LIMIT_PER_MIN: u32 = 12;
+/// Telegram's limit for bots posting to a group.
+const TELEGRAM_RATE_LIMIT_PER_MIN: u32 = 20;
+/// Messages a channel queues before dropping the oldest.
+const MAX_QUEUED: usize = 100;
+
+/// The channel's limit, `None` if it is not configured.
+fn rate_limit_per_min(config: &Config, channel: Channel) -> Option<u32> {
+    match channel {
+        Channel::Email => None,
+        Channel::Webhook => config
+            .webhook
+            .as_ref()
+            .map(|w| w.rate_limit_per_min.unwrap_or(WEBHOOK_RATE_LIMIT_PER_MIN)),
+        Channel::Ntfy => config
+            .ntfy
+            .as_ref()
+            .map(|n| n.rate_limit_per_min.unwrap_or(NTFY_RATE_LIMIT_PER_MIN)),
+        Channel::Telegram => config
+            .telegram
+            .as_ref()
+            .map(|t| t.rate_limit_per_min.unwrap_or(TELEGRAM_RATE_LIMIT_PER_MIN)),
+    }
+}
+
+/// Messages waiting for a channel's rate limit.
+#[derive(Debug, Default)]
+pub struct ChannelQueue {
+    bucket: TokenBucket,
+    pending: VecDeque<Notification>,
+}
+
+/// Sends `notification` by email if `email` is set, and every queued message
+/// the rate limits allow through the other channels, all at the same time.
+fn send(
+    config: &mut Config,
+    notification: Option<&Notification>,
+    email: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut due: HashMap<Channel, Vec<Notification>> = HashMap::new();
+    for channel in QUEUED_CHANNELS {
+        let Some(limit) = rate_limit_per_min(config, channel) else {
+            continue;
+        };
+        let Some(queue) = config.channel_queues.get_mut(&channel) else {
+            continue;
+        };
+        let mut batch = Vec::new();
+        while !queue.pending.is_empty() && queue.bucket.try_take_within(limit, 60, now) {
+            batch.extend(queue.pending.pop_front());
+        }
+        if notification.is_some() && !queue.pending.is_empty() {
+            println!(
+                "{:?} rate limit of {} per minute reached, {} messages queued",
+                channel,
+                limit,
+                queue.pending.len()
+            );
+        }
+        due.insert(channel, batch);
+    }
+
+    let body = notification.map(|n| render_body(config, n, config.email.max_body_len));
+    let charts = match notification {
+        Some(n) if email && config.email.include_chart => render_charts(config, &n.alerts),
+        _ => Vec::new(),
+    };
+    let username = config.email.username.clone();
+    let cached = config.mailers.remove(&username);
+    let mailer = std::sync::Mutex::new(None);
+
+    let shared = &*config;
+    let mut jobs: Vec<Job> = Vec::new();
+    if let (true, Some(notification), Some(body)) = (email, notification, &body) {
+        jobs.push((
+            "email",
+            Box::new(|| {
+                let sent = send_email(shared, cached, &notification.subject, body, &charts)?;
+                *mailer.lock().unwrap() = Some(sent);
+                Ok(())
+            }),
+        ));
+    }
+    let batch = |channel| due.get(&channel).filter(|batch| !batch.is_empty());
+    if let (Some(webhook), Some(batch)) = (&shared.webhook, batch(Channel::Webhook)) {
+        jobs.push((
+            "webhook",
+            Box::new(|| {
+                let retry = webhook.retry.or(shared.retry);
+                for_each(batch, |notification| {
+                    let body = render_body(shared, notification, webhook.max_body_len);
+                    send_webhook(
+                        shared.http_client(),
+                        webhook,
+                        retry,
+                        shared.warmup_until,
+                        notification,
+                        &body,
+                    )
+                })
+            }),
+        ));
+    }
+    if let (Some(ntfy), Some(batch)) = (&shared.ntfy, batch(Channel::Ntfy)) {
+        jobs.push((
+            "ntfy notification",
+            Box::new(|| {
+                let retry = ntfy.retry.or(shared.retry);
+                let max_len = ntfy.max_body_len.unwrap_or(NTFY_MAX_BODY_LEN);
+                for_each(batch, |notification| {
+                    let body = render_body(shared, notification, Some(max_len));
+                    send_ntfy(
+                        shared.http_client(),
+                        ntfy,
+                        retry,
+                        shared.warmup_until,
+                        notification,
+                        &body,
+                    )
+                })
+            }),
+        ));
+    }
+    if let (Some(telegram), Some(batch)) = (&shared.telegram, batch(Channel::Telegram)) {
+        jobs.push((
+            "Telegram message",
+            Box::new(|| {
+                for_each(batch, |notification| {
+                    send_telegram(shared, telegram, notification)
+                })
+            }),
+        ));
+    }
+    let limit = shared.notify_concurrency.unwrap_or(jobs.len());
+    let results = run_concurrently(jobs, limit);
+    if let Some(body) = body {
+        println!("{}", body);
+    }
+
+    // Email is the primary channel, the others only log their failures
+    let mut email = Ok(());
+    for (channel, result) in results {
+        match result {
+            Err(e) if channel == "email" => email = Err(e.into()),
+            Err(e) => eprintln!("Failed to send {}: {}", channel, e),
+            Ok(()) => {}
+        }
+    }
+    if let Some(sent) = mailer.into_inner().unwrap() {
+        config.mailers.insert(username, sent);
+    }
+    email
+}
+
+/// Sends every notification of a channel's batch, even after one failed,
+/// returning the last failure.
+fn for_each(
+    batch: &[Notification],
+    mut send: impl FnMut(&Notification) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut result = Ok(());
+    for notification in batch {
+        if let Err(e) = send(notification) {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// A channel's name and the send to run for it.
+type Job<'a> = (
+    &'static str,
+    Box<dyn FnOnce() -> Result<(), Box<dyn std::error::Error>> + Send + 'a>,
+);
+
+/// Runs `jobs` on up to `limit` threads at once, so a slow channel does not
+/// hold up the others. Results are returned in the order of `jobs`.
+fn run_concurrently(jobs: Vec<Job>, limit: usize) -> Vec<(&'static str, Result<(), String>)> {
+    let workers = limit.clamp(1, jobs.len().max(1));
+    let queue = std::sync::Mutex::new(jobs.into_iter().enumerate());
+    let mut results = Vec::new();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let next = queue.lock().unwrap().next();
+                        let Some((i, (channel, send))) = next else {
+                            return done;
+                        };
+                        done.push((i, channel, send().map_err(|e| e.to_string())));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            results.extend(handle.join().unwrap());
+        }
+    });
+    results.sort_by_key(|(i, _, _)| *i);
+    results
+        .into_iter()
+        .map(|(_, channel, result)| (channel, result))
+        .collect()
+}
+
+pub const DEFAULT_RETRIES: u32 = 1;
+pub const DEFAULT_RETRY_BACKOFF_MS: u64 = 500;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+pub fn timeout(retry: RetryPolicy) -> Duration {
+    Duration::from_secs(retry.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+}
+
+/// Milliseconds left until `warmup_until`, 0 once it passed.
+fn warmup_left_ms(warmup_until: Option<SystemTime>) -> u64 {
+    warmup_until
+        .and_then(|until| until.duration_since(SystemTime::now()).ok())
+        .map_or(0, |left| left.as_millis() as u64)
+}
+
+/// Runs `attempt` until it succeeds, the error is not `retryable` or the
+/// policy's retries are used up, or before `warmup_until` (see
+/// `Config::warmup_until`) until that passed. `attempt` is passed the
+/// attempt number, starting at 0.
+pub fn with_retries<T>(
+    channel: &str,
+    retry: RetryPolicy,
+    warmup_until: Option<SystemTime>,
+    retryable: impl Fn(&(dyn std::error::Error + 'static)) -> bool,
+    mut attempt: impl FnMut(u32) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let retries = retry.retries.unwrap_or(DEFAULT_RETRIES);
+    let backoff = retry.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS);
+    let mut n = 0;
+    loop {
+        match attempt(n) {
+            Err(e) if n < retries && retryable(e.as_ref()) => {
+                let wait = backoff.saturating_mul(1 << n.min(16));
+                eprintln!(
+                    "Sending {} failed ({}), retrying in {} ms ({}/{})",
+                    channel,
+                    e,
+                    wait,
+                    n + 1,
+                    retries
+                );
+                std::thread::sleep(Duration::from_millis(wait));
+                n += 1;
+            }
+            Err(e) if warmup_left_ms(warmup_until) > 0 && retryable(e.as_ref()) => {
+                let wait = backoff
+                    .saturating_mul(1 << n.min(16))
+                    .min(warmup_left_ms(warmup_until));
+                eprintln!(
+                    "Sending {} failed ({}), retrying in {} ms while channels warm up",
+                    channel, e, wait
+                );
+                std::thread::sleep(Duration::from_millis(wait));
+                n += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Version of the `Json` webhook payload, bumped on incompatible changes.
+const WEBHOOK_PAYLOAD_VERSION: u32 = 1;
+
+/// The `Json` webhook payload.
+#[derive(Debug, Serialize)]
+struct WebhookBody<'a> {
+    version: u32,
+    subject: &'a str,
+    message: &'a str,
+    alerts: Vec<WebhookAlert<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookAlert<'a> {
+    symbol: &'a str,
+    name: &'a str,
+    rule: &'a str,
+    condition: &'a str,
+    threshold: Option<f64>,
+    price: f64,
+    /// Unix seconds.
+    timestamp: u64,
+    /// "triggered", "reminder" or "cleared".
+    event: &'static str,
+    /// See `AlertEvent::severity`.
+    severity: &'static str,
+    details: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ack_url: Option<&'a str>,
+}
+
+fn send_webhook(
+    client: reqwest::blocking::ClientBuilder,
+    webhook: &WebhookConfig,
+    retry: RetryPolicy,
+    warmup_until: Option<SystemTime>,
+    notification: &Notification,
+    body: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let payload = match webhook.payload {
+        WebhookPayload::Text => {
+            serde_json::json!({ "text": format!("{}\n{}", notification.subject, body) })
+        }
+        WebhookPayload::Json => serde_json::to_value(WebhookBody {
+            version: WEBHOOK_PAYLOAD_VERSION,
+            subject: &notification.subject,
+            message: &notification.message,
+            alerts: notification
+                .alerts
+                .iter()
+                .map(|alert| WebhookAlert {
+                    symbol: &alert.symbol,
+                    name: &alert.name,
+                    rule: &alert.rule,
+                    condition: &alert.condition,
+                    threshold: alert.threshold,
+                    price: alert.price,
+                    timestamp: alert.timestamp,
+                    event: alert.event.as_str(),
+                    severity: alert.event.severity(),
+                    details: alert.details.trim_end(),
+                    ack_url: alert.ack_url.as_deref(),
+                })
+                .collect(),
+        })?,
+    };
+
+    let body = serde_json::to_vec(&payload)?;
+    let signature = webhook
+        .hmac_secret
+        .as_ref()
+        .map(|secret| sign(secret.as_bytes(), &body));
+    let client = client.timeout(timeout(retry)).build()?;
+    with_retries(
+        "webhook",
+        retry,
+        warmup_until,
+        |_| true,
+        |_| {
+            let mut request = client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                let header = webhook
+                    .signature_header
+                    .as_deref()
+                    .unwrap_or(DEFAULT_SIGNATURE_HEADER);
+                request = request.header(header, signature);
+            }
+            let response = request.send()?;
+            if !response.status().is_success() {
+                return Err(format!("Webhook returned HTTP {}", response.status()).into());
+            }
+            Ok(())
+        },
+    )
+}
+
+pub const DEFAULT_SIGNATURE_HEADER: &str = "X-Signature";
+
+/// "sha256=" and the lowercase hex HMAC-SHA256 of `body` under `secret`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret);
+    let tag = ring::hmac::sign(&key, body);
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// A rendered sparkline, embedded as `cid:chart-<symbol>`.
+struct Chart {
+    symbol: String,
+    caption: String,
+    png: Vec<u8>,
+}
+
+/// Sparklines for every alerted symbol with enough recorded history.
+fn render_charts(config: &Config, alerts: &[FiredAlert]) -> Vec<Chart> {
+    let mut charts: Vec<Chart> = Vec::new();
+    for alert in alerts {
+        if charts.iter().any(|chart| chart.symbol == alert.symbol) {
+            continue;
+        }
+        let prices: Vec<f64> = config
+            .price_history
+            .get(&alert.symbol)
+            .map(|history| history.iter().map(|sample| sample.price).collect())
+            .unwrap_or_default();
+        if prices.len() < chart::MIN_SAMPLES {
+            println!(
+                "Not enough history for a {} chart ({} samples), skipping",
+                alert.symbol,
+                prices.len()
+            );
+            continue;
+        }
+        match chart::sparkline_png(&prices) {
+            Ok(png) => charts.push(Chart {
+                symbol: alert.symbol.clone(),
+                caption: alert.name.clone(),
+                png,
+            }),
+            Err(e) => eprintln!("Failed to render {} chart: {}", alert.symbol, e),
+        }
+    }
+    charts
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+const NTFY_MAX_BODY_LEN: usize = 4096;
+
+/// Publishes `body` to the ntfy topic, with a priority following the most
+/// severe alert.
+fn send_ntfy(
+    client: reqwest::blocking::ClientBuilder,
+    ntfy: &NtfyConfig,
+    retry: RetryPolicy,
+    warmup_until: Option<SystemTime>,
+    notification: &Notification,
+    body: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let warning = notification
+        .alerts
+        .iter()
+        .any(|alert| alert.event.severity() == "warning");
+    let priority = if warning { "high" } else { "default" };
+
+    let client = client.timeout(timeout(retry)).build()?;
+    let url = format!("{}/{}", ntfy.url.trim_end_matches('/'), ntfy.topic);
+    with_retries(
+        "ntfy notification",
+        retry,
+        warmup_until,
+        |_| true,
+        |_| {
+            let mut request = client
+                .post(&url)
+                .header("Title", &notification.subject)
+                .header("Priority", priority)
+                .body(body.trim().to_string());
+            if let Some(token) = &ntfy.token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send()?;
+            if !response.status().is_success() {
+                return Err(format!("ntfy returned HTTP {}", response.status()).into());
+            }
+            Ok(())
+        },
+    )
+}
+
+const TELEGRAM_BASE_URL: &str = "https://api.telegram.org";
+const TELEGRAM_MAX_BODY_LEN: usize = 4096;
+
+/// Default per-alert Telegram templates: bold rule, monospace price.
+const TELEGRAM_MARKDOWN_V2_TEMPLATE: &str =
+    "\n*{rule}*\nCurrent price: `{price}`\n{details}Time: {time}\n{ack}";
+const TELEGRAM_HTML_TEMPLATE: &str =
+    "\n<b>{rule}</b>\nCurrent price: <code>{price}</code>\n{details}Time: {time}\n{ack}";
+
+fn send_telegram(
+    config: &Config,
+    telegram: &TelegramConfig,
+    notification: &Notification,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = telegram.parse_mode;
+    let template = telegram.template.as_deref().unwrap_or(match mode {
+        ParseMode::Plain => DEFAULT_EMAIL_TEMPLATE,
+        ParseMode::MarkdownV2 => TELEGRAM_MARKDOWN_V2_TEMPLATE,
+        ParseMode::Html => TELEGRAM_HTML_TEMPLATE,
+    });
+    let subject = escape(mode, &notification.subject);
+    let heading = match mode {
+        ParseMode::Plain => subject,
+        ParseMode::MarkdownV2 => format!("*{}*", subject),
+        ParseMode::Html => format!("<b>{}</b>", subject),
+    };
+    let max_len = telegram.max_body_len.unwrap_or(TELEGRAM_MAX_BODY_LEN);
+    let text = format!(
+        "{}\n{}",
+        heading,
+        render_body_as(
+            config,
+            notification,
+            template,
+            mode,
+            Some(max_len.saturating_sub(heading.len() + 1))
+        )
+    );
+    let mut payload = serde_json::json!({ "chat_id": telegram.chat_id, "text": text });
+    match mode {
+        ParseMode::Plain => {}
+        ParseMode::MarkdownV2 => payload["parse_mode"] = "MarkdownV2".into(),
+        ParseMode::Html => payload["parse_mode"] = "HTML".into(),
+    }
+
+    let retry = telegram.retry.or(config.retry);
+    let client = config.http_client().timeout(timeout(retry)).build()?;
+    let url = format!(
+        "{}/bot{}/sendMessage",
+        TELEGRAM_BASE_URL, telegram.bot_token
+    );
+    with_retries(
+        "Telegram message",
+        retry,
+        config.warmup_until,
+        |_| true,
+        |_| {
+            // The URL contains the bot token, keep it out of the logs
+            let response = client
+                .post(&url)
+                .json(&payload)
+                .send()
+                .map_err(reqwest::Error::without_url)?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let description = response.text().unwrap_or_default();
+                return Err(format!("Telegram returned HTTP {}: {}", status, description).into());
+            }
+            Ok(())
+        },
+    )
+}
+
+/// The sender of alert emails, `from_name` in front of the account's address.
+pub fn from_mailbox(email: &EmailConfig) -> Result<Mailbox, Box<dyn std::error::Error>> {
+    // Line breaks and the like would not survive the From header
+    if let Some(name) = email
+        .from_name
+        .as_ref()
+        .filter(|n| n.chars().any(char::is_control))
+    {
+        return Err(format!("from_name {:?} contains control characters", name).into());
+    }
+    Ok(Mailbox::new(
+        email.from_name.clone(),
+        email.username.parse()?,
+    ))
+}
+
+/// Sends the email through the `cached` transport if there is one, returning
+/// the transport to cache for the next email.
+fn send_email(
+    config: &Config,
+    cached: Option<SmtpTransport>,
+    subject: &str,
+    body: &str,
+    charts: &[Chart],
+) -> Result<SmtpTransport, Box<dyn std::error::Error>> {
+    let builder = Message::builder()
+        .from(from_mailbox(&config.email)?)
+        .to(config.email.username.parse().unwrap())
+        .subject(subject);
+    // A table needs a monospace font, which only HTML can ask for
+    let email = if charts.is_empty() && config.body_format != BodyFormat::Table {
+        builder.body(body.to_string()).unwrap()
+    } else {
+        let mut html = format!("<pre>{}</pre>", html_escape(body));
+        for chart in charts {
+            html.push_str(&format!(
+                "<p>{}<br><img src=\"cid:chart-{}\"></p>",
+                html_escape(&chart.caption),
+                chart.symbol
+            ));
+        }
+        let mut related = MultiPart::related().singlepart(SinglePart::html(html));
+        for chart in charts {
+            related = related.singlepart(
+                Attachment::new_inline(format!("chart-{}", chart.symbol))
+                    .body(chart.png.clone(), ContentType::parse("image/png")?),
+            );
+        }
+        builder.multipart(
+            MultiPart::alternative()
+                .singlepart(SinglePart::plain(body.to_string()))
+                .multipart(related),
+        )?
+    };
+
+    println!("Sending email");
+    let retry = config.email.retry.or(config.retry);
+    let mut cached = cached;
+    let transient = |e: &(dyn std::error::Error + 'static)| {
+        e.downcast_ref::<lettre::transport::smtp::Error>()
+            .is_none_or(|e| !e.is_permanent())
+    };
+    with_retries("email", retry, config.warmup_until, transient, |attempt| {
+        // A failure is likely a pooled connection the server has since
+        // closed, so retries always reconnect
+        let mailer = match cached.take() {
+            Some(mailer) if attempt == 0 => mailer,
+            _ => build_mailer(&config.email, retry)?,
+        };
+        mailer.send(&email)?;
+        Ok(mailer)
+    })
+}
+
+pub const DEFAULT_POOL_SIZE: u32 = 2;
+pub const DEFAULT_POOL_IDLE_TIMEOUT_S: u64 = 60;
+
+/// A pooled transport, cached per account and reused across checks.
+fn build_mailer(
+    email: &EmailConfig,
+    retry: RetryPolicy,
+) -> Result<SmtpTransport, Box<dyn std::error::Error>> {
+    let creds = Credentials::new(email.username.clone(), email.password.clone());
+    let pool = PoolConfig::new()
+        .max_size(email.pool_size.unwrap_or(DEFAULT_POOL_SIZE))
+        .idle_timeout(Duration::from_secs(
+            email
+                .pool_idle_timeout_s
+                .unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT_S),
+        ));
+    Ok(SmtpTransport::relay("smtp.gmail.com")?
+        .credentials(creds)
+        .pool_config(pool)
+        .timeout(Some(timeout(retry)))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_v2_escapes_every_reserved_character() {
+        assert_eq!(
+            escape(ParseMode::MarkdownV2, "1000SHIB_USDT (x1000) -2.5% [ok]!"),
+            "1000SHIB\\_USDT \\(x1000\\) \\-2\\.5% \\[ok\\]\\!"
+        );
+        assert_eq!(escape(ParseMode::MarkdownV2, "a\\b`c"), "a\\\\b\\`c");
+        assert_eq!(
+            escape(ParseMode::Html, "<b>&</b>"),
+            "&lt;b&gt;&amp;&lt;/b&gt;"
+        );
+        assert_eq!(escape(ParseMode::Plain, "*as is*"), "*as is*");
+    }
+
+    #[test]
+    fn channels_are_sent_within_the_concurrency_limit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let send = || {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        };
+        let jobs: Vec<Job> = vec![
+            ("email", Box::new(|| Err("refused".into()))),
+            ("webhook", Box::new(send)),
+            ("ntfy notification", Box::new(send)),
+            ("Telegram message", Box::new(send)),
+        ];
+
+        let limit = 2;
+        let results = run_concurrently(jobs, limit);
+        // Scheduling decides whether the limit is reached, never exceeded
+        assert!(peak.load(Ordering::SeqCst) <= limit);
+        assert_eq!(
+            results,
+            vec![
+                ("email", Err("refused".to_string())),
+                ("webhook", Ok(())),
+                ("ntfy notification", Ok(())),
+                ("Telegram message", Ok(())),
+            ]
+        );
+    }
+
+    #[test]
+    fn webhook_signatures_are_hmac_sha256_of_the_body() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn rate_limited_channels_queue_what_exceeds_the_limit() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "currencies": [],
+            "webhook": { "url": "http://127.0.0.1:1", "retries": 0, "rate_limit_per_min": 2 },
+        }))
+        .unwrap();
+        let queue = config.channel_queues.entry(Channel::Webhook).or_default();
+        for i in 0..3 {
+            queue
+                .pending
+                .push_back(Notification::text(format!("#{}", i), ""));
+        }
+
+        drain_queues(&mut config);
+        let pending = &config.channel_queues[&Channel::Webhook].pending;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].subject, "#2");
+    }
+
+    #[test]
+    fn first_success_tries_the_channels_in_order() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "currencies": [],
+            "webhook": { "url": "http://127.0.0.1:1", "retries": 0 },
+            "ntfy": { "url": "http://127.0.0.1:1", "topic": "alerts", "retries": 0 },
+            "channel_mode": "FirstSuccess",
+        }))
+        .unwrap();
+        assert_eq!(
+            channel_order(&config),
+            vec![Channel::Email, Channel::Webhook, Channel::Ntfy]
+        );
+
+        config.channel_order = vec![Channel::Ntfy, Channel::Webhook];
+        let error = deliver(&mut config, &Notification::text("down", ""), None).unwrap_err();
+        assert_eq!(error.to_string(), "no channel delivered \"down\"");
+        // Nothing is left queued for the channels that failed
+        assert!(config.channel_queues.values().all(|q| q.pending.is_empty()));
+    }
+
+    #[test]
+    fn from_mailbox_puts_the_display_name_in_front() {
+        let mut email: EmailConfig = serde_json::from_value(serde_json::json!({
+            "username": "alerts@example.com",
+            "password": "",
+        }))
+        .unwrap();
+        assert_eq!(
+            from_mailbox(&email).unwrap().to_string(),
+            "alerts@example.com"
+        );
+        email.from_name = Some("Bye-Watch Alerts".to_string());
+        assert_eq!(
+            from_mailbox(&email).unwrap().to_string(),
+            "Bye-Watch Alerts <alerts@example.com>"
+        );
+        email.from_name = Some("Alerts\nBcc: x@example.com".to_string());
+        assert!(from_mailbox(&email).is_err());
+        email.from_name = None;
+        email.username = "not an address".to_string();
+        assert!(from_mailbox(&email).is_err());
+    }
+
+    #[test]
+    fn sends_keep_retrying_while_channels_warm_up() {
+        let retry = RetryPolicy {
+            retries: Some(0),
+            retry_backoff_ms: Some(10),
+            ..Default::default()
+        };
+        let failing_until = |ok_at: u32| {
+            move |n: u32| match n >= ok_at {
+                true => Ok(n),
+                false => Err::<u32, Box<dyn std::error::Error>>("connection refused".into()),
+            }
+        };
+        let warmup_until = Some(SystemTime::now() + Duration::from_millis(500));
+        let attempts = with_retries("email", retry, warmup_until, |_| true, failing_until(3));
+        assert_eq!(attempts.unwrap(), 3);
+
+        std::thread::sleep(Duration::from_millis(500));
+        let attempts = with_retries("email", retry, warmup_until, |_| true, failing_until(3));
+        assert!(attempts.is_err());
+    }
+
+    #[test]
+    fn snoozing_drops_all_but_operational_notices() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "currencies": [],
+            "webhook": { "url": "http://127.0.0.1:1", "retries": 0 },
+            "snooze_until": u64::MAX,
+        }))
+        .unwrap();
+        let alert = Notification::text("[bye-watch] Price summary", "");
+        assert!(notify_via(&mut config, Channel::Webhook, &alert).is_ok());
+        assert_eq!(config.stats.suppressed_total["notifications_snoozed"], 1);
+
+        let crash = Notification::operational("[bye-watch] Crashed", "");
+        notify_via(&mut config, Channel::Webhook, &crash).ok();
+        assert_eq!(config.stats.suppressed_total["notifications_snoozed"], 1);
+    }
+}
@@ -0,0 +1,115 @@
+//! Credentials kept in a separate `secrets_file` instead of config.json. They
+//! are merged into the configuration when it is loaded and left out whenever
+//! it is written back.
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Keys the secrets file may hold, and where each goes in the configuration.
+const SECRETS: [(&str, &str); 7] = [
+    ("email_password", "/email/password"),
+    ("telegram_bot_token", "/telegram/bot_token"),
+    ("webhook_hmac_secret", "/webhook/hmac_secret"),
+    ("ntfy_token", "/ntfy/token"),
+    ("event_webhook_hmac_secret", "/event_webhook/hmac_secret"),
+    ("mqtt_password", "/mqtt/password"),
+    ("http_token", "/http/token"),
+];
+
+/// Merges the `secrets_file` named by `config`, if any, into it. Returns the
+/// JSON pointers filled from the file, for `strip`.
+pub fn apply(config: &mut Value) -> Result<Vec<&'static str>, Box<dyn std::error::Error>> {
+    let Some(path) = config.get("secrets_file").and_then(Value::as_str) else {
+        return Ok(Vec::new());
+    };
+    let path = path.to_string();
+    warn_if_world_readable(&path);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("could not read secrets_file {}: {}", path, e))?;
+    let secrets: BTreeMap<String, String> = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a JSON object of strings: {}", path, e))?;
+
+    let mut filled = Vec::new();
+    for (key, secret) in secrets {
+        let Some(&(_, pointer)) = SECRETS.iter().find(|(name, _)| *name == key) else {
+            let known: Vec<&str> = SECRETS.iter().map(|(name, _)| *name).collect();
+            return Err(format!(
+                "{}: unknown secret \"{}\", expected one of {:?}",
+                path, key, known
+            )
+            .into());
+        };
+        let (parent, field) = pointer.rsplit_once('/').unwrap_or_default();
+        match config.pointer_mut(parent).and_then(Value::as_object_mut) {
+            Some(section) => {
+                section.insert(field.to_string(), Value::String(secret));
+                filled.push(pointer);
+            }
+            None => eprintln!(
+                "Ignoring {} from {}, {} is not configured",
+                key,
+                path,
+                &parent[1..]
+            ),
+        }
+    }
+    Ok(filled)
+}
+
+/// Removes the secrets `apply` filled in from a serialized configuration.
+pub fn strip(config: &mut Value, filled: &[&str]) {
+    for pointer in filled {
+        let (parent, field) = pointer.rsplit_once('/').unwrap_or_default();
+        if let Some(section) = config.pointer_mut(parent).and_then(Value::as_object_mut) {
+            section.remove(field);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn warn_if_world_readable(path: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.permissions().mode() & 0o004 != 0 {
+            eprintln!(
+                "Warning: secrets_file {} is readable by every user, restrict it with chmod 600",
+                path
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_world_readable(_path: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_merged_in_and_stripped_again() {
+        let path =
+            std::env::temp_dir().join(format!("bye-watch-{}-secrets.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"email_password": "hunter2", "telegram_bot_token": "123:abc"}"#,
+        )
+        .unwrap();
+        let mut config = json!({
+            "secrets_file": path.to_str().unwrap(),
+            "email": { "username": "a@b.com" },
+        });
+
+        let filled = apply(&mut config).unwrap();
+        assert_eq!(filled, vec!["/email/password"]);
+        assert_eq!(config["email"]["password"], "hunter2");
+        assert!(config.get("telegram").is_none());
+
+        strip(&mut config, &filled);
+        assert_eq!(config["email"], json!({ "username": "a@b.com" }));
+
+        std::fs::write(&path, r#"{"smtp_password": "hunter2"}"#).unwrap();
+        assert!(apply(&mut config).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,441 @@
+use crate::Config;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a connection may take to send its request line.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Starts the status server on a background thread. Requests lock the shared
+/// config, so they wait while a check is in progress.
+pub fn spawn(bind: &str, config: Arc<Mutex<Config>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(bind)?;
+    println!("HTTP endpoint listening on {}", bind);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle(stream, &config) {
+                        eprintln!("HTTP request failed: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("HTTP connection failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Returns a fresh, hard to guess token for acknowledging an alert.
+pub fn new_token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    format!("{:016x}", hasher.finish())
+}
+
+fn handle(mut stream: TcpStream, config: &Mutex<Config>) -> std::io::Result<()> {
+    // Connections are served one at a time, an idle one must not hold up
+    // the others
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+
+    let mut content_type = "text/plain";
+    let (status, body) = match (method, path) {
+        ("GET", "/health") => ("200 OK", "ok".to_string()),
+        ("GET", "/evaluate") => {
+            content_type = "application/json";
+            let config = config.lock().unwrap();
+            match authorize(&config, authorization.as_deref())
+                .and_then(|()| evaluate(&config, query))
+            {
+                Ok(result) => ("200 OK", result.to_string()),
+                Err((status, error)) => (status, serde_json::json!({ "error": error }).to_string()),
+            }
+        }
+        ("POST", path) if path.starts_with("/mute/") => {
+            let mut config = config.lock().unwrap();
+            let result = authorize(&config, authorization.as_deref())
+                .and_then(|()| path_symbol(path))
+                .and_then(|symbol| mute(&mut config, &symbol, query));
+            match result {
+                Ok(message) => ("200 OK", message),
+                Err(error) => error,
+            }
+        }
+        ("DELETE", path) if path.starts_with("/mute/") => {
+            let mut config = config.lock().unwrap();
+            let result = authorize(&config, authorization.as_deref())
+                .and_then(|()| path_symbol(path))
+                .and_then(|symbol| unmute(&mut config, &symbol));
+            match result {
+                Ok(message) => ("200 OK", message),
+                Err(error) => error,
+            }
+        }
+        ("GET", path) if path.starts_with("/ack/") => {
+            let token = &path["/ack/".len()..];
+            let mut config = config.lock().unwrap();
+            match acknowledge(&mut config, token) {
+                Some(message) => ("200 OK", message),
+                None => ("404 Not Found", "Unknown or expired token".to_string()),
+            }
+        }
+        _ => ("404 Not Found", "Not found".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Answers `/evaluate?symbol=BTCUSDT&condition=above&threshold=70000`
+/// against the price recorded by the latest check, without creating an alert.
+fn evaluate(config: &Config, query: &str) -> Result<serde_json::Value, (&'static str, String)> {
+    let param = |name: &str| {
+        query_param(query, name).ok_or((
+            "400 Bad Request",
+            format!("Missing or malformed parameter {}", name),
+        ))
+    };
+    let symbol = param("symbol")?.to_uppercase();
+    let condition = param("condition")?.to_lowercase();
+    let threshold: f64 = param("threshold")?
+        .parse()
+        .ok()
+        .filter(|t: &f64| t.is_finite())
+        .ok_or(("400 Bad Request", "threshold is not a number".to_string()))?;
+    let sample = config
+        .price_history
+        .get(&symbol)
+        .and_then(|history| history.back())
+        .ok_or((
+            "404 Not Found",
+            format!(
+                "No price recorded for {}, it must be watched by an alert",
+                symbol
+            ),
+        ))?;
+    let met = match condition.as_str() {
+        "above" => sample.price > threshold,
+        "below" => sample.price < threshold,
+        _ => {
+            return Err((
+                "400 Bad Request",
+                format!(
+                    "Unsupported condition \"{}\", use above or below",
+                    condition
+                ),
+            ))
+        }
+    };
+    Ok(serde_json::json!({
+        "symbol": symbol,
+        "condition": condition,
+        "threshold": threshold,
+        "price": sample.price,
+        "timestamp": sample.timestamp,
+        "met": met,
+    }))
+}
+
+/// The URL decoded symbol of a `/mute/<symbol>` path.
+fn path_symbol(path: &str) -> Result<String, (&'static str, String)> {
+    decode(&path["/mute/".len()..])
+        .map(|symbol| symbol.to_uppercase())
+        .ok_or(("400 Bad Request", "Malformed symbol".to_string()))
+}
+
+fn unmute(config: &mut Config, symbol: &str) -> Result<String, (&'static str, String)> {
+    match config.muted_symbols.remove(symbol) {
+        Some(_) => {
+            println!("Unmuted {}", symbol);
+            Ok(format!("Unmuted {}", symbol))
+        }
+        None => Err(("404 Not Found", format!("{} is not muted", symbol))),
+    }
+}
+
+/// Mutes every alert on `symbol` for `?hours=N`.
+fn mute(config: &mut Config, symbol: &str, query: &str) -> Result<String, (&'static str, String)> {
+    let hours: u64 = query_param(query, "hours")
+        .and_then(|hours| hours.parse().ok())
+        .filter(|hours| *hours > 0)
+        .ok_or((
+            "400 Bad Request",
+            "hours must be a positive number of hours".to_string(),
+        ))?;
+    if !config.all_currencies().any(|c| c.symbol == symbol) {
+        return Err(("404 Not Found", format!("No alert watches {}", symbol)));
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| ("500 Internal Server Error", e.to_string()))?
+        .as_secs();
+    config
+        .muted_symbols
+        .insert(symbol.to_string(), now + hours * 3600);
+    println!("Muted {} for {} hours", symbol, hours);
+    Ok(format!("Muted {} for {} hours", symbol, hours))
+}
+
+/// Checks the `Authorization: Bearer` header against `http.token`.
+fn authorize(config: &Config, authorization: Option<&str>) -> Result<(), (&'static str, String)> {
+    let Some(token) = config.http.as_ref().and_then(|http| http.token.as_deref()) else {
+        return Err((
+            "403 Forbidden",
+            "Set http.token to enable this endpoint".to_string(),
+        ));
+    };
+    let given = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compared in constant time, so the token cannot be guessed bytewise
+    let matches = given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    match matches {
+        true => Ok(()),
+        false => Err((
+            "401 Unauthorized",
+            "Missing or wrong bearer token".to_string(),
+        )),
+    }
+}
+
+/// The URL decoded value of `name` in `query`, `None` if it is missing or
+/// not valid percent-encoded UTF-8.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| decode(&value.replace('+', " ")))
+}
+
+/// Decodes the `%XX` escapes of a URL component.
+fn decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(after.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &after[2..];
+        } else {
+            bytes.push(byte);
+            rest = after;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Snoozes the alert holding `token`, consuming the token.
+fn acknowledge(config: &mut Config, token: &str) -> Option<String> {
+    let extension_secs = config.http.as_ref()?.ack_extension_h * 3600;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let currency = config
+        .all_currencies_mut()
+        .find(|c| c.ack_token.as_deref() == Some(token))?;
+    currency.ack_token = None;
+    currency.snoozed_until = Some(now + extension_secs);
+    println!(
+        "Acknowledged {}, snoozed for {} hours",
+        currency.rule(),
+        extension_secs / 3600
+    );
+    Some(format!(
+        "Snoozed {} for {} hours",
+        currency.rule(),
+        extension_secs / 3600
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// Sends `request` through `handle` and returns the response.
+    fn request(config: &Mutex<Config>, request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        handle(stream, config).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn config() -> Mutex<Config> {
+        Mutex::new(
+            serde_json::from_value(serde_json::json!({
+                "email": { "username": "test@example.com", "password": "" },
+                "check_interval": 60,
+                "withold_notification_h": null,
+                "http": { "bind": "127.0.0.1:0" },
+                "currencies": [{
+                    "symbol": "BTCEUR",
+                    "threshold": 70000,
+                    "alert_condition": "Above",
+                    "last_alerted": 1000,
+                    "ack_token": "0123abcd",
+                }],
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn ack_snoozes_the_alert_once() {
+        let config = config();
+        let response = request(&config, "GET /ack/0123abcd HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("Snoozed BTCEUR Above 70000 for 24 hours"));
+        {
+            let currency = &config.lock().unwrap().currencies[0];
+            assert!(currency.ack_token.is_none());
+            assert!(currency.snoozed_until.is_some());
+        }
+
+        let response = request(&config, "GET /ack/0123abcd HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found"),
+            "{}",
+            response
+        );
+    }
+
+    #[test]
+    fn evaluate_needs_the_token_and_decodes_its_query() {
+        let config = config();
+        let evaluate =
+            "GET /evaluate?symbol=btc%45ur&condition=above&threshold=6%2E5e4 HTTP/1.1\r\n";
+        let response = request(&config, &format!("{}\r\n", evaluate));
+        assert!(
+            response.starts_with("HTTP/1.1 403 Forbidden"),
+            "{}",
+            response
+        );
+
+        {
+            let mut config = config.lock().unwrap();
+            config.http.as_mut().unwrap().token = Some("s3cret".to_string());
+            config.price_history.insert(
+                "BTCEUR".to_string(),
+                [crate::PriceSample {
+                    price: 66_000.0,
+                    timestamp: 1_000,
+                }]
+                .into(),
+            );
+        }
+        let response = request(
+            &config,
+            &format!("{}Authorization: Bearer guess\r\n\r\n", evaluate),
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 401 Unauthorized"),
+            "{}",
+            response
+        );
+        let response = request(
+            &config,
+            &format!("{}authorization: Bearer s3cret\r\n\r\n", evaluate),
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains(r#""symbol":"BTCEUR""#), "{}", response);
+        assert!(response.contains(r#""threshold":65000.0"#), "{}", response);
+        assert!(response.contains(r#""met":true"#), "{}", response);
+
+        assert_eq!(
+            query_param("a=1&symbol=ETH%2FEUR+x", "symbol").unwrap(),
+            "ETH/EUR x"
+        );
+        assert!(query_param("symbol=%E2%82", "symbol").is_none());
+        assert!(query_param("symbol=%4", "symbol").is_none());
+    }
+
+    #[test]
+    fn muting_needs_the_token_and_decodes_the_symbol() {
+        let config = config();
+        let response = request(&config, "POST /mute/btceur?hours=2 HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 403 Forbidden"),
+            "{}",
+            response
+        );
+
+        config.lock().unwrap().http.as_mut().unwrap().token = Some("s3cret".to_string());
+        let auth = "Authorization: Bearer s3cret\r\n\r\n";
+        let response = request(
+            &config,
+            "DELETE /mute/BTCEUR HTTP/1.1\r\nAuthorization: Bearer no\r\n\r\n",
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 401 Unauthorized"),
+            "{}",
+            response
+        );
+        let response = request(
+            &config,
+            &format!("POST /mute/btc%45ur?hours=2 HTTP/1.1\r\n{}", auth),
+        );
+        assert!(
+            response.ends_with("Muted BTCEUR for 2 hours"),
+            "{}",
+            response
+        );
+        assert!(config.lock().unwrap().muted_symbols.contains_key("BTCEUR"));
+        let response = request(
+            &config,
+            &format!("DELETE /mute/BTC%45UR HTTP/1.1\r\n{}", auth),
+        );
+        assert!(response.ends_with("Unmuted BTCEUR"), "{}", response);
+        assert!(config.lock().unwrap().muted_symbols.is_empty());
+        let response = request(&config, &format!("DELETE /mute/BTC%4 HTTP/1.1\r\n{}", auth));
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request"),
+            "{}",
+            response
+        );
+    }
+}
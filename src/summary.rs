@@ -0,0 +1,82 @@
+//! The scheduled price summary, see `SummaryConfig`: current prices of every
+//! alert and their distance to the threshold, sent whether or not anything
+//! fired.
+use crate::notify::Notification;
+use crate::{AlertCondition, Config};
+use chrono::{Local, TimeZone};
+
+/// The latest time at or before `now` that fell on `minute` (since local
+/// midnight), as Unix seconds.
+pub fn last_scheduled(minute: u32, now: u64) -> Option<u64> {
+    let now = Local.timestamp_opt(now as i64, 0).single()?;
+    let today = now.date_naive().and_hms_opt(minute / 60, minute % 60, 0)?;
+    let mut scheduled = Local.from_local_datetime(&today).earliest()?;
+    if scheduled > now {
+        scheduled = Local
+            .from_local_datetime(&(today - chrono::Duration::days(1)))
+            .earliest()?;
+    }
+    Some(scheduled.timestamp() as u64)
+}
+
+pub fn compose(config: &Config) -> Notification {
+    let mut lines = Vec::new();
+    for currency in config.all_currencies() {
+        let Some(price) = currency.last_price else {
+            lines.push(format!("{}: no data", currency.rule()));
+            continue;
+        };
+        let formatted = config.number_format.apply(&format!(
+            "{:.*}",
+            currency.tick.map_or(2, |tick| tick.decimals),
+            price
+        ));
+        let distance = match currency.alert_condition {
+            _ if currency.condition_met => Some(" (met)".to_string()),
+            AlertCondition::Above | AlertCondition::Below => currency
+                .threshold()
+                .filter(|_| price > 0.0)
+                .map(|threshold| format!(" ({:+.2}% to go)", (threshold - price) / price * 100.0)),
+            _ => None,
+        };
+        lines.push(format!(
+            "{}: {}{}",
+            currency.rule(),
+            formatted,
+            distance.unwrap_or_default()
+        ));
+    }
+    Notification::text(
+        "[bye-watch] Price summary",
+        format!(
+            "Current prices as of {}:\n\n{}",
+            Local::now().format(config.timestamp_format()),
+            lines.join("\n")
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_scheduled_is_today_once_the_time_has_passed() {
+        let noon = Local
+            .with_ymd_and_hms(2026, 3, 10, 12, 0, 0)
+            .unwrap()
+            .timestamp() as u64;
+        let at = |hour: u32, minute: u32| {
+            Local
+                .with_ymd_and_hms(2026, 3, 10, hour, minute, 0)
+                .unwrap()
+                .timestamp() as u64
+        };
+        assert_eq!(last_scheduled(8 * 60, noon), Some(at(8, 0)));
+        assert_eq!(last_scheduled(12 * 60, noon), Some(noon));
+        assert_eq!(
+            last_scheduled(18 * 60 + 30, noon),
+            Some(at(18, 30) - 86_400)
+        );
+    }
+}
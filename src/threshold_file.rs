@@ -0,0 +1,109 @@
+//! Thresholds maintained outside config.json, re-read every check.
+
+use crate::{import, Config};
+use std::collections::HashMap;
+
+/// Re-reads every `threshold_file` used by the current alerts and applies
+/// the values. A file that fails to load keeps its last good values.
+pub fn refresh(config: &mut Config) {
+    let mut paths: Vec<String> = config
+        .currencies
+        .iter()
+        .filter_map(|c| c.threshold_file.clone())
+        .collect();
+    paths.sort_unstable();
+    paths.dedup();
+
+    for path in paths {
+        match load(&path) {
+            Ok(values) => {
+                config.threshold_files.insert(path, values);
+            }
+            Err(e) if config.threshold_files.contains_key(&path) => {
+                eprintln!("{}: {}, keeping the last good thresholds", path, e)
+            }
+            Err(e) => eprintln!("{}: {}, no thresholds loaded yet", path, e),
+        }
+    }
+
+    for currency in &mut config.currencies {
+        currency.file_threshold = currency.threshold_file.as_ref().and_then(|path| {
+            let value = config
+                .threshold_files
+                .get(path)?
+                .get(&currency.symbol)
+                .copied();
+            if value.is_none() {
+                eprintln!("{}: no threshold for {}", path, currency.symbol);
+            }
+            value
+        });
+    }
+}
+
+/// A JSON object of symbol to value, or `symbol,value` rows (comma or tab
+/// separated, optional header, `#` comments).
+fn load(path: &str) -> Result<HashMap<String, f64>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let values: HashMap<String, f64> = if content.trim_start().starts_with('{') {
+        serde_json::from_str(&content).map_err(|e| e.to_string())?
+    } else {
+        let mut values = HashMap::new();
+        for (line_number, fields) in import::csv_rows(&content) {
+            match fields[..] {
+                [symbol, value] => {
+                    let value = value.parse().map_err(|_| {
+                        format!("line {}: invalid value \"{}\"", line_number, value)
+                    })?;
+                    values.insert(symbol.to_uppercase(), value);
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: expected 2 fields (symbol, value), found {}",
+                        line_number,
+                        fields.len()
+                    ))
+                }
+            }
+        }
+        values
+    };
+
+    if let Some((symbol, value)) = values.iter().find(|(_, v)| !v.is_finite()) {
+        return Err(format!("threshold {} for {} is not finite", value, symbol));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_broken_file_keeps_the_last_good_thresholds() {
+        let path =
+            std::env::temp_dir().join(format!("bye-watch-{}-thresholds.csv", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "email": { "username": "test@example.com", "password": "" },
+            "check_interval": 60,
+            "currencies": [
+                { "symbol": "BTCEUR", "alert_condition": "Above", "threshold_file": path },
+            ],
+        }))
+        .unwrap();
+
+        std::fs::write(&path, "symbol\tthreshold\n# daily levels\nbtceur\t100\n").unwrap();
+        refresh(&mut config);
+        assert_eq!(config.currencies[0].file_threshold, Some(100.0));
+
+        std::fs::write(&path, "BTCEUR,100,extra\n").unwrap();
+        refresh(&mut config);
+        assert_eq!(config.currencies[0].file_threshold, Some(100.0));
+
+        std::fs::write(&path, "{\"BTCEUR\": 120}").unwrap();
+        refresh(&mut config);
+        assert_eq!(config.currencies[0].file_threshold, Some(120.0));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
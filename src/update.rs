@@ -0,0 +1,54 @@
+//! Opt-in check for a newer release on GitHub.
+
+use serde::Deserialize;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/El-Maco/bye-watch/releases/latest";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+/// Logs when the latest GitHub release is newer than this build. Failures are
+/// only reported, never fatal.
+pub fn check_for_update(client: reqwest::blocking::ClientBuilder) {
+    match latest_release(client) {
+        Ok(release) => {
+            let current = env!("CARGO_PKG_VERSION");
+            match (parse_version(&release.tag_name), parse_version(current)) {
+                (Some(latest), Some(running)) if latest > running => println!(
+                    "A newer version of bye-watch is available: {} (running {}), see {}",
+                    release.tag_name, current, release.html_url
+                ),
+                (Some(_), Some(_)) => println!("bye-watch {} is up to date", current),
+                _ => eprintln!(
+                    "Update check: could not compare release \"{}\" with {}",
+                    release.tag_name, current
+                ),
+            }
+        }
+        Err(e) => eprintln!("Update check failed: {}", e),
+    }
+}
+
+fn latest_release(
+    client: reqwest::blocking::ClientBuilder,
+) -> Result<Release, Box<dyn std::error::Error>> {
+    let client = client
+        .user_agent(concat!("bye-watch/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .build()?;
+    let response = client.get(LATEST_RELEASE_URL).send()?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()).into());
+    }
+    Ok(response.json()?)
+}
+
+/// "v1.2.3" -> [1, 2, 3], ignoring any pre-release suffix.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    core.split('.').map(|part| part.parse().ok()).collect()
+}
@@ -0,0 +1,111 @@
+//! Named levels from the `variables` map, referenced by alert thresholds as
+//! `"${name}"`. References are replaced by their value when the configuration
+//! is loaded and put back whenever it is written.
+use serde_json::Value;
+
+/// A threshold resolved from a variable: its JSON pointer and the reference.
+pub type Resolved = Vec<(String, String)>;
+
+/// Replaces every threshold reference in `config` by the variable's value.
+/// Fails on references to variables that are not defined.
+pub fn apply(config: &mut Value) -> Result<Resolved, Box<dyn std::error::Error>> {
+    let variables = match config.get("variables") {
+        None => serde_json::Map::new(),
+        Some(Value::Object(variables)) => variables.clone(),
+        Some(_) => return Err("variables must be a JSON object of numbers".into()),
+    };
+    if let Some((name, _)) = variables.iter().find(|(_, value)| !value.is_number()) {
+        return Err(format!("variable {} is not a number", name).into());
+    }
+
+    let mut alerts = vec!["/currencies".to_string()];
+    let profiles = config["profiles"].as_array().map_or(0, Vec::len);
+    alerts.extend((0..profiles).map(|i| format!("/profiles/{}/currencies", i)));
+    let mut thresholds = Vec::new();
+    for list in alerts {
+        let count = config
+            .pointer(&list)
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len);
+        for i in 0..count {
+            let alert = format!("{}/{}", list, i);
+            thresholds.push(format!("{}/threshold", alert));
+            let tiers = config
+                .pointer(&format!("{}/tiers", alert))
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len);
+            thresholds.extend((0..tiers).map(|t| format!("{}/tiers/{}/threshold", alert, t)));
+        }
+    }
+
+    let mut resolved = Vec::new();
+    for pointer in thresholds {
+        let Some(threshold) = config.pointer_mut(&pointer) else {
+            continue;
+        };
+        let Some(name) = threshold
+            .as_str()
+            .and_then(|text| text.trim().strip_prefix("${"))
+            .and_then(|text| text.strip_suffix('}'))
+        else {
+            continue;
+        };
+        let value = variables
+            .get(name)
+            .ok_or_else(|| format!("{}: undefined variable ${{{}}}", pointer, name))?;
+        let reference = std::mem::replace(threshold, value.clone());
+        resolved.push((pointer, reference.as_str().unwrap_or_default().to_string()));
+    }
+    Ok(resolved)
+}
+
+/// Puts the references `apply` resolved back into a serialized configuration.
+pub fn restore(config: &mut Value, resolved: &Resolved) {
+    for (pointer, reference) in resolved {
+        if let Some(threshold) = config.pointer_mut(pointer) {
+            *threshold = Value::String(reference.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn references_are_resolved_and_restored() {
+        let mut config = json!({
+            "variables": { "btc_target": 70000 },
+            "currencies": [
+                { "symbol": "BTCEUR", "threshold": "${btc_target}" },
+                { "symbol": "BTCUSDT", "threshold": "+5%" },
+            ],
+            "profiles": [{
+                "currencies": [{
+                    "symbol": "BTCEUR",
+                    "tiers": [{ "threshold": "${btc_target}" }],
+                }],
+            }],
+        });
+        let original = config.clone();
+
+        let resolved = apply(&mut config).unwrap();
+        assert_eq!(config["currencies"][0]["threshold"], 70000);
+        assert_eq!(config["currencies"][1]["threshold"], "+5%");
+        assert_eq!(
+            config["profiles"][0]["currencies"][0]["tiers"][0]["threshold"],
+            70000
+        );
+
+        restore(&mut config, &resolved);
+        assert_eq!(config, original);
+
+        config["currencies"][1]["threshold"] = json!("${eth_target}");
+        let e = apply(&mut config).unwrap_err().to_string();
+        assert_eq!(
+            e,
+            "/currencies/1/threshold: undefined variable ${eth_target}"
+        );
+    }
+}
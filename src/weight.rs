@@ -0,0 +1,56 @@
+//! Request weight spent against Binance's per-minute limits, consulted
+//! before every request so heavy alert mixes defer instead of getting banned.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WINDOW_SECS: u64 = 60;
+
+/// Weight spent during the last minute, as `(timestamp, weight)` entries.
+#[derive(Debug, Default)]
+pub struct WeightTracker {
+    spent: Mutex<VecDeque<(u64, u64)>>,
+}
+
+impl WeightTracker {
+    /// Records `weight` for a request about to be sent, first sleeping for as
+    /// long as it would take the request to fit into `budget` per minute.
+    pub fn spend(&self, label: &str, weight: u64, budget: u64) {
+        let now = unix_now();
+        let wait = wait_secs(&mut self.spent.lock().unwrap(), weight, budget, now);
+        if wait > 0 {
+            println!(
+                "Deferring {} request by {} seconds to stay under the weight budget of {}/min",
+                label, wait, budget
+            );
+            std::thread::sleep(Duration::from_secs(wait));
+        }
+        self.spent.lock().unwrap().push_back((unix_now(), weight));
+    }
+}
+
+/// Seconds until `weight` more fits into `budget`, dropping expired entries.
+/// A request heavier than the whole budget waits for the window to empty.
+pub fn wait_secs(spent: &mut VecDeque<(u64, u64)>, weight: u64, budget: u64, now: u64) -> u64 {
+    while spent.front().is_some_and(|(at, _)| at + WINDOW_SECS <= now) {
+        spent.pop_front();
+    }
+    let mut used: u64 = spent.iter().map(|(_, w)| w).sum();
+    for (at, spent_weight) in spent.iter() {
+        if used + weight <= budget {
+            break;
+        }
+        used -= spent_weight;
+        if used + weight <= budget || used == 0 {
+            return at + WINDOW_SECS - now;
+        }
+    }
+    0
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}